@@ -0,0 +1,74 @@
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+use std::f32::consts::PI;
+use crate::config::BackgroundConfig;
+use crate::error::Result;
+use crate::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mapping {
+    // Pinned behind the frame, filling it without stretching and cropping what does not fit;
+    // reflections keep showing the sky.
+    Screen,
+    // Wrapped around the scene as an equirectangular panorama, seen in reflections too.
+    Spherical,
+}
+
+// A photo or painted backdrop shown wherever rays miss the scene, in place of the sky, so a
+// diorama can be rendered straight over a real background. It is only seen: the sky still
+// lights the scene.
+pub struct Background {
+    pub mapping: Mapping,
+    image: Texture,
+    // Turns a spherical plate about the vertical, in radians.
+    rotation: f32,
+}
+
+impl Background {
+    // None when no image is configured.
+    pub fn load(config: &BackgroundConfig) -> Result<Option<Self>> {
+        let Some(path) = &config.image else {
+            return Ok(None);
+        };
+        Ok(Some(Background {
+            mapping: config.mapping,
+            image: Texture::new(path)?,
+            rotation: config.rotation.to_radians(),
+        }))
+    }
+
+    // The plate at a point of the frame, `u` and `v` running from 0 to 1 left to right and
+    // top to bottom, for screen plates.
+    pub fn at_screen(&self, u: f32, v: f32, aspect: f32) -> Option<Vec3> {
+        if self.mapping != Mapping::Screen {
+            return None;
+        }
+        // Scale the image to cover the frame, centred.
+        let image_aspect = self.image.width() as f32 / self.image.height() as f32;
+        let (u, v) = if image_aspect > aspect {
+            (0.5 + (u - 0.5) * aspect / image_aspect, v)
+        } else {
+            (u, 0.5 + (v - 0.5) * image_aspect / aspect)
+        };
+        Some(self.sample(u, v))
+    }
+
+    // The plate seen along `direction`, for spherical plates.
+    pub fn along(&self, direction: &Vec3) -> Option<Vec3> {
+        if self.mapping != Mapping::Spherical {
+            return None;
+        }
+        // The middle of the panorama lies along -z, where the default camera looks.
+        let azimuth = direction.x.atan2(-direction.z) + self.rotation;
+        let u = (azimuth / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+        Some(self.sample(u, v))
+    }
+
+    fn sample(&self, u: f32, v: f32) -> Vec3 {
+        // Textures count v from the bottom up; clamp to stay off the opposite edge.
+        let [r, g, b] = self.image.get_color(u.clamp(0.0, 0.9999), (1.0 - v).clamp(0.0, 0.9999));
+        Vec3::new(r as f32, g as f32, b as f32)
+    }
+}
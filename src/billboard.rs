@@ -0,0 +1,108 @@
+use nalgebra_glm::Vec3;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::sync::Arc;
+use crate::color::Color;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::material::Material;
+use crate::texture::Texture;
+
+const ALPHA_CUTOFF: u8 = 128;
+
+// Upright textured quad that turns around the vertical axis to face the camera.
+// Texels below the alpha cutoff are skipped so rays continue to whatever is behind.
+pub struct Billboard {
+    pub center: Vec3,
+    pub width: f32,
+    pub height: f32,
+    pub material: Material,
+    normal: Vec3,
+}
+
+impl Billboard {
+    pub fn new(center: Vec3, width: f32, height: f32, material: Material) -> Self {
+        Billboard {
+            center,
+            width,
+            height,
+            material,
+            normal: Vec3::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn face_towards(&mut self, eye: &Vec3) {
+        let to_eye = Vec3::new(eye.x - self.center.x, 0.0, eye.z - self.center.z);
+        if to_eye.magnitude() > 1e-4 {
+            self.normal = to_eye.normalize();
+        }
+    }
+
+    fn right(&self) -> Vec3 {
+        Vec3::new(0.0, 1.0, 0.0).cross(&self.normal)
+    }
+}
+
+impl RayIntersect for Billboard {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let denom = ray_direction.dot(&self.normal);
+        if denom.abs() < 1e-6 {
+            return Intersect::empty();
+        }
+
+        let distance = (self.center - ray_origin).dot(&self.normal) / denom;
+        if distance < 0.0 {
+            return Intersect::empty();
+        }
+
+        let point = ray_origin + ray_direction * distance;
+        let offset = point - self.center;
+        let u = offset.dot(&self.right()) / self.width + 0.5;
+        let v = offset.y / self.height + 0.5;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return Intersect::empty();
+        }
+
+        if let Some(texture) = &self.material.texture {
+            if texture.get_rgba(u, v)[3] < ALPHA_CUTOFF {
+                return Intersect::empty();
+            }
+        }
+
+        let normal = if denom < 0.0 { self.normal } else { -self.normal };
+        let mut intersect = Intersect::new(point, normal, distance, self.material.clone(), Some((u, v)));
+        intersect.uv_size = self.width.max(self.height);
+        intersect
+    }
+}
+
+// Small procedural flower sprite: a stem with two leaves under a round blossom.
+pub fn flower_texture(petal: Color) -> Arc<Texture> {
+    let size = 64;
+    let stem = Rgba([60, 140, 40, 255]);
+    let [_, r, g, b] = petal.to_hex().to_be_bytes();
+    let blossom = Rgba([r, g, b, 255]);
+    let center = Rgba([250, 220, 60, 255]);
+    let mut image = RgbaImage::from_pixel(size, size, Rgba([60, 140, 40, 0]));
+
+    for y in 0..size {
+        for x in 0..size {
+            let fx = x as f32 - size as f32 / 2.0;
+            let fy = y as f32;
+            let blossom_distance = (fx * fx + (fy - 18.0) * (fy - 18.0)).sqrt();
+            let leaf = |side: f32, height: f32| {
+                let lx = fx * side - 6.0;
+                let ly = fy - height;
+                (lx * lx) / 36.0 + (ly * ly) / 6.0 < 1.0
+            };
+
+            if blossom_distance < 5.0 {
+                image.put_pixel(x, y, center);
+            } else if blossom_distance < 13.0 {
+                image.put_pixel(x, y, blossom);
+            } else if (fx.abs() < 1.8 && fy > 18.0) || leaf(1.0, 44.0) || leaf(-1.0, 52.0) {
+                image.put_pixel(x, y, stem);
+            }
+        }
+    }
+
+    Arc::new(Texture::from_image(DynamicImage::ImageRgba8(image)))
+}
@@ -0,0 +1,130 @@
+use nalgebra_glm::Vec3;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::particles::Splat;
+use crate::rng::Rng;
+
+const BEE_BODY_COLOR: Color = Color::new(240, 200, 40);
+const BEE_STRIPE_COLOR: Color = Color::new(30, 25, 10);
+const BEE_SIZE: f32 = 0.05;
+
+pub struct Bee {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    striped: bool,
+}
+
+// Steering weights for the classic separation/alignment/cohesion rules plus a pull home.
+#[derive(Debug, Clone, Copy)]
+pub struct FlockRules {
+    pub neighbour_radius: f32,
+    pub separation_radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub hive_attraction: f32,
+    pub wander: f32,
+    pub max_speed: f32,
+    pub min_speed: f32,
+}
+
+impl Default for FlockRules {
+    fn default() -> Self {
+        FlockRules {
+            neighbour_radius: 1.0,
+            separation_radius: 0.3,
+            separation: 6.0,
+            alignment: 1.0,
+            cohesion: 0.8,
+            hive_attraction: 1.5,
+            wander: 3.0,
+            max_speed: 1.8,
+            min_speed: 0.6,
+        }
+    }
+}
+
+pub struct Swarm {
+    pub bees: Vec<Bee>,
+    pub hive: Vec3,
+    pub rules: FlockRules,
+    rng: Rng,
+}
+
+impl Swarm {
+    pub fn new(hive: Vec3, count: usize, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let bees = (0..count)
+            .map(|i| {
+                let offset = Vec3::new(rng.range(-1.0, 1.0), rng.range(-0.5, 0.5), rng.range(-1.0, 1.0));
+                let velocity = Vec3::new(rng.range(-1.0, 1.0), 0.0, rng.range(-1.0, 1.0));
+                Bee { position: hive + offset, velocity, striped: i % 3 == 0 }
+            })
+            .collect();
+        Swarm { bees, hive, rules: FlockRules::default(), rng }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let rules = self.rules;
+        let steering: Vec<Vec3> = (0..self.bees.len()).map(|i| self.steer(i, &rules)).collect();
+
+        for (bee, force) in self.bees.iter_mut().zip(steering) {
+            let jitter = Vec3::new(self.rng.range(-1.0, 1.0), self.rng.range(-1.0, 1.0), self.rng.range(-1.0, 1.0));
+            bee.velocity += (force + jitter * rules.wander) * dt;
+
+            let speed = bee.velocity.magnitude();
+            if speed > rules.max_speed {
+                bee.velocity *= rules.max_speed / speed;
+            } else if speed < rules.min_speed && speed > 0.0 {
+                bee.velocity *= rules.min_speed / speed;
+            }
+            bee.position += bee.velocity * dt;
+        }
+    }
+
+    fn steer(&self, index: usize, rules: &FlockRules) -> Vec3 {
+        let bee = &self.bees[index];
+        let mut separation = Vec3::zeros();
+        let mut heading = Vec3::zeros();
+        let mut centre = Vec3::zeros();
+        let mut neighbours = 0;
+
+        for (j, other) in self.bees.iter().enumerate() {
+            if j == index {
+                continue;
+            }
+            let offset = bee.position - other.position;
+            let distance = offset.magnitude();
+            if distance > rules.neighbour_radius {
+                continue;
+            }
+            if distance < rules.separation_radius && distance > 0.0 {
+                separation += offset / (distance * distance);
+            }
+            heading += other.velocity;
+            centre += other.position;
+            neighbours += 1;
+        }
+
+        let mut force = separation * rules.separation;
+        if neighbours > 0 {
+            let count = neighbours as f32;
+            force += (heading / count - bee.velocity) * rules.alignment;
+            force += (centre / count - bee.position) * rules.cohesion;
+        }
+        force + (self.hive - bee.position) * rules.hive_attraction
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, camera: &Camera, occluded: impl Fn(&Vec3) -> bool) {
+        for bee in &self.bees {
+            let splat = Splat {
+                position: bee.position,
+                size: BEE_SIZE,
+                color: if bee.striped { BEE_STRIPE_COLOR } else { BEE_BODY_COLOR },
+                alpha: 1.0,
+            };
+            splat.draw(framebuffer, camera, &occluded);
+        }
+    }
+}
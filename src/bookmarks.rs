@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::camera::{Camera, CameraPose};
+use crate::error::{Error, Result};
+
+// A saved viewpoint: where the camera was, what it looked at, and the time of day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub slot: u8,
+    pub eye: [f32; 3],
+    pub center: [f32; 3],
+    pub up: [f32; 3],
+    pub fov_degrees: f32,
+    pub time_of_day: f32,
+}
+
+impl Bookmark {
+    pub fn capture(slot: u8, camera: &Camera, time_of_day: f32) -> Self {
+        Bookmark {
+            slot,
+            eye: camera.eye.into(),
+            center: camera.center.into(),
+            up: camera.up.into(),
+            fov_degrees: camera.projection.fov.to_degrees(),
+            time_of_day,
+        }
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            eye: self.eye.into(),
+            center: self.center.into(),
+            up: self.up.into(),
+            fov: self.fov_degrees.to_radians(),
+        }
+    }
+}
+
+// Numbered bookmarks kept in a small TOML file as a list of [[bookmark]] tables.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Bookmarks {
+    #[serde(default, rename = "bookmark")]
+    list: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn load(filename: &str) -> Result<Self> {
+        let path = Path::new(filename);
+        if !path.is_file() {
+            return Ok(Bookmarks::default());
+        }
+
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| Error::ConfigIo { path: filename.to_string(), source })?;
+        let bookmarks: Bookmarks = toml::from_str(&text)
+            .map_err(|source| Error::Config { path: filename.to_string(), source })?;
+        log::info!("Loaded {} camera bookmarks from {}", bookmarks.list.len(), filename);
+        Ok(bookmarks)
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let text = toml::to_string(self).expect("bookmarks are plain data");
+        std::fs::write(filename, text).map_err(|source| Error::Export { path: filename.to_string(), source })
+    }
+
+    pub fn get(&self, slot: u8) -> Option<&Bookmark> {
+        self.list.iter().find(|bookmark| bookmark.slot == slot)
+    }
+
+    // Replaces whatever was stored in the same slot.
+    pub fn set(&mut self, bookmark: Bookmark) {
+        self.list.retain(|existing| existing.slot != bookmark.slot);
+        self.list.push(bookmark);
+        self.list.sort_by_key(|bookmark| bookmark.slot);
+    }
+}
@@ -0,0 +1,75 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use crate::cube::Cube;
+use crate::material::Material;
+use crate::obb::Obb;
+use crate::scene::Object;
+
+const WALL_THICKNESS: f32 = 0.05;
+
+// Blocks hung under the edge of the world, from each edge column's lowest block down to
+// `depth` blocks below the lowest layer anywhere, so the ground reads as solid from the
+// side instead of a slab of floating columns. Only columns missing a neighbour get one;
+// the inside is never seen.
+pub fn skirt(objects: &[Object], depth: usize, material: &Material) -> Vec<Cube> {
+    let mut lowest: HashMap<[i32; 2], i32> = HashMap::new();
+    for object in objects {
+        if let Object::Cube(cube, false) = object {
+            if let Some([x, y, z]) = cube.grid_cell() {
+                let bottom = lowest.entry([x, z]).or_insert(y);
+                *bottom = (*bottom).min(y);
+            }
+        }
+    }
+    let Some(floor) = lowest.values().min().map(|bottom| bottom - depth as i32) else {
+        return Vec::new();
+    };
+
+    let mut cubes = Vec::new();
+    for (&[x, z], &bottom) in &lowest {
+        let edge = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .iter()
+            .any(|(dx, dz)| !lowest.contains_key(&[x + dx, z + dz]));
+        if !edge {
+            continue;
+        }
+        for y in floor..bottom {
+            let center = Vec3::new(x as f32, y as f32, z as f32);
+            cubes.push(Cube { center, size: 1.0, material: material.clone() });
+        }
+    }
+    // Columns come out of the map in no particular order; keep the scene the same every run.
+    cubes.sort_by(|a, b| (a.center.x, a.center.z, a.center.y).partial_cmp(&(b.center.x, b.center.z, b.center.y)).unwrap());
+    cubes
+}
+
+// Four thin panels around the blocks' footprint, from the bottom of the lowest block to
+// `height` above the top of the highest.
+pub fn walls(objects: &[Object], height: f32, material: &Material) -> Vec<Obb> {
+    let (min, max) = objects
+        .iter()
+        .filter_map(|object| match object {
+            Object::Cube(cube, false) => Some(cube),
+            _ => None,
+        })
+        .fold((Vec3::repeat(f32::INFINITY), Vec3::repeat(f32::NEG_INFINITY)), |(min, max), cube| {
+            let half = Vec3::repeat(cube.size / 2.0);
+            (min.inf(&(cube.center - half)), max.sup(&(cube.center + half)))
+        });
+    if min.x > max.x {
+        return Vec::new();
+    }
+
+    let top = max.y + height;
+    let middle = (min + max) / 2.0;
+    let center_y = (min.y + top) / 2.0;
+    let half_height = (top - min.y) / 2.0;
+    let (half_x, half_z) = ((max.x - min.x) / 2.0, (max.z - min.z) / 2.0);
+    let panel = |x: f32, z: f32, half_extents: Vec3| Obb::new(Vec3::new(x, center_y, z), half_extents, 0.0, material.clone());
+    vec![
+        panel(min.x - WALL_THICKNESS, middle.z, Vec3::new(WALL_THICKNESS, half_height, half_z)),
+        panel(max.x + WALL_THICKNESS, middle.z, Vec3::new(WALL_THICKNESS, half_height, half_z)),
+        panel(middle.x, min.z - WALL_THICKNESS, Vec3::new(half_x, half_height, WALL_THICKNESS)),
+        panel(middle.x, max.z + WALL_THICKNESS, Vec3::new(half_x, half_height, WALL_THICKNESS)),
+    ]
+}
@@ -1,11 +1,42 @@
-use nalgebra_glm::Vec3;
-use std::f32::consts::PI;
+use nalgebra_glm::{Vec3, quat_angle_axis, quat_rotate_vec3};
+use crate::projection::{Projection, Ray};
+
+// Everything that defines a view, so it can be stored and blended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub eye: Vec3,
+    pub center: Vec3,
+    pub up: Vec3,
+    pub fov: f32,
+}
+
+impl CameraPose {
+    pub fn lerp(&self, other: &CameraPose, t: f32) -> CameraPose {
+        CameraPose {
+            eye: self.eye.lerp(&other.eye, t),
+            center: self.center.lerp(&other.center, t),
+            up: self.up.lerp(&other.up, t),
+            fov: self.fov + (other.fov - self.fov) * t,
+        }
+    }
+}
+
+struct Transition {
+    from: CameraPose,
+    to: CameraPose,
+    elapsed: f32,
+    duration: f32,
+}
 
 pub struct Camera {
     pub eye: Vec3,
     pub center: Vec3,
     pub up: Vec3,
     pub speed: f32, 
+    // Multiplier on `speed` for the current step, e.g. while sprinting.
+    pub speed_scale: f32,
+    pub projection: Projection,
+    transition: Option<Transition>,
 }
 
 impl Camera {
@@ -15,61 +46,171 @@ impl Camera {
             center,
             up,
             speed: 0.1, 
+            speed_scale: 1.0,
+            projection: Projection::default(),
+            transition: None,
+        }
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose { eye: self.eye, center: self.center, up: self.up, fov: self.projection.fov }
+    }
+
+    pub fn set_pose(&mut self, pose: &CameraPose) {
+        self.transition = None;
+        self.apply_pose(pose);
+    }
+
+    fn apply_pose(&mut self, pose: &CameraPose) {
+        self.eye = pose.eye;
+        self.center = pose.center;
+        self.up = pose.up;
+        self.projection.fov = pose.fov;
+    }
+
+    // Eases from the current view to `pose` over `duration` seconds; zero snaps immediately.
+    pub fn transition_to(&mut self, pose: CameraPose, duration: f32) {
+        if duration <= 0.0 {
+            self.set_pose(&pose);
+            return;
+        }
+        self.transition = Some(Transition {
+            from: self.pose(),
+            to: pose,
+            elapsed: 0.0,
+            duration,
+        });
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+        transition.elapsed += dt;
+        let t = (transition.elapsed / transition.duration).min(1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+        let pose = transition.from.lerp(&transition.to, eased);
+        if t >= 1.0 {
+            self.transition = None;
         }
+        self.apply_pose(&pose);
     }
 
-    pub fn base_change(&self, vector: &Vec3) -> Vec3 {
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        self.projection.width = width;
+        self.projection.height = height;
+    }
+
+    // World-space primary ray through pixel position (x, y) of the viewport, offset by
+    // `jitter` pixels for supersampling.
+    pub fn generate_ray(&self, x: f32, y: f32, jitter: (f32, f32)) -> Ray {
+        let local = self.projection.local_ray(x, y, jitter);
+        let (forward, right, up) = self.basis();
+        let to_world = |v: Vec3| v.x * right + v.y * up - v.z * forward;
+        Ray {
+            origin: self.eye + to_world(local.origin),
+            direction: to_world(local.direction).normalize(),
+            ..local
+        }
+    }
+
+    // Orthonormal forward, right and up vectors of the view.
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
         let forward = (self.center - self.eye).normalize();
         let right = forward.cross(&self.up).normalize();
         let up = right.cross(&forward).normalize();
-
-        let rotated = vector.x * right + vector.y * up - vector.z * forward;
-
-        rotated.normalize()
+        (forward, right, up)
     }
 
+    // Rotates the eye around the center about the camera's own up and right axes. The up
+    // vector turns with it, so orbiting over the poles never flips or locks.
     pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
-        let radius_vector = self.eye - self.center;
-        let radius = radius_vector.magnitude();
-
-        let current_yaw = radius_vector.z.atan2(radius_vector.x);
-        let radius_xz = (radius_vector.x * radius_vector.x + radius_vector.z * radius_vector.z).sqrt();
-        let current_pitch = (-radius_vector.y).atan2(radius_xz);
+        let center = self.center;
+        self.orbit_around(&center, delta_yaw, delta_pitch);
+    }
 
-        let new_yaw = (current_yaw + delta_yaw) % (2.0 * PI);
-        let new_pitch = (current_pitch + delta_pitch).clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
+    // Same as `orbit`, but around any point: eye and center both swing about `pivot`, so
+    // whatever was on screen keeps its place relative to the pivot.
+    pub fn orbit_around(&mut self, pivot: &Vec3, delta_yaw: f32, delta_pitch: f32) {
+        let (_, right, up) = self.basis();
+        let rotation = quat_angle_axis(-delta_yaw, &up) * quat_angle_axis(delta_pitch, &right);
 
-        let new_eye = self.center + Vec3::new(
-            radius * new_yaw.cos() * new_pitch.cos(),
-            -radius * new_pitch.sin(),
-            radius * new_yaw.sin() * new_pitch.cos()
-        );
+        self.eye = pivot + quat_rotate_vec3(&rotation, &(self.eye - pivot));
+        self.center = pivot + quat_rotate_vec3(&rotation, &(self.center - pivot));
+        self.up = quat_rotate_vec3(&rotation, &up);
+    }
 
-        self.eye = new_eye;
+    // Turns the view about its forward axis; positive angles roll counter-clockwise.
+    pub fn roll(&mut self, angle: f32) {
+        let (forward, _, up) = self.basis();
+        self.up = quat_rotate_vec3(&quat_angle_axis(-angle, &forward), &up);
     }
 
     pub fn move_camera(&mut self, direction: &str) {
-        let forward = (self.center - self.eye).normalize();
-        let right = forward.cross(&self.up).normalize();
+        let (forward, right, up) = self.basis();
+        let step = self.speed * self.speed_scale;
 
         match direction {
             "forward" => {
-                self.eye += forward * self.speed;
-                self.center += forward * self.speed;
+                self.eye += forward * step;
+                self.center += forward * step;
             },
             "backward" => {
-                self.eye -= forward * self.speed;
-                self.center -= forward * self.speed;
+                self.eye -= forward * step;
+                self.center -= forward * step;
             },
             "left" => {
-                self.eye -= right * self.speed;
-                self.center -= right * self.speed;
+                self.eye -= right * step;
+                self.center -= right * step;
             },
             "right" => {
-                self.eye += right * self.speed;
-                self.center += right * self.speed;
+                self.eye += right * step;
+                self.center += right * step;
+            },
+            "up" => {
+                self.eye += up * step;
+                self.center += up * step;
+            },
+            "down" => {
+                self.eye -= up * step;
+                self.center -= up * step;
             },
             _ => {}
         }
     }
+
+    // Viewport position (in pixels) and view depth of a world point, or None if it is
+    // behind the camera or outside the clipping range.
+    pub fn project(&self, point: &Vec3) -> Option<(f32, f32, f32)> {
+        let (forward, right, up) = self.basis();
+        let offset = point - self.eye;
+        let local = Vec3::new(offset.dot(&right), offset.dot(&up), -offset.dot(&forward));
+        self.projection.project_local(&local)
+    }
+}
+
+// Exponentially eases a displayed view towards the camera that input moves around, so key
+// presses start and stop gently. A time constant of zero follows the camera exactly.
+pub struct CameraSmoothing {
+    pub time_constant: f32,
+    view: CameraPose,
+}
+
+impl CameraSmoothing {
+    pub fn new(camera: &Camera, time_constant: f32) -> Self {
+        CameraSmoothing { time_constant, view: camera.pose() }
+    }
+
+    // Steps the view `dt` seconds closer to `target` and returns a camera looking from it.
+    pub fn follow(&mut self, target: &Camera, dt: f32) -> Camera {
+        self.view = if self.time_constant > 0.0 {
+            self.view.lerp(&target.pose(), 1.0 - (-dt / self.time_constant).exp())
+        } else {
+            target.pose()
+        };
+
+        let mut view = Camera::new(self.view.eye, self.view.center, self.view.up);
+        view.projection = Projection { fov: self.view.fov, ..target.projection };
+        view
+    }
 }
@@ -0,0 +1,152 @@
+use nalgebra_glm::Vec3;
+use crate::error::{Error, Result};
+use crate::framebuffer::Rect;
+
+pub const USAGE: &str = "usage: Proyecto3_G [--schem PATH [--schem-at X,Y,Z]] [--export-obj PATH] [--export-vox PATH] [--preset NAME] [--retro PALETTE]
+                   [--timeline PATH [--frames FPS]] [--terminal]
+                   [--from-screenshot PATH [--size WxH] [--samples N] [--crop X,Y,W,H] [--output PATH]
+                    [--checkpoint PATH [--checkpoint-every SECONDS] | --contact-sheet N]]
+                   [--progress-file PATH]";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub schematic: Option<String>,
+    pub schematic_origin: Vec3,
+    pub export_obj: Option<String>,
+    pub export_vox: Option<String>,
+    pub from_screenshot: Option<String>,
+    pub size: Option<(usize, usize)>,
+    // Falls back to the quality preset's samples, then to 1.
+    pub samples: Option<usize>,
+    pub preset: Option<String>,
+    // Palette to cut offline renders down to, over the screenshot's own.
+    pub retro: Option<String>,
+    pub crop: Option<Rect>,
+    pub output: Option<String>,
+    pub checkpoint: Option<String>,
+    pub checkpoint_every: u64,
+    // Number of times of day to render side by side instead of the screenshot's own.
+    pub contact_sheet: Option<usize>,
+    // Timeline to play, or with `frames` to render as a sequence at that many frames per second.
+    pub timeline: Option<String>,
+    pub frames: Option<usize>,
+    // Show the scene in the terminal instead of a window, e.g. over SSH.
+    pub terminal: bool,
+    // JSON file kept up to date with how far an offline render has got.
+    pub progress_file: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            schematic: None,
+            schematic_origin: Vec3::new(-12.0, 1.5, -4.0),
+            export_obj: None,
+            export_vox: None,
+            from_screenshot: None,
+            size: None,
+            samples: None,
+            preset: None,
+            retro: None,
+            crop: None,
+            output: None,
+            checkpoint: None,
+            checkpoint_every: 60,
+            contact_sheet: None,
+            timeline: None,
+            frames: None,
+            terminal: false,
+            progress_file: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| Error::Usage(format!("{} needs a value\n{}", arg, USAGE)));
+            match arg.as_str() {
+                "--terminal" => options.terminal = true,
+                "--schem" => options.schematic = Some(value()?),
+                "--schem-at" => options.schematic_origin = parse_vec3(&value()?)?,
+                "--export-obj" => options.export_obj = Some(value()?),
+                "--export-vox" => options.export_vox = Some(value()?),
+                "--from-screenshot" => options.from_screenshot = Some(value()?),
+                "--size" => options.size = Some(parse_size(&value()?)?),
+                "--samples" => options.samples = Some(parse_count(&value()?)?),
+                "--preset" => options.preset = Some(value()?),
+                "--retro" => options.retro = Some(value()?),
+                "--crop" => options.crop = Some(parse_rect(&value()?)?),
+                "--output" => options.output = Some(value()?),
+                "--checkpoint" => options.checkpoint = Some(value()?),
+                "--checkpoint-every" => options.checkpoint_every = parse_count(&value()?)? as u64,
+                "--contact-sheet" => options.contact_sheet = Some(parse_count(&value()?)?),
+                "--timeline" => options.timeline = Some(value()?),
+                "--frames" => options.frames = Some(parse_count(&value()?)?),
+                "--progress-file" => options.progress_file = Some(value()?),
+                _ => return Err(Error::Usage(format!("unknown argument {}\n{}", arg, USAGE))),
+            }
+        }
+
+        if options.contact_sheet.is_some() && (options.from_screenshot.is_none() || options.checkpoint.is_some()) {
+            return Err(Error::Usage(format!("--contact-sheet needs --from-screenshot and no --checkpoint\n{}", USAGE)));
+        }
+
+        if options.frames.is_some() && (options.timeline.is_none() || options.contact_sheet.is_some() || options.checkpoint.is_some() || options.crop.is_some()) {
+            return Err(Error::Usage(format!("--frames needs --timeline and no --contact-sheet, --checkpoint or --crop\n{}", USAGE)));
+        }
+
+        if options.terminal && (options.from_screenshot.is_some() || options.frames.is_some()) {
+            return Err(Error::Usage(format!("--terminal shows the live scene, not --from-screenshot or --frames renders\n{}", USAGE)));
+        }
+
+        if options.progress_file.is_some() && options.from_screenshot.is_none() && options.frames.is_none() {
+            return Err(Error::Usage(format!("--progress-file needs --from-screenshot or --frames\n{}", USAGE)));
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_vec3(text: &str) -> Result<Vec3> {
+    let parts: Vec<f32> = text
+        .split(',')
+        .map(|part| part.trim().parse::<f32>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| Error::Usage(format!("expected X,Y,Z but got {}", text)))?;
+    match parts[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(Error::Usage(format!("expected X,Y,Z but got {}", text))),
+    }
+}
+
+fn parse_size(text: &str) -> Result<(usize, usize)> {
+    let invalid = || Error::Usage(format!("expected WIDTHxHEIGHT but got {}", text));
+    let (width, height) = text.split_once('x').ok_or_else(invalid)?;
+    let width = parse_count(width).map_err(|_| invalid())?;
+    let height = parse_count(height).map_err(|_| invalid())?;
+    Ok((width, height))
+}
+
+fn parse_count(text: &str) -> Result<usize> {
+    match text.trim().parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(Error::Usage(format!("expected a positive number but got {}", text))),
+    }
+}
+
+fn parse_rect(text: &str) -> Result<Rect> {
+    let invalid = || Error::Usage(format!("expected X,Y,WIDTH,HEIGHT but got {}", text));
+    let parts: Vec<usize> = text
+        .split(',')
+        .map(|part| part.trim().parse::<usize>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| invalid())?;
+    match parts[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Ok(Rect::new(x, y, width, height)),
+        _ => Err(invalid()),
+    }
+}
@@ -0,0 +1,33 @@
+use crate::error::{Error, Result};
+use crate::framebuffer::Framebuffer;
+
+// The system clipboard, opened the first time a frame is copied and kept open afterwards:
+// on X11 and Wayland a copy only lasts while the program that made it holds the clipboard.
+#[derive(Default)]
+pub struct FrameClipboard {
+    #[cfg(feature = "clipboard")]
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl FrameClipboard {
+    #[cfg(feature = "clipboard")]
+    pub fn copy(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+        let clipboard_error = |err: arboard::Error| Error::Clipboard(err.to_string());
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self.clipboard.insert(arboard::Clipboard::new().map_err(clipboard_error)?),
+        };
+        let bytes: Vec<u8> = framebuffer
+            .buffer
+            .iter()
+            .flat_map(|&pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 0xFF])
+            .collect();
+        let image = arboard::ImageData { width: framebuffer.width, height: framebuffer.height, bytes: bytes.into() };
+        clipboard.set_image(image).map_err(clipboard_error)
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    pub fn copy(&mut self, _framebuffer: &Framebuffer) -> Result<()> {
+        Err(Error::Clipboard("this build has no clipboard feature".to_string()))
+    }
+}
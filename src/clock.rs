@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+// Most ticks run in one frame; if frames take longer than this many ticks, the simulation
+// slows down instead of spending ever more of each frame catching up.
+const MAX_CATCH_UP: u32 = 30;
+
+// Steps the simulation in fixed ticks, however long frames take, so the day cycle and
+// everything moving in the scene run at the same pace on slow and fast renders alike.
+pub struct SimulationClock {
+    pub paused: bool,
+    pub ticks: u64,
+    step: Duration,
+    // Frame time not yet simulated, always less than one tick after `advance`.
+    pending: Duration,
+    step_requested: bool,
+}
+
+impl SimulationClock {
+    pub fn new(ticks_per_second: u32) -> Self {
+        SimulationClock {
+            paused: false,
+            ticks: 0,
+            step: Duration::from_secs_f64(1.0 / ticks_per_second.max(1) as f64),
+            pending: Duration::ZERO,
+            step_requested: false,
+        }
+    }
+
+    // Length of one tick in seconds.
+    pub fn dt(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.pending = Duration::ZERO;
+        log::info!("Simulation {} at tick {}", if self.paused { "paused" } else { "resumed" }, self.ticks);
+    }
+
+    pub fn request_step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
+        }
+    }
+
+    // Number of ticks to simulate for a frame that took `elapsed`; while paused that is one
+    // for a pending single step and none otherwise.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let ticks = if self.paused {
+            std::mem::take(&mut self.step_requested) as u32
+        } else {
+            self.pending += elapsed;
+            let due = (self.pending.as_nanos() / self.step.as_nanos()) as u32;
+            self.pending -= self.step * due;
+            if due > MAX_CATCH_UP {
+                log::debug!("Simulation fell {} ticks behind; dropping them", due - MAX_CATCH_UP);
+            }
+            due.min(MAX_CATCH_UP)
+        };
+        self.ticks += ticks as u64;
+        ticks
+    }
+}
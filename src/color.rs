@@ -1,4 +1,5 @@
 use std::fmt;
+use nalgebra_glm::Vec3;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -23,9 +24,37 @@ impl Color {
         Color { r: 0, g: 0, b: 0 }
     }
 
-    pub fn to_hex(&self) -> u32 {
+    pub fn to_hex(self) -> u32 {
         ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
+
+    pub fn from_vec3(v: Vec3) -> Self {
+        Color {
+            r: v.x.clamp(0.0, 255.0) as u8,
+            g: v.y.clamp(0.0, 255.0) as u8,
+            b: v.z.clamp(0.0, 255.0) as u8,
+        }
+    }
+
+    // Rounds to the nearest level after adding `offset`, in levels, to every channel.
+    pub fn from_vec3_dithered(v: Vec3, offset: f32) -> Self {
+        let level = |channel: f32| (channel + offset).round().clamp(0.0, 255.0) as u8;
+        Color { r: level(v.x), g: level(v.y), b: level(v.z) }
+    }
+
+    pub fn to_vec3(self) -> Vec3 {
+        Vec3::new(self.r as f32, self.g as f32, self.b as f32)
+    }
+
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+        }
+    }
 }
 
 use std::ops::Add;
@@ -60,4 +89,4 @@ impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,442 @@
+use serde::Deserialize;
+use std::path::Path;
+use crate::background::Mapping;
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::events::ScheduledEvent;
+use crate::light::LightSwitches;
+use crate::quality::QualityPreset;
+use crate::projection::Lens;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Base seed for everything random in the scene (particles, swarm, weather).
+    pub seed: u64,
+    pub seasons: SeasonsConfig,
+    pub screenshot: ScreenshotConfig,
+    pub camera: CameraConfig,
+    pub display: DisplayConfig,
+    pub render: RenderConfig,
+    pub sun: SunConfig,
+    pub aurora: AuroraConfig,
+    // Structures from the `structures/` library placed in the scene at startup.
+    #[serde(rename = "prefab")]
+    pub prefabs: Vec<PrefabConfig>,
+    // Torches and lanterns lighting the scene besides the sun.
+    #[serde(rename = "light")]
+    pub lights: Vec<LightConfig>,
+    pub scatter: ScatterConfig,
+    pub border: BorderConfig,
+    pub background: BackgroundConfig,
+    // Eclipses and meteor showers at set times of day.
+    #[serde(rename = "event")]
+    pub events: Vec<ScheduledEvent>,
+    // Quality presets of one's own, next to fast, balanced and final.
+    #[serde(rename = "preset")]
+    pub presets: Vec<QualityPreset>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            seed: 7,
+            seasons: SeasonsConfig::default(),
+            screenshot: ScreenshotConfig::default(),
+            camera: CameraConfig::default(),
+            display: DisplayConfig::default(),
+            render: RenderConfig::default(),
+            sun: SunConfig::default(),
+            aurora: AuroraConfig::default(),
+            prefabs: Vec::new(),
+            lights: Vec::new(),
+            scatter: ScatterConfig::default(),
+            border: BorderConfig::default(),
+            background: BackgroundConfig::default(),
+            events: Vec::new(),
+            presets: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrefabConfig {
+    // File name in the structure library, without `.toml`.
+    pub name: String,
+    // Where the structure's lowest corner block goes.
+    pub at: [f32; 3],
+    // Quarter turns about the vertical axis.
+    #[serde(default)]
+    pub turns: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LightConfig {
+    pub position: [f32; 3],
+    #[serde(default = "white")]
+    pub color: [u8; 3],
+    // Brightness up to a block away, falling off with the square of the distance beyond.
+    pub intensity: f32,
+    // `enabled` and `casts_shadows`, both on unless set.
+    #[serde(flatten)]
+    pub switches: LightSwitches,
+}
+
+fn white() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SunConfig {
+    // Seconds a whole day takes at the seasons' usual day length.
+    pub day_length: f32,
+    // Degrees above the horizon the sun reaches at noon.
+    pub max_elevation: f32,
+    // Degrees the sun's daily path leans away from the vertical, much like latitude.
+    pub path_tilt: f32,
+    // The moon's phase when the scene starts (0 new, 0.5 full), and days from one new moon
+    // to the next.
+    pub moon_phase: f32,
+    pub moon_cycle_days: f32,
+}
+
+impl Default for SunConfig {
+    fn default() -> Self {
+        SunConfig {
+            day_length: 21.0,
+            max_elevation: 90.0,
+            path_tilt: 0.0,
+            moon_phase: 0.5,
+            moon_cycle_days: 8.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuroraConfig {
+    // Shows it every night; otherwise only scheduled aurora events do.
+    pub enabled: bool,
+    // Degrees above the horizon it starts at.
+    pub min_elevation: f32,
+    pub brightness: f32,
+    // How fast its curtains ripple; 1 is a slow drift.
+    pub speed: f32,
+    // Its colour at the bottom and towards the top of the sky.
+    pub low_color: [u8; 3],
+    pub high_color: [u8; 3],
+}
+
+impl Default for AuroraConfig {
+    fn default() -> Self {
+        AuroraConfig {
+            enabled: false,
+            min_elevation: 15.0,
+            brightness: 0.8,
+            speed: 1.0,
+            low_color: [90, 255, 160],
+            high_color: [170, 90, 255],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BorderConfig {
+    // Blocks the skirt under the edge of the world reaches below its lowest layer; 0 leaves
+    // the undersides open.
+    pub skirt_depth: usize,
+    // Block name the skirt is made of.
+    pub skirt_block: String,
+    // How far the see-through wall around the world rises above its highest block; 0 is no wall.
+    pub wall_height: f32,
+    pub wall_tint: [u8; 3],
+}
+
+impl Default for BorderConfig {
+    fn default() -> Self {
+        BorderConfig {
+            skirt_depth: 2,
+            skirt_block: String::from("stone"),
+            wall_height: 0.0,
+            wall_tint: [215, 235, 255],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackgroundConfig {
+    // Image shown where rays miss the scene instead of the sky; none keeps the sky.
+    pub image: Option<String>,
+    pub mapping: Mapping,
+    // Degrees a spherical plate is turned about the vertical.
+    pub rotation: f32,
+}
+
+impl Default for BackgroundConfig {
+    fn default() -> Self {
+        BackgroundConfig {
+            image: None,
+            mapping: Mapping::Screen,
+            rotation: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScatterConfig {
+    // Spread decorations over the open ground when the scene is built.
+    pub enabled: bool,
+    #[serde(rename = "biome")]
+    pub biomes: Vec<BiomeConfig>,
+}
+
+impl Default for ScatterConfig {
+    fn default() -> Self {
+        let decoration = |decoration, weight| DecorationConfig { decoration, weight };
+        let structure = |name: &str| Decoration::Structure { name: name.to_string() };
+        ScatterConfig {
+            enabled: false,
+            biomes: vec![
+                BiomeConfig {
+                    surface: String::from("grass"),
+                    spacing: 3.0,
+                    decorations: vec![
+                        decoration(structure("oak_tree"), 1.0),
+                        decoration(structure("tall_tree"), 0.5),
+                        decoration(structure("bush"), 1.0),
+                        decoration(Decoration::Flower { petal: [230, 60, 80] }, 1.5),
+                        decoration(Decoration::Flower { petal: [250, 200, 40] }, 1.5),
+                    ],
+                },
+                BiomeConfig {
+                    surface: String::from("stone"),
+                    spacing: 4.0,
+                    decorations: vec![decoration(structure("rock"), 2.0), decoration(structure("boulder"), 1.0)],
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomeConfig {
+    // Palette block the ground is made of, like "grass" or "sand".
+    pub surface: String,
+    // Least distance between two decorations on this ground, in blocks; smaller is denser.
+    pub spacing: f32,
+    pub decorations: Vec<DecorationConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DecorationConfig {
+    #[serde(flatten)]
+    pub decoration: Decoration,
+    // How often this decoration is picked relative to the others on the same ground.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Decoration {
+    // A structure from the library, standing on the ground by the middle of its lowest layer.
+    Structure { name: String },
+    // A flower billboard with petals of this colour.
+    Flower { petal: [u8; 3] },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotConfig {
+    pub directory: String,
+    // Also write the render metadata as a .json file next to each PNG.
+    pub sidecar_json: bool,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            directory: String::from("screenshots"),
+            sidecar_json: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    // The monitor's resolution, which fullscreen (F11) fills. The window library cannot
+    // look it up, so without it fullscreen assumes 1920x1080.
+    pub fullscreen_size: Option<[usize; 2]>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    // Distance moved per frame by WASD/Space/Ctrl; Shift moves 4x faster, Alt 4x slower.
+    pub speed: f32,
+    // Seconds spent flying to a recalled bookmark; 0 jumps straight there.
+    pub bookmark_transition: f32,
+    // Stop the orbiting camera from passing through cubes and doors.
+    pub collision: bool,
+    // Time constant in seconds for easing the view after the input; 0 turns smoothing off.
+    pub smoothing: f32,
+    pub lens: Lens,
+    // Clipping distances from the eye; geometry outside them is not drawn.
+    pub near: f32,
+    pub far: Option<f32>,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            speed: 0.1,
+            bookmark_transition: 1.5,
+            collision: true,
+            smoothing: 0.0,
+            lens: Lens::Perspective,
+            near: 0.0,
+            far: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    // Sky visibility rays per surface cell for ambient light; more is smoother but slower to
+    // warm up, 0 turns it off.
+    pub sky_samples: usize,
+    // Block-style ambient occlusion in the corners where blocks meet.
+    pub corner_occlusion: bool,
+    // Milliseconds of tracing per frame in time-sliced mode (T).
+    pub frame_budget_ms: f32,
+    // Threads tracing each frame, counting the main one; 0 uses every core.
+    pub threads: usize,
+    // Reflect from low resolution cubemaps while the camera moves instead of tracing rays;
+    // one probe per `probe_spacing` blocks, `probe_resolution` texels along each face.
+    pub reflection_probes: bool,
+    pub probe_spacing: f32,
+    pub probe_resolution: usize,
+    // Megabytes the block textures may take with their mip chains; the largest are halved on
+    // load until they fit. 0 leaves them at full size.
+    pub texture_budget_mb: f32,
+    // Dither the picture as it is rounded to 8 bits per channel, trading bands in the sky for
+    // faint noise.
+    pub dither: bool,
+    // Debug aid: paint pixels whose shading comes out NaN or infinite magenta and log the
+    // rays behind the first few.
+    pub nan_watchdog: bool,
+    // Old hardware palette to cut the picture down to, e.g. "pico8"; none keeps full colour.
+    pub retro: Option<String>,
+    // Quality preset to start with, overriding the settings above; none keeps them.
+    pub preset: Option<String>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig {
+            sky_samples: 16,
+            corner_occlusion: true,
+            frame_budget_ms: 12.0,
+            threads: 0,
+            reflection_probes: true,
+            probe_spacing: 8.0,
+            probe_resolution: 16,
+            texture_budget_mb: 0.0,
+            dither: true,
+            nan_watchdog: false,
+            retro: None,
+            preset: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SeasonsConfig {
+    pub days_per_season: f32,
+    pub blend_days: f32,
+    #[serde(rename = "season")]
+    pub list: Vec<SeasonConfig>,
+}
+
+impl Default for SeasonsConfig {
+    fn default() -> Self {
+        SeasonsConfig {
+            days_per_season: 8.0,
+            blend_days: 2.0,
+            list: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SeasonConfig {
+    pub name: String,
+    pub leaf_tint: [u8; 3],
+    pub grass_tint: [u8; 3],
+    pub snow_cover: bool,
+    // Multiplier on the length of a full day; above 1 the sun moves slower.
+    pub day_length: f32,
+    pub weather: WeatherBias,
+}
+
+impl Default for SeasonConfig {
+    fn default() -> Self {
+        SeasonConfig {
+            name: String::from("season"),
+            leaf_tint: [255, 255, 255],
+            grass_tint: [255, 255, 255],
+            snow_cover: false,
+            day_length: 1.0,
+            weather: WeatherBias::default(),
+        }
+    }
+}
+
+// Relative odds of each kind of weather when a new day starts.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct WeatherBias {
+    pub clear: f32,
+    pub rain: f32,
+    pub snow: f32,
+}
+
+impl Default for WeatherBias {
+    fn default() -> Self {
+        WeatherBias { clear: 1.0, rain: 0.0, snow: 0.0 }
+    }
+}
+
+pub fn tint_color(tint: [u8; 3]) -> Color {
+    Color::new(tint[0], tint[1], tint[2])
+}
+
+impl Config {
+    // A missing file is not an error: every section falls back to its defaults.
+    pub fn load(filename: &str) -> Result<Self> {
+        let path = Path::new(filename);
+        if !path.is_file() {
+            log::warn!("No config at {}, using defaults", filename);
+            return Ok(Config::default());
+        }
+
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| Error::ConfigIo { path: filename.to_string(), source })?;
+        let config = toml::from_str(&text)
+            .map_err(|source| Error::Config { path: filename.to_string(), source })?;
+        log::info!("Loaded config {}", filename);
+        Ok(config)
+    }
+}
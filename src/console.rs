@@ -0,0 +1,92 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use crate::events::{EventKind, ScheduledEvent};
+
+const HELP: &str = "commands: fill BLOCK (the selected box), replace BLOCK BLOCK (everywhere), blocks, \
+stamp STRUCTURE (copy it for Ctrl+V), structures, schedule eclipse|meteors|aurora HOUR HOURS, \
+preset NAME, presets, retro PALETTE|off, memory, watchdog on|off, help";
+
+pub enum Command {
+    Fill(String),
+    Replace(String, String),
+    Blocks,
+    Stamp(String),
+    Structures,
+    Schedule(ScheduledEvent),
+    Preset(String),
+    Presets,
+    Memory,
+    // Whether to flag pixels shaded into NaN or infinity.
+    Watchdog(bool),
+    // None turns the retro palette off.
+    Retro(Option<String>),
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Option<Command>, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => Ok(None),
+            ["fill", block] => Ok(Some(Command::Fill(block.to_string()))),
+            ["replace", from, to] => Ok(Some(Command::Replace(from.to_string(), to.to_string()))),
+            ["blocks"] => Ok(Some(Command::Blocks)),
+            ["stamp", name] => Ok(Some(Command::Stamp(name.to_string()))),
+            ["structures"] => Ok(Some(Command::Structures)),
+            ["schedule", kind, at, duration] => {
+                let kind = EventKind::parse(kind).ok_or_else(|| format!("unknown event {:?}; {}", kind, HELP))?;
+                match (at.parse(), duration.parse()) {
+                    (Ok(at), Ok(duration)) => Ok(Some(Command::Schedule(ScheduledEvent { kind, at, duration, day: None }))),
+                    _ => Err(format!("schedule takes an hour and a duration in hours; {}", HELP)),
+                }
+            }
+            ["preset", name] => Ok(Some(Command::Preset(name.to_string()))),
+            ["presets"] => Ok(Some(Command::Presets)),
+            ["memory"] => Ok(Some(Command::Memory)),
+            ["watchdog", "on"] => Ok(Some(Command::Watchdog(true))),
+            ["watchdog", "off"] => Ok(Some(Command::Watchdog(false))),
+            ["retro", "off"] => Ok(Some(Command::Retro(None))),
+            ["retro", name] => Ok(Some(Command::Retro(Some(name.to_string())))),
+            ["help"] => Err(HELP.to_string()),
+            _ => Err(format!("unknown command {:?}; {}", line.trim(), HELP)),
+        }
+    }
+}
+
+// Commands typed into the terminal the app was started from. Lines are read on a thread of
+// their own, so the window never waits for input.
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::Builder::new()
+            .name(String::from("console"))
+            .spawn(move || {
+                for line in std::io::stdin().lock().lines() {
+                    let Ok(line) = line else { break };
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to start the console thread");
+        Console { lines }
+    }
+
+    // Commands entered since the last call; lines that are not commands are answered in the log.
+    pub fn poll(&self) -> Vec<Command> {
+        self.lines
+            .try_iter()
+            .filter_map(|line| match Command::parse(&line) {
+                Ok(command) => command,
+                Err(message) => {
+                    log::info!("{}", message);
+                    None
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,40 @@
+use crate::camera::Camera;
+use crate::daycycle::DAWN;
+use crate::error::Result;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::progress::{self, Progress};
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+
+// Pixels between neighbouring shots.
+const GUTTER: usize = 4;
+const GUTTER_COLOR: u32 = 0x202020;
+
+// The camera's shot at `count` times of day spread evenly over a whole day from dawn, laid
+// out left to right and top to bottom in a roughly square grid, to compare lighting side by
+// side before committing to a long final render.
+pub fn render(renderer: &Renderer, scene: &mut Scene, camera: &Camera, count: usize, samples: usize, progress: &mut Progress) -> Result<Framebuffer> {
+    let (width, height) = (camera.projection.width, camera.projection.height);
+    let columns = (count as f32).sqrt().ceil() as usize;
+    let rows = count.div_ceil(columns);
+    let mut sheet = Framebuffer::new(columns * (width + GUTTER) - GUTTER, rows * (height + GUTTER) - GUTTER);
+    sheet.set_background_color(GUTTER_COLOR);
+    sheet.clear();
+
+    let mut shot = Framebuffer::new(width, height);
+    for index in 0..count {
+        if progress::interrupted() {
+            break;
+        }
+        let hours = (DAWN + 24.0 * index as f32 / count as f32).rem_euclid(24.0);
+        scene.day.set_time_of_day(hours);
+        scene.follow_day();
+        progress.stage = format!("Shot {}/{} at {:02}:{:02}", index + 1, count, hours as u32, (hours.fract() * 60.0) as u32);
+        shot.clear();
+        progress.render_tiles(renderer, &mut shot, Rect::new(0, 0, width, height), scene, camera, samples)?;
+
+        let (column, row) = (index % columns, index / columns);
+        sheet.blit(&shot, Rect::new(column * (width + GUTTER), row * (height + GUTTER), width, height));
+    }
+    Ok(sheet)
+}
@@ -0,0 +1,77 @@
+use nalgebra_glm::Vec3;
+use std::collections::{HashMap, HashSet};
+use crate::cube::Cube;
+
+// How much a fully enclosed corner darkens ambient light.
+const STRENGTH: f32 = 0.6;
+
+// Ambient occlusion of unit grid blocks, baked once per face corner from which neighbouring
+// cells are solid, the way block games shade their terrain. Shading blends the four corners
+// of the face that was hit, so contact shadows cost no extra rays.
+pub struct CornerOcclusion {
+    // Corner factors in (u, v) order (0, 0), (1, 0), (0, 1), (1, 1); faces with nothing
+    // around them are left out.
+    faces: HashMap<([i32; 3], usize), [f32; 4]>,
+}
+
+impl CornerOcclusion {
+    pub fn bake(solid: &HashSet<[i32; 3]>) -> Self {
+        let mut faces = HashMap::new();
+        for &cell in solid {
+            for (face, normal) in FACE_NORMALS.iter().enumerate() {
+                let outside = offset(cell, normal, 1);
+                if solid.contains(&outside) {
+                    continue;
+                }
+                let (u_axis, v_axis) = Cube::uv_axes(normal);
+                let corner = |du: i32, dv: i32| {
+                    let side_u = solid.contains(&offset(outside, &u_axis, du));
+                    let side_v = solid.contains(&offset(outside, &v_axis, dv));
+                    let diagonal = solid.contains(&offset(offset(outside, &u_axis, du), &v_axis, dv));
+                    let blocked = if side_u && side_v { 3 } else { side_u as u8 + side_v as u8 + diagonal as u8 };
+                    1.0 - STRENGTH * blocked as f32 / 3.0
+                };
+                let corners = [corner(-1, -1), corner(1, -1), corner(-1, 1), corner(1, 1)];
+                if corners.iter().any(|&factor| factor < 1.0) {
+                    faces.insert((cell, face), corners);
+                }
+            }
+        }
+        log::debug!("Baked corner occlusion for {} block faces", faces.len());
+        CornerOcclusion { faces }
+    }
+
+    // Ambient factor at face position `uv` of the block in `cell` facing along `normal`.
+    pub fn factor(&self, cell: [i32; 3], normal: &Vec3, (u, v): (f32, f32)) -> f32 {
+        let Some(&[c00, c10, c01, c11]) = self.faces.get(&(cell, face_of(normal))) else {
+            return 1.0;
+        };
+        let bottom = c00 + (c10 - c00) * u;
+        let top = c01 + (c11 - c01) * u;
+        bottom + (top - bottom) * v
+    }
+}
+
+const FACE_NORMALS: [Vec3; 6] = [
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(-1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.0, -1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0),
+    Vec3::new(0.0, 0.0, -1.0),
+];
+
+// The face a normal points most along, so beveled edges still count as their face.
+fn face_of(normal: &Vec3) -> usize {
+    (0..FACE_NORMALS.len())
+        .max_by(|&a, &b| FACE_NORMALS[a].dot(normal).total_cmp(&FACE_NORMALS[b].dot(normal)))
+        .unwrap_or(0)
+}
+
+fn offset(cell: [i32; 3], direction: &Vec3, steps: i32) -> [i32; 3] {
+    [
+        cell[0] + direction.x as i32 * steps,
+        cell[1] + direction.y as i32 * steps,
+        cell[2] + direction.z as i32 * steps,
+    ]
+}
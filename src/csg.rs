@@ -0,0 +1,186 @@
+use nalgebra_glm::Vec3;
+use crate::cube::Cube;
+use crate::sphere::Sphere;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::material::Material;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Boundary {
+    pub distance: f32,
+    pub normal: Vec3,
+}
+
+// Stretch of the ray that lies inside a solid, with the outward normal at each end.
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub enter: Boundary,
+    pub exit: Boundary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+pub enum Shape {
+    Cube(Cube),
+    Sphere(Sphere),
+    Csg(Box<Csg>),
+}
+
+pub struct Csg {
+    pub op: CsgOp,
+    pub left: Shape,
+    pub right: Shape,
+    pub material: Material,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, left: Shape, right: Shape, material: Material) -> Self {
+        Csg { op, left, right, material }
+    }
+
+    pub fn spans(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Vec<Span> {
+        let left = self.left.spans(ray_origin, ray_direction);
+        let right = self.right.spans(ray_origin, ray_direction);
+        combine(self.op, &left, &right)
+    }
+}
+
+impl Shape {
+    // Spans sorted by distance, covering the whole line (including behind the origin).
+    pub fn spans(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Vec<Span> {
+        match self {
+            Shape::Cube(cube) => cube_span(cube, ray_origin, ray_direction).into_iter().collect(),
+            Shape::Sphere(sphere) => sphere
+                .hit_distances(ray_origin, ray_direction)
+                .map(|(t_near, t_far)| {
+                    let normal_at = |t: f32| (ray_origin + ray_direction * t - sphere.center).normalize();
+                    Span {
+                        enter: Boundary { distance: t_near, normal: normal_at(t_near) },
+                        exit: Boundary { distance: t_far, normal: normal_at(t_far) },
+                    }
+                })
+                .into_iter()
+                .collect(),
+            Shape::Csg(csg) => csg.spans(ray_origin, ray_direction),
+        }
+    }
+}
+
+fn cube_span(cube: &Cube, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Span> {
+    let half_size = cube.size / 2.0;
+    let mut enter = Boundary { distance: f32::NEG_INFINITY, normal: Vec3::zeros() };
+    let mut exit = Boundary { distance: f32::INFINITY, normal: Vec3::zeros() };
+
+    for axis in 0..3 {
+        let min = cube.center[axis] - half_size;
+        let max = cube.center[axis] + half_size;
+        if ray_direction[axis].abs() < 1e-8 {
+            if ray_origin[axis] < min || ray_origin[axis] > max {
+                return None;
+            }
+            continue;
+        }
+
+        let mut near = (min - ray_origin[axis]) / ray_direction[axis];
+        let mut far = (max - ray_origin[axis]) / ray_direction[axis];
+        let mut near_normal = Vec3::zeros();
+        near_normal[axis] = -1.0;
+        let mut far_normal = -near_normal;
+        if near > far {
+            std::mem::swap(&mut near, &mut far);
+            std::mem::swap(&mut near_normal, &mut far_normal);
+        }
+
+        if near > enter.distance {
+            enter = Boundary { distance: near, normal: near_normal };
+        }
+        if far < exit.distance {
+            exit = Boundary { distance: far, normal: far_normal };
+        }
+    }
+
+    (enter.distance <= exit.distance).then_some(Span { enter, exit })
+}
+
+fn flipped(boundary: Boundary) -> Boundary {
+    Boundary { distance: boundary.distance, normal: -boundary.normal }
+}
+
+// Sweeps the boundaries of both span lists in order, tracking whether the ray is inside
+// each operand and emitting spans where the combined solid is occupied.
+fn combine(op: CsgOp, left: &[Span], right: &[Span]) -> Vec<Span> {
+    let mut events: Vec<(Boundary, bool, bool)> = Vec::new();
+    for span in left {
+        events.push((span.enter, true, true));
+        events.push((span.exit, true, false));
+    }
+    for span in right {
+        events.push((span.enter, false, true));
+        events.push((span.exit, false, false));
+    }
+    events.sort_by(|a, b| a.0.distance.total_cmp(&b.0.distance));
+
+    let inside = |in_left: bool, in_right: bool| match op {
+        CsgOp::Union => in_left || in_right,
+        CsgOp::Intersection => in_left && in_right,
+        CsgOp::Difference => in_left && !in_right,
+    };
+
+    let mut spans = Vec::new();
+    let (mut in_left, mut in_right) = (false, false);
+    let mut current: Option<Boundary> = None;
+
+    for (boundary, is_left, entering) in events {
+        let was_inside = inside(in_left, in_right);
+        if is_left {
+            in_left = entering;
+        } else {
+            in_right = entering;
+        }
+        let is_inside = inside(in_left, in_right);
+
+        // Boundaries of a subtracted solid face the other way on the result.
+        let boundary = if op == CsgOp::Difference && !is_left { flipped(boundary) } else { boundary };
+
+        if !was_inside && is_inside {
+            current = Some(boundary);
+        } else if was_inside && !is_inside {
+            if let Some(enter) = current.take() {
+                spans.push(Span { enter, exit: boundary });
+            }
+        }
+    }
+
+    spans
+}
+
+impl RayIntersect for Csg {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let boundary = self
+            .spans(ray_origin, ray_direction)
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .find(|boundary| boundary.distance >= 0.0);
+
+        let Some(boundary) = boundary else {
+            return Intersect::empty();
+        };
+
+        let point = ray_origin + ray_direction * boundary.distance;
+        let normal = boundary.normal;
+        let (u, v) = if normal.x.abs() > 0.5 {
+            (point.z, point.y)
+        } else if normal.y.abs() > 0.5 {
+            (point.x, point.z)
+        } else {
+            (point.x, point.y)
+        };
+        let uv = (u.rem_euclid(1.0), v.rem_euclid(1.0));
+
+        Intersect::new(point, normal, boundary.distance, self.material.clone(), Some(uv))
+    }
+}
@@ -2,6 +2,7 @@ use nalgebra_glm::Vec3;
 use crate::ray_intersect::{Intersect, RayIntersect};
 use crate::material::Material;
 
+#[derive(Debug, Clone)]
 pub struct Cube {
     pub center: Vec3,
     pub size: f32,
@@ -28,6 +29,49 @@ impl Cube {
 
         (u, v)
     }
+
+    // World directions in which u and v grow on a face with this normal, as in `get_uv`.
+    pub fn uv_axes(normal: &Vec3) -> (Vec3, Vec3) {
+        if normal.x.abs() > 0.9 {
+            (Vec3::z(), Vec3::y())
+        } else if normal.y.abs() > 0.9 {
+            (Vec3::x(), Vec3::z())
+        } else {
+            (Vec3::x(), Vec3::y())
+        }
+    }
+
+    // Integer cell of a unit cube centred on the block grid, if it is one.
+    pub fn grid_cell(&self) -> Option<[i32; 3]> {
+        let cell = self.center.map(|c| c.round());
+        let aligned = (self.center - cell).abs().max() < 1e-3 && (self.size - 1.0).abs() < 1e-3;
+        aligned.then(|| [cell.x as i32, cell.y as i32, cell.z as i32])
+    }
+
+    // Whether `point` lies inside the cube grown by `margin` on every side.
+    pub fn contains(&self, point: &Vec3, margin: f32) -> bool {
+        (point - self.center).abs().max() <= self.size / 2.0 + margin
+    }
+
+    fn bevel_normal(&self, point: &Vec3, normal: &Vec3, margin: f32) -> Vec3 {
+        let half_size = self.size / 2.0;
+        let local = (*point - (self.center - Vec3::new(half_size, half_size, half_size))) / self.size;
+        let mut beveled = *normal;
+
+        for axis in 0..3 {
+            if normal[axis].abs() > 0.9 {
+                continue;
+            }
+            let t = local[axis].clamp(0.0, 1.0);
+            if t < margin {
+                beveled[axis] -= 1.0 - t / margin;
+            } else if t > 1.0 - margin {
+                beveled[axis] += 1.0 - (1.0 - t) / margin;
+            }
+        }
+
+        beveled.normalize()
+    }
 }
 
 impl RayIntersect for Cube {
@@ -73,14 +117,16 @@ impl RayIntersect for Cube {
             t_min = t_z_min;
         }
         if t_z_max < t_max {
-            t_max = t_z_max
+            t_max = t_z_max;
         }
 
-        if t_min < 0.0 {
+        // Rays starting inside the cube (e.g. refracted rays) hit its far side on the way out.
+        let distance = if t_min >= 0.0 { t_min } else { t_max };
+        if distance < 0.0 {
             return Intersect::empty();
         }
 
-        let point = ray_origin + ray_direction * t_min;
+        let point = ray_origin + ray_direction * distance;
         let mut normal = Vec3::new(0.0, 0.0, 0.0);
 
         let epsilon = 1e-4;
@@ -99,7 +145,164 @@ impl RayIntersect for Cube {
         }
 
         let uv = self.get_uv(&point, &normal);
-        let distance = t_min;
-        Intersect::new(point, normal, distance, self.material.clone(), Some(uv))
+        if self.material.bevel > 0.0 && normal != Vec3::zeros() {
+            normal = self.bevel_normal(&point, &normal, self.material.bevel);
+        }
+        let mut intersect = Intersect::new(point, normal, distance, self.material.clone(), Some(uv));
+        intersect.uv_size = self.size;
+        intersect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Hits closer than this to an edge, or rays closer to grazing, could fairly go either way.
+    const AMBIGUOUS: f64 = 1e-3;
+
+    // Where a ray enters and leaves the cube's box, found slab by slab in double precision
+    // and with rays parallel to a slab handled apart; None when it misses or the box lies
+    // wholly behind the origin.
+    fn reference(cube: &Cube, origin: &Vec3, direction: &Vec3) -> Option<(f64, f64)> {
+        let half = cube.size as f64 / 2.0;
+        let (mut enter, mut exit) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let (o, d, c) = (origin[axis] as f64, direction[axis] as f64, cube.center[axis] as f64);
+            if d == 0.0 {
+                if (o - c).abs() > half {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((c - half - o) / d, (c + half - o) / d);
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+        (enter <= exit && exit >= 0.0).then_some((enter, exit))
+    }
+
+    fn cube() -> impl Strategy<Value = Cube> {
+        (-10.0f32..10.0, -10.0f32..10.0, -10.0f32..10.0, 0.2f32..4.0)
+            .prop_map(|(x, y, z, size)| Cube { center: Vec3::new(x, y, z), size, material: Material::black() })
+    }
+
+    fn point(range: f32) -> impl Strategy<Value = Vec3> {
+        (-range..range, -range..range, -range..range).prop_map(|(x, y, z)| Vec3::new(x, y, z))
+    }
+
+    // Unit directions, a quarter of them along an axis or in the plane of two.
+    fn direction() -> impl Strategy<Value = Vec3> {
+        let any = (0.0..std::f32::consts::TAU, -1.57f32..1.57)
+            .prop_map(|(azimuth, elevation)| Vec3::new(elevation.cos() * azimuth.cos(), elevation.sin(), elevation.cos() * azimuth.sin()));
+        let flat = (any.clone(), 0..3usize, prop::bool::ANY).prop_filter_map("zero", |(mut direction, axis, both)| {
+            direction[axis] = 0.0;
+            if both {
+                direction[(axis + 1) % 3] = 0.0;
+            }
+            (direction.norm() > 1e-3).then(|| direction.normalize())
+        });
+        prop_oneof![3 => any, 1 => flat]
+    }
+
+    // A box and a ray from somewhere around it, aimed at a point in or near the box so
+    // most of them hit. A quarter of them have one or two components zeroed, to cover
+    // rays parallel to the faces.
+    fn aimed_ray() -> impl Strategy<Value = (Cube, Vec3, Vec3)> {
+        (cube(), point(20.0), point(0.75), 0..12usize, prop::bool::ANY).prop_filter_map("zero", |(cube, origin, aim, flatten, both)| {
+            let mut direction = cube.center + aim * cube.size - origin;
+            if flatten < 3 {
+                direction[flatten] = 0.0;
+                if both {
+                    direction[(flatten + 1) % 3] = 0.0;
+                }
+            }
+            (direction.norm() > 1e-3).then(|| (cube, origin, direction.normalize()))
+        })
+    }
+
+    // Faces of the cube `point` lies on, allowing for rounding.
+    fn faces_at(cube: &Cube, point: &Vec3, tolerance: f64) -> usize {
+        let half = cube.size as f64 / 2.0;
+        (0..3).filter(|&axis| ((point[axis] - cube.center[axis]) as f64).abs() > half - tolerance).count()
+    }
+
+    // Checks a hit against the reference's entry and exit.
+    fn check_hit(cube: &Cube, origin: &Vec3, direction: &Vec3, enter: f64, exit: f64) -> std::result::Result<(), TestCaseError> {
+        let intersect = cube.ray_intersect(origin, direction);
+        prop_assert!(intersect.is_intersecting, "missed a box the reference hits at {}..{}", enter, exit);
+
+        // The nearest crossing ahead: the way in, or the way out for origins inside.
+        let expected = if enter >= 0.0 { enter } else { exit };
+        let scale = 1.0 + expected.abs() + (origin - cube.center).norm() as f64;
+        prop_assert!(intersect.distance >= 0.0);
+        prop_assert!((intersect.distance as f64 - expected).abs() < 1e-4 * scale, "distance {} but expected {}", intersect.distance, expected);
+
+        let point = origin + direction * intersect.distance;
+        prop_assert!((intersect.point - point).norm() < 1e-3);
+        prop_assert!(((intersect.point - cube.center).abs().max() - cube.size / 2.0).abs() < 1e-3, "hit point off the surface");
+
+        // An outward unit normal along the axis of the face hit, facing the ray on the way in
+        // and away from it on the way out.
+        let normal = intersect.normal;
+        prop_assert!((normal.norm() - 1.0).abs() < 1e-4, "normal {:?} is not unit length", normal);
+        prop_assert!((normal.abs().max() - 1.0).abs() < 1e-4, "normal {:?} is not along an axis", normal);
+        prop_assert!(((intersect.point - cube.center).dot(&normal) - cube.size / 2.0).abs() < 1e-3, "normal {:?} is not the face's", normal);
+        if enter >= 0.0 {
+            prop_assert!(normal.dot(direction) < 0.0, "normal {:?} faces away from an entering ray", normal);
+        } else {
+            prop_assert!(normal.dot(direction) > 0.0, "normal {:?} faces an exiting ray", normal);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn matches_the_reference((cube, origin, direction) in aimed_ray()) {
+            match reference(&cube, &origin, &direction) {
+                Some((enter, exit)) => {
+                    let scale = 1.0 + (origin - cube.center).norm() as f64;
+                    prop_assume!(exit - enter > AMBIGUOUS * scale && enter.abs() > AMBIGUOUS * scale);
+                    let crossing = if enter >= 0.0 { enter } else { exit };
+                    prop_assume!(faces_at(&cube, &(origin + direction * crossing as f32), AMBIGUOUS * scale) == 1);
+                    check_hit(&cube, &origin, &direction, enter, exit)?;
+                }
+                None => {
+                    // Skip rays that all but touch the box.
+                    let grown = Cube { size: cube.size * 1.01 + 2e-3, ..cube.clone() };
+                    prop_assume!(reference(&grown, &origin, &direction).is_none());
+                    prop_assert!(!cube.ray_intersect(&origin, &direction).is_intersecting);
+                }
+            }
+        }
+
+        #[test]
+        fn rays_from_inside_hit_the_far_side(cube in cube(), offset in (-0.45f32..0.45, -0.45f32..0.45, -0.45f32..0.45), direction in direction()) {
+            let origin = cube.center + Vec3::new(offset.0, offset.1, offset.2) * cube.size;
+            let (enter, exit) = reference(&cube, &origin, &direction).expect("rays from inside always leave");
+            prop_assert!(enter < 0.0);
+            let scale = 1.0 + cube.size as f64;
+            prop_assume!(faces_at(&cube, &(origin + direction * exit as f32), AMBIGUOUS * scale) == 1);
+            check_hit(&cube, &origin, &direction, enter, exit)?;
+        }
+
+        #[test]
+        fn boxes_behind_the_origin_are_missed(cube in cube(), distance in 0.1f32..10.0, direction in direction()) {
+            // Start past the box, looking straight away from its centre.
+            let origin = cube.center + direction * (cube.size + distance);
+            prop_assert!(!cube.ray_intersect(&origin, &direction).is_intersecting);
+        }
+
+        #[test]
+        fn nothing_ahead_of_the_hit_is_inside((cube, origin, direction) in aimed_ray()) {
+            // Nothing on the ray in front of the reported hit lies inside the box.
+            let intersect = cube.ray_intersect(&origin, &direction);
+            prop_assume!(intersect.is_intersecting && !cube.contains(&origin, 0.0));
+            for step in 1..16 {
+                let before = origin + direction * (intersect.distance * step as f32 / 16.0 - 1e-3).max(0.0);
+                prop_assert!(!cube.contains(&before, -1e-3), "{:?} lies inside the box before the hit", before);
+            }
+        }
     }
 }
@@ -0,0 +1,123 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+
+pub const DAWN: f32 = 6.0;
+pub const NOON: f32 = 12.0;
+pub const DUSK: f32 = 18.0;
+pub const MIDNIGHT: f32 = 0.0;
+
+struct Transition {
+    from: f32,
+    to: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+// Sun angle 0 is dawn on the +x horizon, PI / 2 is noon overhead. A tilted path leans the
+// sun's daily circle towards +z, as at higher latitudes, and lifts it so noon is at the
+// chosen elevation; the sun may then never set, or never rise.
+pub struct DayCycle {
+    pub angle: f32,
+    pub radius: f32,
+    // Radians the daily circle leans away from the vertical.
+    tilt: f32,
+    // Radians the circle sits above the one through the horizon's east and west points.
+    declination: f32,
+    // Where the moon is in its cycle: 0 new, 0.5 full. It moves on with the days.
+    pub moon_phase: f32,
+    moon_cycle_days: f32,
+    transition: Option<Transition>,
+}
+
+impl DayCycle {
+    pub fn new(radius: f32, hours: f32) -> Self {
+        DayCycle {
+            angle: hours_to_angle(hours),
+            radius,
+            tilt: 0.0,
+            declination: 0.0,
+            moon_phase: 0.5,
+            moon_cycle_days: 8.0,
+            transition: None,
+        }
+    }
+
+    // A sun that reaches `max_elevation` degrees at noon on a circle leaning `tilt` degrees.
+    pub fn with_path(mut self, max_elevation: f32, tilt: f32) -> Self {
+        self.tilt = tilt.clamp(0.0, 90.0).to_radians();
+        self.declination = (max_elevation + tilt - 90.0).clamp(-90.0, 90.0).to_radians();
+        self
+    }
+
+    // A moon starting at `phase` that goes through its phases once every `cycle_days` days.
+    pub fn with_moon(mut self, phase: f32, cycle_days: f32) -> Self {
+        self.moon_phase = phase.rem_euclid(1.0);
+        self.moon_cycle_days = cycle_days.max(0.1);
+        self
+    }
+
+    pub fn hours(&self) -> f32 {
+        (self.angle / (2.0 * PI) * 24.0 + DAWN).rem_euclid(24.0)
+    }
+
+    pub fn set_time_of_day(&mut self, hours: f32) {
+        self.transition = None;
+        self.angle = hours_to_angle(hours);
+    }
+
+    pub fn transition_to(&mut self, hours: f32, duration: f32) {
+        let target = hours_to_angle(hours);
+        let mut delta = (target - self.angle).rem_euclid(2.0 * PI);
+        if delta > PI {
+            delta -= 2.0 * PI;
+        }
+        self.transition = Some(Transition {
+            from: self.angle,
+            to: self.angle + delta,
+            elapsed: 0.0,
+            duration: duration.max(1e-3),
+        });
+    }
+
+    // Advances a running transition by `dt` seconds, or the regular cycle by `angle_step`.
+    pub fn update(&mut self, dt: f32, angle_step: f32) {
+        let previous = self.angle;
+        match &mut self.transition {
+            Some(transition) => {
+                transition.elapsed += dt;
+                let t = (transition.elapsed / transition.duration).min(1.0);
+                let eased = t * t * (3.0 - 2.0 * t);
+                self.angle = transition.from + (transition.to - transition.from) * eased;
+                if t >= 1.0 {
+                    self.transition = None;
+                }
+            }
+            None => self.angle += angle_step,
+        }
+        self.angle = self.angle.rem_euclid(2.0 * PI);
+        let turned = (self.angle - previous + PI).rem_euclid(2.0 * PI) - PI;
+        self.moon_phase = (self.moon_phase + turned / (2.0 * PI) / self.moon_cycle_days).rem_euclid(1.0);
+    }
+
+    // Unit vector towards the sun.
+    pub fn sun_direction(&self) -> Vec3 {
+        // The axis the sun circles about, and the direction of noon on the untilted circle.
+        let pole = Vec3::new(0.0, self.tilt.sin(), -self.tilt.cos());
+        let noon = Vec3::new(0.0, self.tilt.cos(), self.tilt.sin());
+        let circle = Vec3::new(self.angle.cos(), 0.0, 0.0) + noon * self.angle.sin();
+        pole * self.declination.sin() + circle * self.declination.cos()
+    }
+
+    // Where the sun is drawn in the sky.
+    pub fn sun_position(&self) -> Vec3 {
+        self.sun_direction() * self.radius
+    }
+}
+
+pub fn moon_illumination(phase: f32) -> f32 {
+    (1.0 - (2.0 * PI * phase).cos()) / 2.0
+}
+
+fn hours_to_angle(hours: f32) -> f32 {
+    ((hours - DAWN) / 24.0 * 2.0 * PI).rem_euclid(2.0 * PI)
+}
@@ -0,0 +1,110 @@
+use minifb::{Window, WindowOptions};
+use crate::config::DisplayConfig;
+use crate::error::Result;
+use crate::framebuffer::Framebuffer;
+
+const TITLE: &str = "Refractor";
+// minifb cannot ask the monitor for its resolution; this is assumed when the config does
+// not give it.
+const DEFAULT_FULLSCREEN_SIZE: (usize, usize) = (1920, 1080);
+
+pub struct Display {
+    pub window: Window,
+    pub fullscreen: bool,
+    windowed_width: usize,
+    windowed_height: usize,
+    fullscreen_size: (usize, usize),
+    output: Framebuffer,
+}
+
+impl Display {
+    pub fn new(width: usize, height: usize, config: &DisplayConfig) -> Result<Self> {
+        let mut window = Window::new(TITLE, width, height, WindowOptions::default())?;
+        window.set_target_fps(0);
+        let fullscreen_size = match config.fullscreen_size {
+            Some([width, height]) => (width.max(1), height.max(1)),
+            None => DEFAULT_FULLSCREEN_SIZE,
+        };
+        Ok(Display {
+            window,
+            fullscreen: false,
+            windowed_width: width,
+            windowed_height: height,
+            fullscreen_size,
+            output: Framebuffer::new(width, height),
+        })
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        if self.fullscreen {
+            self.fullscreen_size
+        } else {
+            (self.windowed_width, self.windowed_height)
+        }
+    }
+
+    // Fullscreen keeps roughly the windowed pixel count so the frame rate stays the same.
+    pub fn render_scale(&self) -> f32 {
+        let (width, height) = self.size();
+        let windowed_pixels = (self.windowed_width * self.windowed_height) as f32;
+        (windowed_pixels / (width * height) as f32).sqrt().min(1.0)
+    }
+
+    pub fn render_size(&self) -> (usize, usize) {
+        let (width, height) = self.size();
+        let scale = self.render_scale();
+        (
+            ((width as f32 * scale) as usize).max(1),
+            ((height as f32 * scale) as usize).max(1),
+        )
+    }
+
+    pub fn toggle_fullscreen(&mut self) -> Result<()> {
+        self.fullscreen = !self.fullscreen;
+        let (width, height) = self.size();
+
+        let options = if self.fullscreen {
+            WindowOptions {
+                borderless: true,
+                title: false,
+                topmost: true,
+                ..WindowOptions::default()
+            }
+        } else {
+            WindowOptions::default()
+        };
+
+        self.window = Window::new(TITLE, width, height, options)?;
+        self.window.set_target_fps(0);
+        if self.fullscreen {
+            self.window.set_position(0, 0);
+        }
+        self.output = Framebuffer::new(width, height);
+        log::info!("Switched to {}x{} {}", width, height, if self.fullscreen { "fullscreen" } else { "windowed" });
+        Ok(())
+    }
+}
+
+// Somewhere finished frames are shown: the window, or the terminal with --terminal.
+pub trait Presenter {
+    // Pixels to render for it to show a frame at its full size.
+    fn render_size(&self) -> (usize, usize);
+    fn present(&mut self, frame: &Framebuffer) -> Result<()>;
+}
+
+impl Presenter for Display {
+    fn render_size(&self) -> (usize, usize) {
+        Display::render_size(self)
+    }
+
+    fn present(&mut self, frame: &Framebuffer) -> Result<()> {
+        if frame.width == self.output.width && frame.height == self.output.height {
+            self.window.update_with_buffer(&frame.buffer, frame.width, frame.height)?;
+        } else {
+            let bounds = self.output.bounds();
+            self.output.blit(frame, bounds);
+            self.window.update_with_buffer(&self.output.buffer, self.output.width, self.output.height)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+
+// 8x8 Bayer matrix: thresholds spread so that any run of neighbouring pixels covers the
+// range evenly, which turns a smooth gradient into a fine regular pattern rather than bands.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// Offset in [-0.5, 0.5) to add, scaled by the step between output levels, before rounding
+// the pixel at (x, y) to the nearest level.
+pub fn ordered(x: usize, y: usize) -> f32 {
+    (BAYER[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5
+}
+
+// `color`, in levels from 0 to 255, as the 8-bit colour for the pixel at (x, y). With
+// `enabled` it is dithered, so a gradient spanning only a few levels shows no bands;
+// otherwise it is cut down to whole levels as shading does.
+pub fn to_color(color: Vec3, x: usize, y: usize, enabled: bool) -> Color {
+    if enabled {
+        Color::from_vec3_dithered(color, ordered(x, y))
+    } else {
+        Color::from_vec3(color)
+    }
+}
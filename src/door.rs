@@ -0,0 +1,82 @@
+use nalgebra_glm::{Vec3, rotate_y_vec3};
+use std::f32::consts::PI;
+use crate::material::Material;
+use crate::obb::Obb;
+use crate::ray_intersect::{Intersect, RayIntersect};
+
+// A door panel hinged on its left edge. `openness` runs from 0 (closed) to 1 (swung 90°).
+pub struct Door {
+    pub hinge: Vec3,
+    pub yaw: f32,
+    pub width: f32,
+    pub height: f32,
+    pub thickness: f32,
+    pub swing_time: f32,
+    pub openness: f32,
+    open: bool,
+    shape: Obb,
+}
+
+impl Door {
+    pub fn new(hinge: Vec3, yaw: f32, width: f32, height: f32, material: Material) -> Self {
+        let mut door = Door {
+            hinge,
+            yaw,
+            width,
+            height,
+            thickness: 0.12,
+            swing_time: 0.4,
+            openness: 0.0,
+            open: false,
+            shape: Obb::new(hinge, Vec3::zeros(), yaw, material),
+        };
+        door.refresh_shape();
+        door
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        log::debug!("Door at {:?} {}", self.hinge, if self.open { "opening" } else { "closing" });
+    }
+
+    pub fn is_animating(&self) -> bool {
+        let target = if self.open { 1.0 } else { 0.0 };
+        self.openness != target
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if !self.is_animating() {
+            return;
+        }
+        let step = dt / self.swing_time.max(1e-3);
+        self.openness = if self.open {
+            (self.openness + step).min(1.0)
+        } else {
+            (self.openness - step).max(0.0)
+        };
+        self.refresh_shape();
+    }
+
+    fn refresh_shape(&mut self) {
+        let t = self.openness;
+        let eased = t * t * (3.0 - 2.0 * t);
+        let yaw = self.yaw + eased * PI / 2.0;
+        let half_extents = Vec3::new(self.width / 2.0, self.height / 2.0, self.thickness / 2.0);
+        let offset = rotate_y_vec3(&Vec3::new(half_extents.x, half_extents.y, 0.0), yaw);
+
+        self.shape.center = self.hinge + offset;
+        self.shape.half_extents = half_extents;
+        self.shape.yaw = yaw;
+    }
+
+    // Current collision volume; follows the panel while it swings.
+    pub fn collider(&self) -> &Obb {
+        &self.shape
+    }
+}
+
+impl RayIntersect for Door {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        self.shape.ray_intersect(ray_origin, ray_direction)
+    }
+}
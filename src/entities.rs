@@ -0,0 +1,92 @@
+use nalgebra_glm::Vec3;
+use crate::boids::Swarm;
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::particles::ParticleSystem;
+use crate::scene::{Object, Scene, solid_cells};
+use crate::thrown::ThrownBlock;
+
+// Everything that moves on its own, kept apart from the blocks, which only change through
+// edits. Each kind has its own store and its own system, all run once per simulation tick
+// by `update`. Doors and thrown blocks are traced like blocks, so they live among the
+// scene's objects and their systems move them there; `settle` then brings the scene's
+// cached lighting up to date once per frame rather than once per tick.
+pub struct Entities {
+    pub particles: ParticleSystem,
+    pub swarm: Swarm,
+    geometry_moved: bool,
+    blocks_landed: bool,
+}
+
+impl Entities {
+    pub fn new(particles: ParticleSystem, swarm: Swarm) -> Self {
+        Entities { particles, swarm, geometry_moved: false, blocks_landed: false }
+    }
+
+    pub fn update(&mut self, objects: &mut [Object], dt: f32) {
+        self.particles.update(dt);
+        self.swarm.update(dt);
+        self.geometry_moved |= animate_doors(objects, dt);
+        let (flying, landed) = fly_thrown_blocks(objects, dt);
+        self.geometry_moved |= flying;
+        self.blocks_landed |= landed;
+    }
+
+    // Launches a block. It goes in after every other object, so the indices edits were
+    // recorded with stay valid, and keeps its place once it lands.
+    pub fn throw(&mut self, objects: &mut Vec<Object>, block: ThrownBlock) {
+        log::debug!("Threw a block from {:?}", block.center());
+        objects.push(Object::Thrown(block));
+    }
+
+    // Drops the lighting cached for where things stood if anything traced has moved since
+    // the last call. Returns whether thrown blocks have landed since, which the caller
+    // handles like blocks being placed.
+    pub fn settle(&mut self, scene: &mut Scene) -> bool {
+        if std::mem::take(&mut self.geometry_moved) {
+            scene.geometry_changed();
+        }
+        std::mem::take(&mut self.blocks_landed)
+    }
+
+    // Sprites drawn over the traced frame, hidden where `occluded` says something is in front.
+    pub fn draw(&self, framebuffer: &mut Framebuffer, camera: &Camera, occluded: impl Fn(&Vec3) -> bool + Copy) {
+        self.particles.draw(framebuffer, camera, occluded);
+        self.swarm.draw(framebuffer, camera, occluded);
+    }
+}
+
+// Swings every door towards its target; whether any of them moved.
+fn animate_doors(objects: &mut [Object], dt: f32) -> bool {
+    let mut moved = false;
+    for object in objects.iter_mut() {
+        if let Object::Door(door) = object {
+            moved |= door.is_animating();
+            door.update(dt);
+        }
+    }
+    moved
+}
+
+// Flies every thrown block and turns the ones that came to rest into blocks where they
+// are; whether any were flying, and whether any landed.
+fn fly_thrown_blocks(objects: &mut [Object], dt: f32) -> (bool, bool) {
+    if !objects.iter().any(|object| matches!(object, Object::Thrown(block) if block.is_flying())) {
+        return (false, false);
+    }
+    let mut solid = solid_cells(objects);
+    let mut landed = false;
+    for object in objects.iter_mut() {
+        let Object::Thrown(block) = object else {
+            continue;
+        };
+        block.update(&solid, dt);
+        if let Some(cube) = block.settled(&solid) {
+            // Another block landing this tick must not take the same cell.
+            solid.extend(cube.grid_cell());
+            *object = Object::Cube(cube, false);
+            landed = true;
+        }
+    }
+    (true, landed)
+}
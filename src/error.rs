@@ -0,0 +1,60 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("texture {name} not found at {path}")]
+    TextureNotFound { name: String, path: String },
+    #[error("failed to load texture {path}: {source}")]
+    Texture {
+        path: String,
+        #[source]
+        source: image::ImageError,
+    },
+    #[error("failed to read config {path}: {source}")]
+    ConfigIo {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid config {path}: {source}")]
+    Config {
+        path: String,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("failed to read {path}: {source}")]
+    ImportIo {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("cannot import {path}: {reason}")]
+    Import { path: String, reason: String },
+    #[error("failed to write {path}: {source}")]
+    Export {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to encode {path}: {source}")]
+    Png {
+        path: String,
+        #[source]
+        source: png::EncodingError,
+    },
+    #[cfg(feature = "export")]
+    #[error("cannot export {path}: {reason}")]
+    Unexportable { path: String, reason: String },
+    #[error("{0}")]
+    Usage(String),
+    #[error("interrupted, after saving what was rendered so far")]
+    Interrupted,
+    #[error("failed to copy to the clipboard: {0}")]
+    Clipboard(String),
+    #[error("failed to draw in the terminal: {0}")]
+    Terminal(#[source] std::io::Error),
+    #[error("failed to create or update the window: {0}")]
+    Window(#[from] minifb::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
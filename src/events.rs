@@ -0,0 +1,80 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    // The sun darkens, and the sky and ambient light with it.
+    Eclipse,
+    // Shooting stars streak across the sky.
+    Meteors,
+    // Curtains of light ripple across the night sky.
+    Aurora,
+}
+
+impl EventKind {
+    pub fn parse(name: &str) -> Option<EventKind> {
+        match name {
+            "eclipse" => Some(EventKind::Eclipse),
+            "meteors" => Some(EventKind::Meteors),
+            "aurora" => Some(EventKind::Aurora),
+            _ => None,
+        }
+    }
+}
+
+// Something that happens at a time of day: every day, or only on `day` (0 being the
+// day the scene starts, counted from midnight).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduledEvent {
+    pub kind: EventKind,
+    // Hour it begins at.
+    pub at: f32,
+    // Hours it lasts.
+    pub duration: f32,
+    #[serde(default)]
+    pub day: Option<u32>,
+}
+
+impl ScheduledEvent {
+    // How far the event is under way on `day` at `hours`: rising from 0 as it begins to 1
+    // halfway through and back to 0 as it ends.
+    fn strength(&self, day: u32, hours: f32) -> f32 {
+        let since = hours - self.at;
+        // An event running past midnight started the day before.
+        let started = if since < 0.0 { day.checked_sub(1) } else { Some(day) };
+        if self.day.is_some_and(|only| started != Some(only)) {
+            return 0.0;
+        }
+        let t = since.rem_euclid(24.0) / self.duration.max(1e-3);
+        if t > 1.0 {
+            return 0.0;
+        }
+        (std::f32::consts::PI * t).sin()
+    }
+}
+
+// The events set up in the config or from the console, checked against the clock.
+#[derive(Default)]
+pub struct EventSchedule {
+    events: Vec<ScheduledEvent>,
+}
+
+impl EventSchedule {
+    pub fn new(events: Vec<ScheduledEvent>) -> Self {
+        EventSchedule { events }
+    }
+
+    pub fn add(&mut self, event: ScheduledEvent) {
+        log::info!("Scheduled {:?} at {:.1}h for {:.1}h", event.kind, event.at, event.duration);
+        self.events.push(event);
+    }
+
+    // How strongly `kind` is under way, from the strongest of its events; 0 when none are.
+    pub fn strength(&self, kind: EventKind, day: u32, hours: f32) -> f32 {
+        self.events
+            .iter()
+            .filter(|event| event.kind == kind)
+            .map(|event| event.strength(day, hours))
+            .fold(0.0, f32::max)
+    }
+}
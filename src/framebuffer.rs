@@ -1,4 +1,46 @@
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn clipped(&self, width: usize, height: usize) -> Rect {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        Rect {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+
+    // The same part of an image resized from `from` to `to` pixels, grown out to whole pixels.
+    pub fn scaled(&self, from: (usize, usize), to: (usize, usize)) -> Rect {
+        let start = |position: usize, from: usize, to: usize| position.saturating_mul(to) / from.max(1);
+        let end = |position: usize, from: usize, to: usize| position.saturating_mul(to).div_ceil(from.max(1));
+        let (x, y) = (start(self.x, from.0, to.0), start(self.y, from.1, to.1));
+        Rect {
+            x,
+            y,
+            width: end(self.x.saturating_add(self.width), from.0, to.0) - x,
+            height: end(self.y.saturating_add(self.height), from.1, to.1) - y,
+        }
+    }
+}
+
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
@@ -37,4 +79,68 @@ impl Framebuffer {
     pub fn set_current_color(&mut self, color: u32) {
         self.current_color = color;
     }
-}
\ No newline at end of file
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<u32> {
+        if x < self.width && y < self.height {
+            Some(self.buffer[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    // Fills the part of `rect` that lies inside the framebuffer with the current color.
+    pub fn fill_rect(&mut self, rect: Rect) {
+        let rect = rect.clipped(self.width, self.height);
+        for y in rect.y..rect.y + rect.height {
+            let row = y * self.width;
+            self.buffer[row + rect.x..row + rect.x + rect.width].fill(self.current_color);
+        }
+    }
+
+    // One-pixel border just inside `rect`, in the current color.
+    pub fn outline_rect(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, 1));
+        self.fill_rect(Rect::new(rect.x, rect.y + rect.height - 1, rect.width, 1));
+        self.fill_rect(Rect::new(rect.x, rect.y, 1, rect.height));
+        self.fill_rect(Rect::new(rect.x + rect.width - 1, rect.y, 1, rect.height));
+    }
+
+    // Draws the whole of `src` into `dst_rect`, scaling with nearest-neighbour sampling.
+    pub fn blit(&mut self, src: &Framebuffer, dst_rect: Rect) {
+        self.blit_region(src, src.bounds(), dst_rect);
+    }
+
+    // Draws `src_rect` of `src` into `dst_rect`, scaling with nearest-neighbour sampling.
+    pub fn blit_region(&mut self, src: &Framebuffer, src_rect: Rect, dst_rect: Rect) {
+        let src_rect = src_rect.clipped(src.width, src.height);
+        if src_rect.is_empty() || dst_rect.is_empty() {
+            return;
+        }
+
+        let clipped = dst_rect.clipped(self.width, self.height);
+        for y in clipped.y..clipped.y + clipped.height {
+            let src_y = src_rect.y + (y - dst_rect.y) * src_rect.height / dst_rect.height;
+            let src_row = src_y * src.width;
+            let dst_row = y * self.width;
+
+            if src_rect.width == dst_rect.width {
+                let src_start = src_row + src_rect.x + (clipped.x - dst_rect.x);
+                self.buffer[dst_row + clipped.x..dst_row + clipped.x + clipped.width]
+                    .copy_from_slice(&src.buffer[src_start..src_start + clipped.width]);
+                continue;
+            }
+
+            for x in clipped.x..clipped.x + clipped.width {
+                let src_x = src_rect.x + (x - dst_rect.x) * src_rect.width / dst_rect.width;
+                self.buffer[dst_row + x] = src.buffer[src_row + src_x];
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::cube::Cube;
+use crate::material::Material;
+use crate::scene::Object;
+
+// Most groups kept for undo; the oldest are forgotten first.
+const MAX_GROUPS: usize = 200;
+
+// A single change to the blocks, with what it takes to revert it.
+#[derive(Clone)]
+pub enum Edit {
+    // `cube` goes in at `index`, moving later objects up by one.
+    Place { index: usize, cube: Cube },
+    // The block at `index` is taken out; `cube` is what was there.
+    Remove { index: usize, cube: Cube },
+    Paint { index: usize, before: Material, after: Material },
+}
+
+impl Edit {
+    fn apply(&self, objects: &mut Vec<Object>) {
+        match self {
+            Edit::Place { index, cube } => {
+                objects.insert(*index, Object::Cube(cube.clone(), false));
+                relink_portals(objects, *index, 1);
+            }
+            Edit::Remove { index, .. } => {
+                objects.remove(*index);
+                relink_portals(objects, *index, -1);
+            }
+            Edit::Paint { index, after, .. } => {
+                if let Object::Cube(cube, false) = &mut objects[*index] {
+                    cube.material = after.clone();
+                }
+            }
+        }
+    }
+
+    fn inverse(&self) -> Edit {
+        match self {
+            Edit::Place { index, cube } => Edit::Remove { index: *index, cube: cube.clone() },
+            Edit::Remove { index, cube } => Edit::Place { index: *index, cube: cube.clone() },
+            Edit::Paint { index, before, after } => Edit::Paint { index: *index, before: after.clone(), after: before.clone() },
+        }
+    }
+
+    // Whether the edit moved blocks rather than just changing how one looks.
+    pub fn is_structural(&self) -> bool {
+        !matches!(self, Edit::Paint { .. })
+    }
+}
+
+// Portals refer to their partner by index; keeps those pointing at the same object after
+// `shift` objects went in (or out, when negative) at `index`.
+fn relink_portals(objects: &mut [Object], index: usize, shift: isize) {
+    for object in objects.iter_mut() {
+        if let Object::Portal(portal) = object {
+            if portal.link >= index {
+                portal.link = portal.link.saturating_add_signed(shift);
+            }
+        }
+    }
+}
+
+// Undo and redo for block edits. Edits made between `begin_group` and `end_group`, like
+// every block touched by one drag, are undone and redone together. All edits go through
+// `apply`, so indices recorded in the history stay valid when they are replayed in order.
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    open: Option<Vec<Edit>>,
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        EditHistory::default()
+    }
+
+    pub fn begin_group(&mut self) {
+        self.end_group();
+        self.open = Some(Vec::new());
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.open.take() {
+            self.push(group);
+        }
+    }
+
+    // Makes the edit and records it, in the open group if there is one.
+    pub fn apply(&mut self, objects: &mut Vec<Object>, edit: Edit) {
+        edit.apply(objects);
+        match &mut self.open {
+            Some(group) => group.push(edit),
+            None => self.push(vec![edit]),
+        }
+    }
+
+    // Makes several edits as one undo step.
+    pub fn apply_group(&mut self, objects: &mut Vec<Object>, edits: Vec<Edit>) {
+        self.begin_group();
+        for edit in edits {
+            self.apply(objects, edit);
+        }
+        self.end_group();
+    }
+
+    // Reverts the last group and returns its edits, or None when there is nothing to undo.
+    pub fn undo(&mut self, objects: &mut Vec<Object>) -> Option<&[Edit]> {
+        self.end_group();
+        let group = self.undo.pop()?;
+        for edit in group.iter().rev() {
+            edit.inverse().apply(objects);
+        }
+        self.redo.push(group);
+        self.redo.last().map(Vec::as_slice)
+    }
+
+    // Makes the last undone group again and returns its edits.
+    pub fn redo(&mut self, objects: &mut Vec<Object>) -> Option<&[Edit]> {
+        self.end_group();
+        let group = self.redo.pop()?;
+        for edit in &group {
+            edit.apply(objects);
+        }
+        self.undo.push(group);
+        self.undo.last().map(Vec::as_slice)
+    }
+
+    fn push(&mut self, group: Vec<Edit>) {
+        if group.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push(group);
+        if self.undo.len() > MAX_GROUPS {
+            self.undo.remove(0);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use nalgebra_glm::Vec3;
+use std::sync::RwLock;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+const REFRESH_ANGLE: f32 = 0.01;
+
+pub type CacheKey = (i32, i32, i32, u8);
+
+// One-bounce diffuse irradiance, cached per small surface cell and face orientation.
+// Bounce light depends on the sun, so the cache is flushed whenever the sun moves enough.
+pub struct IrradianceCache {
+    pub enabled: bool,
+    pub cell_size: f32,
+    pub samples: usize,
+    pub strength: f32,
+    entries: RwLock<HashMap<CacheKey, Vec3>>,
+    sun_direction: Vec3,
+}
+
+impl IrradianceCache {
+    pub fn new(cell_size: f32, samples: usize, strength: f32) -> Self {
+        IrradianceCache {
+            enabled: false,
+            cell_size,
+            samples,
+            strength,
+            entries: RwLock::new(HashMap::new()),
+            sun_direction: Vec3::zeros(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        log::info!("Diffuse global illumination {}", if self.enabled { "enabled" } else { "disabled" });
+    }
+
+    pub fn update(&mut self, sun_direction: &Vec3) {
+        if sun_direction.dot(&self.sun_direction) < REFRESH_ANGLE.cos() {
+            self.clear();
+            self.sun_direction = *sun_direction;
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+        if !entries.is_empty() {
+            log::trace!("Flushing {} irradiance cache entries", entries.len());
+        }
+        entries.clear();
+    }
+
+    pub fn lookup(&self, point: &Vec3, normal: &Vec3, compute: impl FnOnce() -> Vec3) -> Vec3 {
+        let key = self.key(point, normal);
+        if let Some(irradiance) = self.entries.read().unwrap().get(&key) {
+            return *irradiance;
+        }
+        let irradiance = compute();
+        self.entries.write().unwrap().insert(key, irradiance);
+        irradiance
+    }
+
+    fn key(&self, point: &Vec3, normal: &Vec3) -> CacheKey {
+        cache_key(point, normal, self.cell_size)
+    }
+}
+
+// Cell of size `cell_size` holding `point`, told apart by which way the surface faces.
+pub fn cache_key(point: &Vec3, normal: &Vec3, cell_size: f32) -> CacheKey {
+    let cell = point / cell_size;
+    (
+        cell.x.floor() as i32,
+        cell.y.floor() as i32,
+        cell.z.floor() as i32,
+        face_index(normal),
+    )
+}
+
+fn face_index(normal: &Vec3) -> u8 {
+    let abs = normal.abs();
+    let (axis, component) = if abs.x >= abs.y && abs.x >= abs.z {
+        (0, normal.x)
+    } else if abs.y >= abs.z {
+        (1, normal.y)
+    } else {
+        (2, normal.z)
+    };
+    axis * 2 + (component < 0.0) as u8
+}
+
+// Deterministic cosine-weighted directions around `normal` laid out on a Fibonacci spiral.
+pub fn hemisphere_directions(normal: &Vec3, count: usize) -> Vec<Vec3> {
+    let helper = if normal.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+    let tangent = normal.cross(&helper).normalize();
+    let bitangent = normal.cross(&tangent);
+    let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+
+    (0..count)
+        .map(|i| {
+            let u = (i as f32 + 0.5) / count as f32;
+            let radius = u.sqrt();
+            let phi = i as f32 * golden_angle;
+            let local_z = (1.0 - u).sqrt();
+            (tangent * (radius * phi.cos()) + bitangent * (radius * phi.sin()) + normal * local_z).normalize()
+        })
+        .collect()
+}
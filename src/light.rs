@@ -1,11 +1,17 @@
 
 use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 use crate::color::Color;
 
+// A lamp at a point in the scene, like a torch or lantern, whose light falls off with the
+// square of the distance.
+#[derive(Debug, Clone, Copy)]
 pub struct Light {
     pub position: Vec3,
     pub color: Color,
     pub intensity: f32,
+    pub switches: LightSwitches,
 }
 
 impl Light {
@@ -14,6 +20,114 @@ impl Light {
             position,
             color,
             intensity,
+            switches: LightSwitches::default(),
         }
     }
-}
\ No newline at end of file
+}
+
+// Per-light switches, so expensive lights can be turned off or rendered without shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightSwitches {
+    pub enabled: bool,
+    pub casts_shadows: bool,
+}
+
+impl LightSwitches {
+    pub fn toggle(&mut self, name: &str) {
+        self.enabled = !self.enabled;
+        log::info!("{} {}", name, if self.enabled { "on" } else { "off" });
+    }
+
+    pub fn toggle_shadows(&mut self, name: &str) {
+        self.casts_shadows = !self.casts_shadows;
+        log::info!("{} shadows {}", name, if self.casts_shadows { "on" } else { "off" });
+    }
+}
+
+impl Default for LightSwitches {
+    fn default() -> Self {
+        LightSwitches {
+            enabled: true,
+            casts_shadows: true,
+        }
+    }
+}
+
+// A light so far away that its rays arrive parallel, like the sun. `direction` points
+// towards the light; `angular_diameter` (radians) is the size of its disc in the sky,
+// which is what softens the edges of its shadows.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub intensity: f32,
+    pub angular_diameter: f32,
+    pub switches: LightSwitches,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: f32, angular_diameter: f32) -> Self {
+        DirectionalLight {
+            direction: direction.normalize(),
+            intensity,
+            angular_diameter,
+            switches: LightSwitches::default(),
+        }
+    }
+
+    // Direction towards a point on the light's disc, spread uniformly over it by `u` and `v` in [0, 1).
+    pub fn sample_direction(&self, u: f32, v: f32) -> Vec3 {
+        if self.angular_diameter <= 0.0 {
+            return self.direction;
+        }
+        let helper = if self.direction.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+        let tangent = self.direction.cross(&helper).normalize();
+        let bitangent = self.direction.cross(&tangent);
+        let radius = (self.angular_diameter * 0.5).tan() * u.sqrt();
+        let phi = 2.0 * PI * v;
+        (self.direction + tangent * (radius * phi.cos()) + bitangent * (radius * phi.sin())).normalize()
+    }
+}
+
+// Direct sunlight reaching the scene. The sun's intensity is scaled by the cosine of its
+// zenith angle, dimmed by atmospheric extinction over the relative air mass (Kasten-Young),
+// and faded smoothly to zero as the sun crosses the horizon.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SunlightModel {
+    pub exposure: f32,
+    pub zenith_weight: f32,
+    pub extinction: f32,
+    pub horizon_fade: f32,
+}
+
+impl SunlightModel {
+    pub fn intensity(&self, sun: &DirectionalLight) -> f32 {
+        if !sun.switches.enabled {
+            return 0.0;
+        }
+        let cos_zenith = sun.direction.y;
+        let fade = ((cos_zenith + self.horizon_fade) / (2.0 * self.horizon_fade)).clamp(0.0, 1.0);
+        if fade <= 0.0 {
+            return 0.0;
+        }
+        let fade = fade * fade * (3.0 - 2.0 * fade);
+
+        let elevation = cos_zenith.clamp(-1.0, 1.0).asin().to_degrees().max(0.0);
+        let air_mass = 1.0 / (cos_zenith.max(0.0) + 0.50572 * (elevation + 6.07995).powf(-1.6364));
+        let transmittance = (-self.extinction * air_mass).exp();
+
+        let angular = 1.0 - self.zenith_weight + self.zenith_weight * cos_zenith.max(0.0);
+        sun.intensity * self.exposure * angular * transmittance * fade
+    }
+}
+
+impl Default for SunlightModel {
+    fn default() -> Self {
+        SunlightModel {
+            exposure: 1.6,
+            zenith_weight: 0.6,
+            extinction: 0.08,
+            horizon_fade: 0.05,
+        }
+    }
+}
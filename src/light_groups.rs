@@ -0,0 +1,89 @@
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::dither;
+use crate::framebuffer::Framebuffer;
+
+// Lighting split by where it comes from: direct sunlight, the fill that lifts shadows
+// (ambient and bounced light), the sky seen directly or in reflections, and light given off
+// by emissive blocks and lamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightGroup {
+    Sun,
+    Fill,
+    Sky,
+    Glow,
+}
+
+impl LightGroup {
+    pub const ALL: [LightGroup; 4] = [LightGroup::Sun, LightGroup::Fill, LightGroup::Sky, LightGroup::Glow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LightGroup::Sun => "sun",
+            LightGroup::Fill => "fill",
+            LightGroup::Sky => "sky",
+            LightGroup::Glow => "glow",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// A still rendered once per light group. The groups add up to the full image, so their
+// balance can be changed afterwards by scaling each layer, without tracing again.
+pub struct LightLayers {
+    pub scales: [f32; 4],
+    pub selected: LightGroup,
+    layers: Vec<Framebuffer>,
+}
+
+impl LightLayers {
+    // `render` draws the frame lit by a single group into the framebuffer it is given.
+    pub fn new(width: usize, height: usize, mut render: impl FnMut(LightGroup, &mut Framebuffer)) -> Self {
+        let layers = LightGroup::ALL
+            .iter()
+            .map(|&group| {
+                let mut layer = Framebuffer::new(width, height);
+                render(group, &mut layer);
+                layer
+            })
+            .collect();
+        LightLayers {
+            scales: [1.0; 4],
+            selected: LightGroup::Sun,
+            layers,
+        }
+    }
+
+    pub fn layer(&self, group: LightGroup) -> &Framebuffer {
+        &self.layers[group.index()]
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = LightGroup::ALL[(self.selected.index() + 1) % LightGroup::ALL.len()];
+        log::info!("Adjusting the {} layer", self.selected.name());
+    }
+
+    pub fn scale_selected(&mut self, factor: f32) {
+        let scale = &mut self.scales[self.selected.index()];
+        *scale = (*scale * factor).clamp(0.0, 8.0);
+        log::info!(
+            "Light balance: sun {:.2}, fill {:.2}, sky {:.2}, glow {:.2}",
+            self.scales[0], self.scales[1], self.scales[2], self.scales[3],
+        );
+    }
+
+    pub fn composite(&self, framebuffer: &mut Framebuffer, dither: bool) {
+        let width = framebuffer.width;
+        for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+            let sum = self
+                .layers
+                .iter()
+                .zip(self.scales)
+                .fold(Vec3::zeros(), |sum, (layer, scale)| sum + Color::from_hex(layer.buffer[index]).to_vec3() * scale);
+            *pixel = dither::to_color(sum, index % width, index / width, dither).to_hex();
+        }
+    }
+}
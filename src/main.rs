@@ -1,191 +1,473 @@
+
 mod framebuffer;
 mod ray_intersect;
 mod cube;
 mod color;
 mod camera;
+mod projection;
+mod quality;
 mod light;
 mod material;
 mod texture;
-
-use minifb::{Window, WindowOptions, Key};
-use nalgebra_glm::{Vec3, normalize};
-use std::time::Duration;
+mod sky;
+mod error;
+mod display;
+mod pacing;
+mod clock;
+mod daycycle;
+mod settings;
+mod irradiance;
+mod sun_visibility;
+mod sky_occlusion;
+mod corner_occlusion;
+mod light_groups;
+mod reflection_plane;
+mod reflection_probes;
+mod ray_cone;
+mod portal;
+mod sphere;
+mod csg;
+mod billboard;
+mod rng;
+mod particles;
+mod boids;
+mod entities;
+mod events;
+mod thrown;
+mod obb;
+mod door;
+mod config;
+mod season;
+mod snow;
+#[cfg(feature = "schematic")]
+mod nbt;
+mod registry;
+mod palette;
+#[cfg(feature = "schematic")]
+mod schematic;
+mod cli;
+#[cfg(feature = "export")]
+mod obj_export;
+#[cfg(feature = "export")]
+mod vox_export;
+mod screenshot;
+mod progressive;
+mod progress;
+mod memory;
+mod dither;
+mod retro;
+mod terminal;
+mod clipboard;
+mod watchdog;
+mod shading_math;
+mod background;
+mod time_slice;
+mod worker_pool;
+mod renderer;
+mod scene;
+mod contact_sheet;
+mod painting;
+mod history;
+mod structure;
+mod scatter;
+mod border;
+mod console;
+mod bookmarks;
+mod timeline;
+
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode};
+use nalgebra_glm::Vec3;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::f32::consts::PI;
 use crate::color::Color;
-use crate::ray_intersect::{Intersect, RayIntersect};
 use crate::cube::Cube;
-use crate::framebuffer::Framebuffer;
-use crate::camera::Camera;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::camera::{Camera, CameraSmoothing};
 use crate::material::Material;
-use crate::texture::Texture;
-use std::rc::Rc;
-
-const ORIGIN_BIAS: f32 = 1e-4;
-const DAY_SKY_COLOR: Color = Color::new(68, 142, 228);
-const NIGHT_SKY_COLOR: Color = Color::new(10, 10, 30);
-
-fn offset_origin(intersect: &Intersect, direction: &Vec3) -> Vec3 {
-    let offset = intersect.normal * ORIGIN_BIAS;
-    if direction.dot(&intersect.normal) < 0.0 {
-        intersect.point - offset
-    } else {
-        intersect.point + offset
-    }
+use crate::texture::{Texture, TextureWatcher};
+use crate::error::{Error, Result};
+use crate::display::{Display, Presenter};
+use crate::terminal::TerminalPresenter;
+use crate::clipboard::FrameClipboard;
+use crate::pacing::{FramePacer, FrameRate};
+use crate::clock::SimulationClock;
+use crate::daycycle::DayCycle;
+use crate::settings::{RenderSettings, ShadowBias};
+use crate::light::Light;
+use crate::light_groups::LightLayers;
+use crate::portal::Portal;
+use crate::sphere::Sphere;
+use crate::csg::{Csg, CsgOp, Shape};
+use crate::billboard::{Billboard, flower_texture};
+use crate::particles::{Emitter, EmitterKind, ParticleSystem};
+use crate::boids::Swarm;
+use crate::entities::Entities;
+use crate::events::{EventKind, EventSchedule};
+use crate::thrown::ThrownBlock;
+use crate::door::Door;
+use crate::config::{Config, Decoration, LightConfig, tint_color};
+use crate::season::{SeasonClock, SeasonState, Weather, snow_covered};
+use crate::snow::SnowCover;
+use crate::palette::Palette;
+use crate::painting::{Painter, cell_against};
+use crate::history::{Edit, EditHistory};
+use crate::scatter::scatter;
+use crate::border::{skirt, walls};
+use crate::structure::{Selection, Structure, StructureLibrary};
+use crate::console::{Command, Console};
+use crate::reflection_plane::ReflectionPlane;
+use crate::reflection_probes::ReflectionProbes;
+use crate::quality::QualityPreset;
+#[cfg(feature = "schematic")]
+use crate::schematic::Schematic;
+use crate::cli::Options;
+use crate::registry::MaterialRegistry;
+#[cfg(feature = "export")]
+use crate::obj_export::export_obj;
+#[cfg(feature = "export")]
+use crate::vox_export::export_vox;
+use crate::screenshot::{RenderMetadata, metadata_json, read_metadata, save_light_layers, save_png, take_screenshot};
+use crate::progressive::ProgressiveRender;
+use crate::progress::Progress;
+use crate::retro::Retro;
+use crate::time_slice::TimeSlicer;
+use crate::renderer::{Motion, Renderer};
+use crate::sky::Aurora;
+use crate::background::Background;
+use crate::scene::{Object, Scene, closest_intersect, exposed_tops, water_level};
+use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::timeline::{Recorder, Timeline, TimelineState};
+use std::sync::Arc;
+use std::path::Path;
+
+const BOOKMARKS_FILE: &str = "bookmarks.toml";
+const STRUCTURES_DIRECTORY: &str = "structures";
+const BOOKMARK_KEYS: [Key; 8] = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8];
+const CAMERA_CLEARANCE: f32 = 0.15;
+const DRAG_ORBIT_SPEED: f32 = 0.01;
+const SPRINT_SCALE: f32 = 4.0;
+const SLOW_SCALE: f32 = 0.25;
+const MOTION_BLUR_SAMPLES: usize = 4;
+const CROP_SAMPLES: usize = 4;
+const MARQUEE_COLOR: u32 = 0xFFD23F;
+const REFLECTION_PLANE_RESOLUTION: f32 = 0.5;
+const TIME_SLICE_TILE: usize = 16;
+// Simulation ticks per second, whatever the frame rate.
+const SIMULATION_RATE: u32 = 60;
+// A thrown block leaves the eye at this speed along the view, plus a little upwards, and
+// spins about the vertical in radians per second.
+const THROW_SPEED: f32 = 10.0;
+const THROW_LIFT: f32 = 2.0;
+const THROW_SPIN: f32 = 6.0;
+// Shooting stars per second at the height of a meteor shower.
+const METEOR_RATE: f32 = 6.0;
+// Seconds between the keys of a recorded timeline.
+const TIMELINE_KEY_INTERVAL: f32 = 0.25;
+
+fn marquee_rect(start: (usize, usize), end: (usize, usize)) -> Rect {
+    let (x, y) = (start.0.min(end.0), start.1.min(end.1));
+    Rect::new(x, y, start.0.max(end.0) - x + 1, start.1.max(end.1) - y + 1)
 }
 
-fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
-    incident - 2.0 * incident.dot(normal) * normal
+// Mouse position in framebuffer pixels, which differ from window pixels when the render is scaled.
+fn mouse_position(display: &Display, framebuffer: &Framebuffer) -> Option<(f32, f32)> {
+    let (mouse_x, mouse_y) = display.window.get_mouse_pos(MouseMode::Clamp)?;
+    let (display_width, display_height) = display.size();
+    Some((
+        mouse_x * framebuffer.width as f32 / display_width as f32,
+        mouse_y * framebuffer.height as f32 / display_height as f32,
+    ))
 }
 
-fn cast_shadow(
-    intersect: &Intersect,
-    light_position: &Vec3,
-    objects: &[Object],
-) -> f32 {
-    let light_dir = (light_position - intersect.point).normalize();
-    let light_distance = (light_position - intersect.point).magnitude();
-    let shadow_ray_origin = offset_origin(intersect, &light_dir);
-    let mut shadow_intensity = 0.0;
-
-    for object in objects {
-        let shadow_intersect = match object {
-            Object::Cube(cube, _) => cube.ray_intersect(&shadow_ray_origin, &light_dir),
-        };
-        if shadow_intersect.is_intersecting && shadow_intersect.distance < light_distance {
-            let distance_ratio = shadow_intersect.distance / light_distance;
-            shadow_intensity = 1.0 - distance_ratio.powf(2.0).min(1.0);
-            break;
+// Keeps an orbiting camera out of solid geometry by sliding the eye away from the target,
+// along the view ray, until it clears every cube and door by `CAMERA_CLEARANCE`.
+fn push_camera_out(camera: &mut Camera, objects: &[Object]) {
+    let blocked = |point: &Vec3| {
+        objects.iter().any(|object| match object {
+            Object::Cube(cube, false) => cube.contains(point, CAMERA_CLEARANCE),
+            Object::Door(door) => door.collider().contains(point, CAMERA_CLEARANCE),
+            _ => false,
+        })
+    };
+
+    let outward = (camera.eye - camera.center).normalize();
+    for _ in 0..400 {
+        if !blocked(&camera.eye) {
+            return;
         }
+        camera.eye += outward * 0.05;
     }
-
-    shadow_intensity
 }
 
-enum Object {
-    Cube(Cube, bool),
+// Index of the object under viewport position (x, y), and the point hit.
+fn pick(objects: &[Object], camera: &Camera, x: f32, y: f32) -> Option<(usize, Vec3)> {
+    let ray = camera.generate_ray(x, y, (0.0, 0.0));
+    let (intersect, hit) = closest_intersect(&ray.origin, &ray.direction, objects);
+    let hit = hit?;
+    let index = objects.iter().position(|object| std::ptr::eq(object, hit))?;
+    Some((index, intersect.point))
 }
 
-fn adjust_sky_color(sun_position: &Vec3) -> Color {
-    if sun_position.y > 0.0 {
-        DAY_SKY_COLOR
-    } else {
-        NIGHT_SKY_COLOR
+fn apply_season(objects: &mut [Object], state: &SeasonState, leaves: &Arc<Texture>, grass: &Arc<Texture>, snowy_grass: &Arc<Texture>) {
+    for object in objects.iter_mut() {
+        let Object::Cube(cube, _) = object else {
+            continue;
+        };
+        let material = &mut cube.material;
+        let Some(texture) = &material.texture else {
+            continue;
+        };
+
+        if Arc::ptr_eq(texture, leaves) {
+            material.tint = state.leaf_tint;
+        } else if Arc::ptr_eq(texture, grass) || Arc::ptr_eq(texture, snowy_grass) {
+            material.tint = state.grass_tint;
+            material.texture = Some(if state.snow_cover { snowy_grass.clone() } else { grass.clone() });
+        }
     }
 }
 
-pub fn cast_ray(
-    ray_origin: &Vec3,
-    ray_direction: &Vec3,
-    objects: &[Object],
-    sun_position: &Vec3,
-    sun_intensity: f32,
-    depth: u32,
-) -> Color {
-    if depth > 3 {
-        return adjust_sky_color(sun_position);
+fn apply_snow(objects: &mut [Object], snow: &SnowCover) {
+    for &index in snow.exposed() {
+        if let Object::Cube(cube, _) = &mut objects[index] {
+            cube.material.snow = snow.depth;
+        }
     }
+}
 
-    let mut intersect = Intersect::empty();
-    let mut zbuffer = f32::INFINITY;
+// Writes the copied blocks to a timestamped file in the structures directory.
+fn save_structure(structure: &Structure, palette: &Palette) -> Result<String> {
+    std::fs::create_dir_all(STRUCTURES_DIRECTORY)
+        .map_err(|source| Error::Export { path: STRUCTURES_DIRECTORY.to_string(), source })?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis());
+    let filename = Path::new(STRUCTURES_DIRECTORY)
+        .join(format!("structure-{}.toml", stamp))
+        .to_string_lossy()
+        .into_owned();
+    structure.save(&filename, palette)?;
+    Ok(filename)
+}
 
-    for object in objects {
-        let i = match object {
-            Object::Cube(cube, _) => cube.ray_intersect(ray_origin, ray_direction),
-        };
-        if i.is_intersecting && i.distance < zbuffer {
-            zbuffer = i.distance;
-            intersect = i;
-        }
+// Makes `edits` as one undo step and updates what depends on the blocks.
+fn make_edits(history: &mut EditHistory, scene: &mut Scene, snow: &mut SnowCover, edits: Vec<Edit>) {
+    if edits.is_empty() {
+        return;
     }
+    log::info!("Changed {} blocks", edits.len());
+    let structural = edits.iter().any(Edit::is_structural);
+    history.apply_group(&mut scene.objects, edits);
+    blocks_edited(scene, snow, structural);
+}
 
-    if !intersect.is_intersecting {
-        return adjust_sky_color(sun_position);
+// Brings snow and the scene's caches up to date after block edits; `structural` when
+// blocks were added or removed, which changes the tops that can collect snow.
+fn blocks_edited(scene: &mut Scene, snow: &mut SnowCover, structural: bool) {
+    if structural {
+        for object in scene.objects.iter_mut() {
+            if let Object::Cube(cube, false) = object {
+                cube.material.snow = 0.0;
+            }
+        }
+        snow.set_exposed(exposed_tops(&scene.objects));
+        apply_snow(&mut scene.objects, snow);
     }
+    scene.materials_changed();
+}
 
-    let light_dir = (sun_position - intersect.point).normalize();
-    let view_dir = (ray_origin - intersect.point).normalize();
-    let reflect_dir = reflect(&-light_dir, &intersect.normal).normalize();
-
-    let shadow_intensity = cast_shadow(&intersect, sun_position, objects);
+fn apply_weather(particles: &mut ParticleSystem, weather: Weather) {
+    let (rain, snow) = match weather {
+        Weather::Clear => (0.0, 0.0),
+        Weather::Rain => (150.0, 0.0),
+        Weather::Snow => (0.0, 40.0),
+    };
+    particles.set_rate(EmitterKind::Rain, rain);
+    particles.set_rate(EmitterKind::Snow, snow);
+}
 
+#[cfg(feature = "schematic")]
+fn import_schematic(path: &str, origin: Vec3, registry: &MaterialRegistry) -> Result<Vec<Cube>> {
+    Ok(Schematic::load(path)?.to_cubes(origin, registry))
+}
 
-    let sun_height = sun_position.y.max(0.0);
-    let light_intensity = if sun_height > 0.0 {
-        sun_intensity * (sun_height / 15.0) + 1.0 
-    } else {
-        0.0
-    };
+#[cfg(not(feature = "schematic"))]
+fn import_schematic(_path: &str, _origin: Vec3, _registry: &MaterialRegistry) -> Result<Vec<Cube>> {
+    Err(Error::Usage("--schem needs a build with the schematic feature".to_string()))
+}
 
-    let diffuse_intensity = intersect.normal.dot(&light_dir).abs().max(0.5);
-    let specular_intensity = view_dir.dot(&reflect_dir).max(0.0).powf(intersect.material.specular);
+#[cfg(feature = "export")]
+fn export(options: &Options, cubes: &[&Cube]) -> Result<()> {
+    if let Some(path) = &options.export_obj {
+        let stats = export_obj(path, cubes)?;
+        log::info!(
+            "Exported {} cubes to {} as {} quads, {} materials, {} textures",
+            cubes.len(), path, stats.quads, stats.materials, stats.textures,
+        );
+    }
+    if let Some(path) = &options.export_vox {
+        let stats = export_vox(path, cubes)?;
+        log::info!("Exported {} voxels ({:?}, {} colours) to {}", stats.voxels, stats.size, stats.colors, path);
+    }
+    Ok(())
+}
 
-    let diffuse_color = if let Some(texture) = &intersect.material.texture {
-        let (u, v) = intersect.uv.unwrap();
-        let [r, g, b] = texture.get_color(u, v);
-        Color::new(r, g, b)
-    } else {
-        intersect.material.diffuse
-    };
+#[cfg(not(feature = "export"))]
+fn export(_options: &Options, _cubes: &[&Cube]) -> Result<()> {
+    Err(Error::Usage("--export-obj and --export-vox need a build with the export feature".to_string()))
+}
 
-    let ambient_light = if sun_position.y < 0.0 { 0.3 } else { 0.2 };
+// What a screenshot of `view` records about the scene and how it is rendered.
+fn snapshot(view: &Camera, scene: &Scene, seasons: &SeasonClock, snow: &SnowCover, settings: RenderSettings, seed: u64) -> RenderMetadata {
+    RenderMetadata {
+        eye: view.eye.into(),
+        center: view.center.into(),
+        up: view.up.into(),
+        fov_degrees: view.projection.fov.to_degrees(),
+        lens: view.projection.lens,
+        near: view.projection.near,
+        far: view.projection.far,
+        time_of_day: scene.day.hours(),
+        moon_phase: scene.day.moon_phase,
+        eclipse: scene.eclipse,
+        aurora: scene.aurora.strength,
+        aurora_time: scene.aurora.time,
+        seed,
+        width: view.projection.width,
+        height: view.projection.height,
+        global_illumination: scene.irradiance.enabled,
+        sun: scene.sun.switches,
+        season_day: seasons.days(),
+        weather: seasons.weather(),
+        snow_depth: snow.depth,
+        settings,
+        crop: None,
+        retro: None,
+    }
+}
 
-    let diffuse = diffuse_color * intersect.material.albedo[0] * diffuse_intensity * light_intensity * (1.0 - shadow_intensity);
-    let specular = Color::new(255, 255, 255) * intersect.material.albedo[1] * specular_intensity * light_intensity * (1.0 - shadow_intensity);
-    let ambient = diffuse_color * ambient_light;
+// Puts the timeline's camera, time of day, snow and object positions in place. The weather
+// is left to the caller, which knows what shows it.
+fn follow_timeline(state: &TimelineState, camera: &mut Camera, scene: &mut Scene, snow: &mut SnowCover) {
+    if let Some(pose) = &state.camera {
+        camera.set_pose(pose);
+    }
+    if let Some(hours) = state.time_of_day {
+        scene.day.set_time_of_day(hours);
+    }
+    if let Some(depth) = state.snow_depth {
+        if (depth - snow.depth).abs() > 1e-4 {
+            snow.depth = depth;
+            apply_snow(&mut scene.objects, snow);
+        }
+    }
+    let mut moved = false;
+    for &(index, center) in &state.objects {
+        match scene.objects.get_mut(index) {
+            Some(object) => moved |= object.set_center(center),
+            None => log::warn!("The timeline moves object {}, but the scene has only {}", index, scene.objects.len()),
+        }
+    }
+    if moved {
+        scene.geometry_changed();
+    }
+    scene.follow_day();
+}
 
-    diffuse + specular + ambient
+fn save_timeline(directory: &str, timeline: &Timeline) -> Result<String> {
+    std::fs::create_dir_all(directory).map_err(|source| Error::Export { path: directory.to_string(), source })?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis());
+    let filename = Path::new(directory).join(format!("timeline-{}.json", stamp)).to_string_lossy().into_owned();
+    timeline.save(&filename)?;
+    Ok(filename)
 }
 
-pub fn render(framebuffer: &mut Framebuffer, objects: &[Object], camera: &Camera, sun_position: &Vec3, sun_intensity: f32) {
-    let width = framebuffer.width as f32;
-    let height = framebuffer.height as f32;
-    let aspect_ratio = width / height;
-    let fov = PI / 3.0;
-    let perspective_scale = (fov * 0.5).tan();
+fn preset_names(presets: &[QualityPreset]) -> String {
+    presets.iter().map(|preset| preset.name.as_str()).collect::<Vec<_>>().join(", ")
+}
 
-    for y in 0..framebuffer.height {
-        for x in 0..framebuffer.width {
-            let screen_x = (2.0 * x as f32) / width - 1.0;
-            let screen_y = -(2.0 * y as f32) / height + 1.0;
+fn preset_named(presets: &[QualityPreset], name: &str) -> Result<usize> {
+    quality::find(presets, name).ok_or_else(|| Error::Usage(format!("unknown preset {}; presets: {}", name, preset_names(presets))))
+}
 
-            let screen_x = screen_x * aspect_ratio * perspective_scale;
-            let screen_y = screen_y * perspective_scale;
+fn retro_named(name: &str) -> Result<Retro> {
+    Retro::named(name).ok_or_else(|| Error::Usage(format!("unknown retro palette {}; palettes: {}", name, Retro::names())))
+}
 
-            let ray_direction = normalize(&Vec3::new(screen_x, screen_y, -1.0));
-            let rotated_direction = camera.base_change(&ray_direction);
+// A lamp from a [[light]] table of the config.
+fn lamp(config: &LightConfig) -> Light {
+    Light { switches: config.switches, ..Light::new(config.position.into(), tint_color(config.color), config.intensity) }
+}
 
-            let pixel_color = cast_ray(&camera.eye, &rotated_direction, objects, sun_position, sun_intensity, 0);
+fn use_preset(
+    preset: &QualityPreset,
+    renderer: &mut Renderer,
+    scene: &mut Scene,
+    crop_samples: &mut usize,
+    reflection_probes: &mut bool,
+) {
+    preset.apply(&mut renderer.settings, scene);
+    *crop_samples = preset.samples.max(1);
+    *reflection_probes = preset.reflection_probes;
+    log::info!("Quality preset {}", preset.name);
+}
 
-            framebuffer.set_current_color(pixel_color.to_hex());
-            framebuffer.point(x, y);
+// Reloads the block textures at the preset's size and palette, and the snowy grass made
+// from the grass one, dropping light cached from the old pixels. A texture that fails to
+// load keeps its old pixels.
+fn use_preset_textures(preset: &QualityPreset, textures: &[Arc<Texture>], grass: &Texture, snowy_grass: &Texture, scene: &Scene) {
+    let mut changed = false;
+    for texture in textures {
+        match texture.set_preview(preset.texture_reduction()) {
+            Ok(reloaded) => changed |= reloaded,
+            Err(err) => log::warn!("{}", err),
         }
     }
+    if changed {
+        snowy_grass.replace(snow_covered(grass));
+        scene.irradiance.clear();
+        scene.probes.clear();
+    }
 }
 
 fn main() {
-    let window_width = 800;
-    let window_height = 600;
-    let framebuffer_width = 800;
-    let framebuffer_height = 600;
-    let frame_delay = Duration::from_millis(16);
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
+    if let Err(err) = run() {
+        log::error!("{}", err);
+        std::process::exit(1);
+    }
+}
 
-    let mut window = Window::new(
-        "Refractor",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    ).unwrap();
-
-    let grass_texture = Rc::new(Texture::new("src/Grass.png"));
-    let dirt_texture = Rc::new(Texture::new("src/Dirt.png"));
-    let leaves_texture = Rc::new(Texture::new("src/Leaves.png"));
-    let trunk_texture = Rc::new(Texture::new("src/Trunk.png"));
-    let sun_texture = Rc::new(Texture::new("src/SunMoon.png"));
-    let water_texture = Rc::new(Texture::new("src/Water.png"));
-    let hive_texture = Rc::new(Texture::new("src/Hive.png"));
-    let stone_texture = Rc::new(Texture::new("src/Stone.png"));
+fn run() -> Result<()> {
+    let window_width = 800;
+    let window_height = 600;
+    let target_fps = 60;
+    let options = Options::parse(std::env::args().skip(1))?;
+    let config = Config::load("config.toml")?;
+    let presets = quality::presets(&config.presets);
+    let mut bookmarks = Bookmarks::load(BOOKMARKS_FILE)?;
+
+    let grass_texture = Arc::new(Texture::new("src/Grass.png")?);
+    let dirt_texture = Arc::new(Texture::new("src/Dirt.png")?);
+    let leaves_texture = Arc::new(Texture::new("src/Leaves.png")?);
+    let trunk_texture = Arc::new(Texture::new("src/Trunk.png")?);
+    let sun_texture = Arc::new(Texture::new("src/SunMoon.png")?);
+    let water_texture = Arc::new(Texture::new("src/Water.png")?);
+    let hive_texture = Arc::new(Texture::new("src/Hive.png")?);
+    let stone_texture = Arc::new(Texture::new("src/Stone.png")?);
+    let textures = vec![
+        grass_texture.clone(),
+        dirt_texture.clone(),
+        leaves_texture.clone(),
+        trunk_texture.clone(),
+        sun_texture.clone(),
+        water_texture.clone(),
+        hive_texture.clone(),
+        stone_texture.clone(),
+    ];
+    if config.render.texture_budget_mb > 0.0 {
+        texture::fit_budget(&textures, (config.render.texture_budget_mb * 1_048_576.0) as usize);
+    }
+    let snowy_grass_texture = Arc::new(snow_covered(&grass_texture));
 
     let grass_material = Material::new(
         Color::black(),
@@ -193,7 +475,7 @@ fn main() {
         [0.9, 0.1, 0.0, 0.0],
         0.0,
         Some(grass_texture.clone()),
-    );
+    ).with_bevel(0.06);
 
     let dirt_material = Material::new(
         Color::black(),
@@ -201,7 +483,7 @@ fn main() {
         [0.9, 0.1, 0.0, 0.0],
         0.0,
         Some(dirt_texture.clone()),
-    );
+    ).with_bevel(0.06);
 
     let leaves_material = Material::new(
         Color::black(),
@@ -209,15 +491,15 @@ fn main() {
         [0.9, 0.1, 0.0, 0.0],
         0.0,
         Some(leaves_texture.clone()),
-    );
+    ).with_translucency(0.6);
 
     let trunk_material = Material::new(
         Color::black(),
-        1.0,
-        [0.9, 0.1, 0.0, 0.0],
+        20.0,
+        [0.9, 0.2, 0.0, 0.0],
         0.0,
         Some(trunk_texture.clone()),
-    );
+    ).with_bevel(0.06).with_anisotropy(0.8);
 
     let pale_yellow = Material::new(
         Color::black(),
@@ -227,13 +509,14 @@ fn main() {
         Some(sun_texture.clone())
     );
 
+    // Lets half the light through, bent as by water, so the lake bed shows through the surface.
     let water_material = Material::new(
-        Color::black(),
-        1.0,
-        [0.9, 0.1, 0.0, 0.0],
-        0.0,
+        Color::new(200, 225, 255),
+        80.0,
+        [0.4, 0.5, 0.1, 0.5],
+        1.33,
         Some(water_texture.clone())
-    );
+    ).with_dispersion(0.01).with_depth_tint(Color::new(18, 52, 96), 2.5).with_foam(0.8);
 
     let hive_material = Material::new(
         Color::black(),
@@ -249,9 +532,20 @@ fn main() {
         [0.9, 0.1, 0.0, 0.0],
         0.0,
         Some(stone_texture.clone())
+    ).with_bevel(0.06);
+
+    let mirror_material = Material::mirror();
+    let glass_material = Material::glass(Color::new(210, 240, 230));
+
+    let portal_material = Material::new(
+        Color::new(140, 60, 200),
+        10.0,
+        [0.9, 0.3, 0.0, 0.0],
+        0.0,
+        None,
     );
 
-    let mut objects = [
+    let mut objects = vec![
         Object::Cube(Cube { center: Vec3::new(0.0, 10.0, 0.0), size: 1.0, material: pale_yellow.clone() }, true), //Sol
 
 
@@ -529,7 +823,337 @@ fn main() {
         Object::Cube(Cube { center: Vec3::new(0.0, 9.0, 1.0), size: 1.0, material: leaves_material.clone() }, false), //Hoja
         Object::Cube(Cube { center: Vec3::new(0.0, 9.0, -1.0), size: 1.0, material: leaves_material.clone() }, false), //Hoja
         Object::Cube(Cube { center: Vec3::new(0.0, 10.0, 0.0), size: 1.0, material: leaves_material.clone() }, false), //Hoja
+
+
+        Object::Cube(Cube { center: Vec3::new(-3.0, 3.0, -3.0), size: 1.0, material: mirror_material.clone() }, false), //Espejo
+        Object::Cube(Cube { center: Vec3::new(-3.0, 3.0, 0.0), size: 1.0, material: glass_material.clone() }, false), //Vidrio
+        Object::Cube(Cube { center: Vec3::new(-3.0, 4.0, 0.0), size: 1.0, material: glass_material.clone() }, false), //Vidrio
+        Object::Cube(Cube { center: Vec3::new(-2.0, 2.75, -2.0), size: 0.5, material: trunk_material.clone() }, false), //Fogata
+    ];
+
+    // A block worn round at the corners with an arched niche cut into its face.
+    let worn_block = Csg::new(
+        CsgOp::Intersection,
+        Shape::Cube(Cube { center: Vec3::new(2.0, 3.0, 2.0), size: 1.0, material: stone_material.clone() }),
+        Shape::Sphere(Sphere { center: Vec3::new(2.0, 3.0, 2.0), radius: 0.75, material: stone_material.clone() }),
+        stone_material.clone(),
+    );
+    let arch = Csg::new(
+        CsgOp::Union,
+        Shape::Cube(Cube { center: Vec3::new(2.0, 2.9, 2.5), size: 0.5, material: stone_material.clone() }),
+        Shape::Sphere(Sphere { center: Vec3::new(2.0, 3.15, 2.5), radius: 0.25, material: stone_material.clone() }),
+        stone_material.clone(),
+    );
+    objects.push(Object::Csg(Csg::new(
+        CsgOp::Difference,
+        Shape::Csg(Box::new(worn_block)),
+        Shape::Csg(Box::new(arch)),
+        stone_material.clone(),
+    ))); //Nicho
+
+    let flower_positions = [
+        (Vec3::new(4.0, 2.8, 3.0), Color::new(230, 60, 80)),
+        (Vec3::new(6.2, 2.8, 3.1), Color::new(250, 250, 250)),
+        (Vec3::new(5.0, 2.8, -3.0), Color::new(150, 90, 230)),
+        (Vec3::new(8.0, 2.8, 1.2), Color::new(230, 60, 80)),
+        (Vec3::new(7.8, 2.8, -2.1), Color::new(250, 200, 40)),
     ];
+    for (position, petal) in flower_positions {
+        let flower_material = Material::new(Color::black(), 1.0, [0.9, 0.1, 0.0, 0.0], 0.0, Some(flower_texture(petal)));
+        objects.push(Object::Billboard(Billboard::new(position, 0.6, 0.6, flower_material))); //Flor
+    }
+
+    objects.push(Object::Door(Door::new(Vec3::new(-0.5, 2.5, 3.0), 0.0, 1.0, 2.0, trunk_material.clone()))); //Puerta
+
+    let portal_a = objects.len();
+    let portal_b = portal_a + 1;
+    objects.push(Object::Portal(Portal::new(Vec3::new(-2.0, 3.0, 2.5), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 1.0, portal_b, portal_material.clone()))); //Portal
+    objects.push(Object::Portal(Portal::new(Vec3::new(7.5, 3.0, -1.0), Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0, portal_a, portal_material.clone()))); //Portal
+
+    // Block names in structure files and schematics; blocks the scene has no material for
+    // fall back to the built-in palette.
+    let palette = Palette::builtin("src");
+    let mut registry = palette.registry();
+    registry.register("grass", &grass_material);
+    registry.register("wood", &trunk_material);
+    registry.register("leaves", &leaves_material);
+    registry.register("hive", &hive_material);
+    registry.register("grass_block", &grass_material);
+    registry.register("dirt", &dirt_material);
+    registry.register("coarse_dirt", &dirt_material);
+    registry.register("*_log", &trunk_material);
+    registry.register("*_wood", &trunk_material);
+    registry.register("*_planks", &trunk_material);
+    registry.register("*_leaves", &leaves_material);
+    registry.register("water", &water_material);
+    registry.register("stone", &stone_material);
+    registry.register("cobblestone", &stone_material);
+    registry.register("stone_bricks", &stone_material);
+    registry.register("beehive", &hive_material);
+    registry.register("bee_nest", &hive_material);
+    registry.register("glass", &glass_material);
+
+    let library = StructureLibrary::load(STRUCTURES_DIRECTORY, &registry);
+    for prefab in &config.prefabs {
+        let Some(structure) = library.get(&prefab.name) else {
+            log::warn!("No structure named {} in {}", prefab.name, STRUCTURES_DIRECTORY);
+            continue;
+        };
+        let structure = (0..prefab.turns % 4).fold(structure.clone(), |structure, _| structure.rotated());
+        let cubes = structure.cubes_at(&objects, &Vec3::from(prefab.at));
+        objects.extend(cubes.into_iter().map(|cube| Object::Cube(cube, false)));
+    }
+
+    if let Some(path) = &options.schematic {
+        let cubes = import_schematic(path, options.schematic_origin, &registry)?;
+        log::info!("Imported {} visible blocks from {}", cubes.len(), path);
+        objects.extend(cubes.into_iter().map(|cube| Object::Cube(cube, false)));
+    }
+
+    if config.scatter.enabled {
+        let spots = scatter(&objects, &palette, &config.scatter.biomes, config.seed + 32);
+        log::info!("Scattering {} decorations", spots.len());
+        for spot in spots {
+            match spot.decoration {
+                Decoration::Structure { name } => match library.get(name) {
+                    Some(structure) => {
+                        let cubes = structure.cubes_at(&objects, &(spot.cell - structure.base()));
+                        objects.extend(cubes.into_iter().map(|cube| Object::Cube(cube, false)));
+                    }
+                    None => log::warn!("No structure named {} in {}", name, STRUCTURES_DIRECTORY),
+                },
+                Decoration::Flower { petal } => {
+                    let flower_material = Material::new(Color::black(), 1.0, [0.9, 0.1, 0.0, 0.0], 0.0, Some(flower_texture(tint_color(*petal))));
+                    objects.push(Object::Billboard(Billboard::new(spot.cell - Vec3::new(0.0, 0.2, 0.0), 0.6, 0.6, flower_material)));
+                }
+            }
+        }
+    }
+
+    // The skirt and wall go in last, around everything built, imported or scattered above.
+    if config.border.skirt_depth > 0 {
+        match registry.get(&config.border.skirt_block) {
+            Some(material) => {
+                let cubes = skirt(&objects, config.border.skirt_depth, material);
+                log::info!("Hung {} skirt blocks under the edge of the world", cubes.len());
+                objects.extend(cubes.into_iter().map(|cube| Object::Cube(cube, false)));
+            }
+            None => log::warn!("Unknown skirt block {}", config.border.skirt_block),
+        }
+    }
+    if config.border.wall_height > 0.0 {
+        // An index of 1.0 keeps the view through the wall straight.
+        let wall_material = Material::new(tint_color(config.border.wall_tint), 50.0, [0.05, 0.1, 0.0, 0.9], 1.0, None);
+        let panels = walls(&objects, config.border.wall_height, &wall_material);
+        objects.extend(panels.into_iter().map(Object::Wall));
+    }
+
+    if options.export_obj.is_some() || options.export_vox.is_some() {
+        let cubes: Vec<&Cube> = objects
+            .iter()
+            .filter_map(|object| match object {
+                Object::Cube(cube, false) => Some(cube),
+                _ => None,
+            })
+            .collect();
+        return export(&options, &cubes);
+    }
+
+    let mut snow = SnowCover::new(exposed_tops(&objects));
+
+    let mut timeline = options.timeline.as_deref().map(Timeline::load).transpose()?;
+    // A timeline rendered to frames starts from its recorded state unless a screenshot is given.
+    let offline = match (&options.from_screenshot, &options.timeline) {
+        (Some(source), _) => Some((source.as_str(), read_metadata(source)?)),
+        (None, Some(path)) if options.frames.is_some() => {
+            let start = timeline.as_ref().and_then(|timeline| timeline.start.clone());
+            let start = start.ok_or_else(|| Error::Usage(format!("{} has no starting state; add --from-screenshot", path)))?;
+            Some((path.as_str(), start))
+        }
+        _ => None,
+    };
+    if let Some((source, mut metadata)) = offline {
+        let (width, height) = options.size.unwrap_or((metadata.width, metadata.height));
+        let preset = options.preset.as_deref().map(|name| preset_named(&presets, name)).transpose()?.map(|index| &presets[index]);
+        let samples = options.samples.or(preset.map(|preset| preset.samples)).unwrap_or(1);
+        let retro = options.retro.as_deref().or(metadata.retro.as_deref()).map(retro_named).transpose()?;
+        metadata.retro = retro.as_ref().map(|retro| retro.name.to_string());
+        log::info!("Re-rendering {} at {}x{} with {} samples per pixel", source, width, height, samples);
+
+        let mut camera = Camera::new(metadata.eye.into(), metadata.center.into(), metadata.up.into());
+        camera.projection.fov = metadata.fov_degrees.to_radians();
+        camera.projection.lens = metadata.lens;
+        camera.projection.near = metadata.near;
+        camera.projection.far = metadata.far;
+        camera.set_viewport(width, height);
+        let day = DayCycle::new(15.0, metadata.time_of_day)
+            .with_path(config.sun.max_elevation, config.sun.path_tilt)
+            .with_moon(metadata.moon_phase, config.sun.moon_cycle_days);
+        let mut scene = Scene::new(objects, day, pale_yellow.clone());
+        scene.sun.switches = metadata.sun;
+        scene.eclipse = metadata.eclipse;
+        scene.aurora = Aurora::new(&config.aurora);
+        scene.background = Background::load(&config.background)?;
+        scene.lights = config.lights.iter().map(lamp).collect();
+        scene.aurora.strength = metadata.aurora;
+        scene.aurora.time = metadata.aurora_time;
+        scene.follow_day();
+        scene.irradiance.enabled = metadata.global_illumination;
+
+        let mut seasons = SeasonClock::new(config.seasons.clone(), metadata.seed + 16);
+        seasons.advance(metadata.season_day);
+        if let Some(state) = seasons.state() {
+            apply_season(&mut scene.objects, &state, &leaves_texture, &grass_texture, &snowy_grass_texture);
+        }
+        snow.depth = metadata.snow_depth;
+        apply_snow(&mut scene.objects, &snow);
+
+        for object in scene.objects.iter_mut() {
+            if let Object::Billboard(billboard) = object {
+                billboard.face_towards(&camera.eye);
+            }
+        }
+
+        if let Some(preset) = preset {
+            preset.apply(&mut metadata.settings, &mut scene);
+            use_preset_textures(preset, &textures, &grass_texture, &snowy_grass_texture, &scene);
+        }
+        progress::catch_interrupt();
+        let start = Instant::now();
+        let renderer = Renderer::new(metadata.settings, config.render.threads);
+        let captured = (metadata.width, metadata.height);
+        metadata.width = width;
+        metadata.height = height;
+        if let Some(count) = options.contact_sheet {
+            let output = options.output.clone().unwrap_or_else(|| {
+                Path::new(source).with_extension("").to_string_lossy().into_owned() + "-contact.png"
+            });
+            let mut progress = Progress::new((count * width * height * samples) as u64, options.progress_file.clone());
+            let mut sheet = contact_sheet::render(&renderer, &mut scene, &camera, count, samples, &mut progress)?;
+            if let Some(retro) = &retro {
+                retro.apply(&mut sheet);
+            }
+            save_png(&output, &sheet, &metadata)?;
+            progress.finish()?;
+            log::info!("Saved {} in {:.1}s", output, start.elapsed().as_secs_f32());
+            return if progress::interrupted() { Err(Error::Interrupted) } else { Ok(()) };
+        }
+        if let (Some(fps), Some(timeline)) = (options.frames, &timeline) {
+            let prefix = options.output.clone().unwrap_or_else(|| {
+                Path::new(source).with_extension("").to_string_lossy().into_owned() + "-frame"
+            });
+            let frames = (timeline.duration() * fps as f32).floor() as usize + 1;
+            let mut framebuffer = Framebuffer::new(width, height);
+            let mut progress = Progress::new((frames * width * height * samples) as u64, options.progress_file.clone());
+            for frame in 0..frames {
+                let state = timeline.at(frame as f32 / fps as f32);
+                follow_timeline(&state, &mut camera, &mut scene, &mut snow);
+                camera.set_viewport(width, height);
+                for object in scene.objects.iter_mut() {
+                    if let Object::Billboard(billboard) = object {
+                        billboard.face_towards(&camera.eye);
+                    }
+                }
+                progress.stage = format!("Frame {}/{}", frame + 1, frames);
+                progress.render_tiles(&renderer, &mut framebuffer, Rect::new(0, 0, width, height), &scene, &camera, samples)?;
+                // Every frame written is whole; the one Ctrl+C cut short is dropped.
+                if progress::interrupted() {
+                    break;
+                }
+
+                if let Some(retro) = &retro {
+                    retro.apply(&mut framebuffer);
+                }
+
+                let shot = snapshot(&camera, &scene, &seasons, &snow, metadata.settings, metadata.seed);
+                let frame_metadata = RenderMetadata { weather: state.weather.unwrap_or(metadata.weather), retro: metadata.retro.clone(), ..shot };
+                let filename = format!("{}-{:04}.png", prefix, frame);
+                save_png(&filename, &framebuffer, &frame_metadata)?;
+                log::info!("Saved frame {}/{} to {} after {:.1}s", frame + 1, frames, filename, start.elapsed().as_secs_f32());
+            }
+            progress.finish()?;
+            return if progress::interrupted() { Err(Error::Interrupted) } else { Ok(()) };
+        }
+        // A crop saved with the shot is in the pixels it was captured at; --crop is in the
+        // pixels being rendered.
+        let saved_crop = metadata.crop.map(|[x, y, w, h]| Rect::new(x, y, w, h).scaled(captured, (width, height)));
+        let crop = match options.crop.or(saved_crop) {
+            Some(crop) => crop.clipped(width, height),
+            None => Rect::new(0, 0, width, height),
+        };
+        if crop.is_empty() {
+            return Err(Error::Usage(format!("the crop lies outside the {}x{} image", width, height)));
+        }
+        metadata.crop = None;
+        if crop != Rect::new(0, 0, width, height) {
+            log::info!("Rendering only {}x{} pixels at ({}, {})", crop.width, crop.height, crop.x, crop.y);
+            metadata.crop = Some([crop.x, crop.y, crop.width, crop.height]);
+        }
+        let mut framebuffer = Framebuffer::new(crop.width, crop.height);
+        let output = options.output.clone().unwrap_or_else(|| {
+            Path::new(source).with_extension("").to_string_lossy().into_owned() + "-rerender.png"
+        });
+
+        match &options.checkpoint {
+            Some(checkpoint) => {
+                // One jittered sample per pass; the preview and resume file are flushed
+                // periodically so an interrupted render can be inspected and continued.
+                let description = metadata_json(&metadata, false);
+                let mut progressive = ProgressiveRender::resume(checkpoint, crop.width, crop.height, &description)?;
+                let flush_interval = Duration::from_secs(options.checkpoint_every);
+                let mut last_flush = Instant::now();
+                let remaining = samples.saturating_sub(progressive.passes);
+                let mut progress = Progress::new((remaining * crop.width * crop.height) as u64, options.progress_file.clone());
+
+                // A pass is what the checkpoint counts, so Ctrl+C waits for the current one.
+                while progressive.passes < samples && !progress::interrupted() {
+                    progressive.add_pass(renderer.pool(), |x, y, jitter| {
+                        renderer.trace_pixel(&camera, ((x + crop.x) as f32, (y + crop.y) as f32), jitter, &scene)
+                    });
+                    progress.advance((crop.width * crop.height) as u64, &format!("Pass {}/{}", progressive.passes, samples))?;
+
+                    if last_flush.elapsed() >= flush_interval && progressive.passes < samples {
+                        progressive.resolve(&mut framebuffer, metadata.settings.dither);
+                        if let Some(retro) = &retro {
+                            retro.apply(&mut framebuffer);
+                        }
+                        save_png(&output, &framebuffer, &metadata)?;
+                        progressive.save(checkpoint)?;
+                        last_flush = Instant::now();
+                    }
+                }
+                progressive.resolve(&mut framebuffer, metadata.settings.dither);
+                progressive.save(checkpoint)?;
+                progress.finish()?;
+            }
+            None => {
+                let mut full = Framebuffer::new(width, height);
+                let mut progress = Progress::new((crop.width * crop.height * samples) as u64, options.progress_file.clone());
+                progress.render_tiles(&renderer, &mut full, crop, &scene, &camera, samples)?;
+                framebuffer.blit_region(&full, crop, framebuffer.bounds());
+                progress.finish()?;
+            }
+        }
+        if let Some(retro) = &retro {
+            retro.apply(&mut framebuffer);
+        }
+
+        save_png(&output, &framebuffer, &metadata)?;
+        log::info!("Saved {} in {:.1}s", output, start.elapsed().as_secs_f32());
+        return if progress::interrupted() { Err(Error::Interrupted) } else { Ok(()) };
+    }
+
+    let mut particles = ParticleSystem::new(1200, config.seed);
+    particles.add_emitter(Emitter::new(Vec3::new(5.0, 2.5, 0.0), EmitterKind::Splash, 25.0));
+    particles.add_emitter(Emitter::new(Vec3::new(-2.0, 3.0, -2.0), EmitterKind::Smoke, 12.0));
+    particles.add_emitter(Emitter::new(Vec3::new(2.0, 11.0, 0.0), EmitterKind::Rain, 0.0));
+    particles.add_emitter(Emitter::new(Vec3::new(2.0, 11.0, 0.0), EmitterKind::Snow, 0.0));
+    particles.add_emitter(Emitter::new(Vec3::new(12.0, 24.0, 0.0), EmitterKind::Meteor, 0.0));
+    let mut seasons = SeasonClock::new(config.seasons.clone(), config.seed + 16);
+    apply_weather(&mut particles, seasons.weather());
+    let swarm = Swarm::new(Vec3::new(1.6, 5.2, 0.6), 8, config.seed + 4);
+    let mut entities = Entities::new(particles, swarm);
+    let mut events = EventSchedule::new(config.events.clone());
 
     let mut camera = Camera::new(
         Vec3::new(0.0, 5.0, 7.0),
@@ -537,33 +1161,598 @@ fn main() {
         Vec3::new(0.0, 3.0, 0.0),
     );
 
-    let mut angle: f32 = 0.0;
-    let radius = 15.0;
+    camera.speed = config.camera.speed;
+    camera.projection.lens = config.camera.lens;
+    camera.projection.near = config.camera.near;
+    camera.projection.far = config.camera.far;
+    let mut smoothing = CameraSmoothing::new(&camera, config.camera.smoothing);
+
+    let day = DayCycle::new(15.0, daycycle::DAWN)
+        .with_path(config.sun.max_elevation, config.sun.path_tilt)
+        .with_moon(config.sun.moon_phase, config.sun.moon_cycle_days);
+    let mut scene = Scene::new(objects, day, pale_yellow.clone());
+    scene.aurora = Aurora::new(&config.aurora);
+    scene.background = Background::load(&config.background)?;
+    scene.lights = config.lights.iter().map(lamp).collect();
+    scene.probes = ReflectionProbes::new(config.render.probe_spacing, config.render.probe_resolution);
+    scene.probes.place(&scene.objects);
+    let time_of_day_transition = 1.0;
     let rotation_speed = 0.05;
-    let sun_intensity = 2.0;
+    let settings = RenderSettings {
+        shadow_bias: ShadowBias {
+            distance_scale: 1e-5,
+            slope_scale: 5e-5,
+            ..ShadowBias::default()
+        },
+        sky_samples: config.render.sky_samples,
+        corner_occlusion: config.render.corner_occlusion,
+        texture_filtering: true,
+        dither: config.render.dither,
+        nan_watchdog: config.render.nan_watchdog,
+        ..RenderSettings::default()
+    };
+    let mut renderer = Renderer::new(settings, config.render.threads);
+    // Samples for refined regions and whether to use reflection probes, both set by the
+    // quality preset when there is one.
+    let mut crop_samples = CROP_SAMPLES;
+    let mut reflection_probes = config.render.reflection_probes;
+    let mut preset = options.preset.as_deref().or(config.render.preset.as_deref()).map(|name| preset_named(&presets, name)).transpose()?;
+    if let Some(index) = preset {
+        use_preset(&presets[index], &mut renderer, &mut scene, &mut crop_samples, &mut reflection_probes);
+        use_preset_textures(&presets[index], &textures, &grass_texture, &snowy_grass_texture, &scene);
+    }
+    let mut texture_watcher = TextureWatcher::new(textures.clone());
+
+    log::info!("Scene ready with {} objects", scene.objects.len());
+    memory::report(&textures, &scene);
+
+    if options.terminal {
+        let mut presenter = TerminalPresenter::new()?;
+        return terminal::run(&mut presenter, &renderer, &mut scene, &mut camera, config.sun.day_length.max(1.0));
+    }
+    let mut display = Display::new(window_width, window_height, &config.display)?;
+    let (framebuffer_width, framebuffer_height) = display.render_size();
+    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
+    // The framebuffer carries over between frames, so the retro palette goes on a copy that is
+    // shown and saved.
+    let mut retro = config.render.retro.as_deref().map(retro_named).transpose()?;
+    let mut retro_frame = Framebuffer::new(framebuffer.width, framebuffer.height);
+
+    let mut pacer = FramePacer::new(FrameRate::Capped(target_fps));
+    let mut clock = SimulationClock::new(SIMULATION_RATE);
+    let mut stats_start = Instant::now();
+    let mut stats_frames = 0;
+    let mut stats_render_time = Duration::ZERO;
+    let mut mouse_was_down = false;
+    let mut last_cursor = None;
+    let mut orbit_pivot: Option<Vec3> = None;
+    let mut motion_blur = false;
+    let mut previous_motion = Motion { camera: camera.pose(), sun: scene.sun.direction };
+    let mut marquee_start = None;
+    let mut crop: Option<Rect> = None;
+    let mut frozen: Option<Framebuffer> = None;
+    let mut balancing = false;
+    let mut time_slicer: Option<TimeSlicer> = None;
+    let mut layers: Option<LightLayers> = None;
+    let mut painter = Painter::new(Palette::builtin("src"));
+    let mut history = EditHistory::new();
+    // The block a paint drag last touched, while one is going on.
+    let mut stroke: Option<Option<usize>> = None;
+    let mut middle_was_down = false;
+    let mut selection = Selection::default();
+    let mut clipboard = Structure::default();
+    // The library structure Period last took into the clipboard.
+    let mut prefab: Option<usize> = None;
+    let console = Console::new();
+    // Seconds into the timeline being played, and the one being recorded.
+    let mut playback = timeline.as_ref().map(|_| 0.0);
+    let mut recorder: Option<Recorder> = None;
+    let mut frame_clipboard = FrameClipboard::default();
+
+    while display.window.is_open() && !display.window.is_key_down(Key::Escape) {
+        pacer.begin_frame();
+
+        if display.window.is_key_pressed(Key::F11, KeyRepeat::No) {
+            display.toggle_fullscreen()?;
+            let (framebuffer_width, framebuffer_height) = display.render_size();
+            framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
+            crop = None;
+            frozen = None;
+            layers = None;
+        }
+        // Repainted textures show up live; bounce light cached from the old pixels is dropped.
+        for texture in texture_watcher.poll() {
+            if Arc::ptr_eq(&texture, &grass_texture) {
+                snowy_grass_texture.replace(snow_covered(&grass_texture));
+            }
+            scene.irradiance.clear();
+            scene.probes.clear();
+        }
+        let ctrl = display.window.is_key_down(Key::LeftCtrl) || display.window.is_key_down(Key::RightCtrl);
+        if !ctrl && display.window.is_key_pressed(Key::V, KeyRepeat::No) {
+            pacer.toggle_cap(target_fps);
+        }
+        // U starts and stops recording a timeline, which I then plays back (as it does one
+        // passed with --timeline).
+        if display.window.is_key_pressed(Key::U, KeyRepeat::No) {
+            match recorder.take() {
+                Some(recording) => {
+                    let recorded = recording.finish();
+                    match save_timeline(&config.screenshot.directory, &recorded) {
+                        Ok(path) => log::info!("Saved a {:.1}s timeline to {}", recorded.duration(), path),
+                        Err(err) => log::error!("{}", err),
+                    }
+                    timeline = Some(recorded);
+                }
+                None => {
+                    log::info!("Recording a timeline; U again stops");
+                    recorder = Some(Recorder::new(snapshot(&camera, &scene, &seasons, &snow, renderer.settings, config.seed), TIMELINE_KEY_INTERVAL));
+                }
+            }
+        }
+        if display.window.is_key_pressed(Key::I, KeyRepeat::No) && timeline.is_some() {
+            playback = match playback {
+                Some(_) => None,
+                None => Some(0.0),
+            };
+        }
+        // F9 steps through the quality presets.
+        if display.window.is_key_pressed(Key::F9, KeyRepeat::No) {
+            let index = preset.map_or(0, |index| (index + 1) % presets.len());
+            preset = Some(index);
+            use_preset(&presets[index], &mut renderer, &mut scene, &mut crop_samples, &mut reflection_probes);
+            use_preset_textures(&presets[index], &textures, &grass_texture, &snowy_grass_texture, &scene);
+        }
+        if display.window.is_key_pressed(Key::G, KeyRepeat::No) {
+            scene.irradiance.toggle();
+        }
+        // R swaps water reflection rays for a mirrored pass, which is faster but coarser.
+        if !ctrl && display.window.is_key_pressed(Key::R, KeyRepeat::No) {
+            renderer.reflection_plane = match renderer.reflection_plane {
+                Some(_) => {
+                    log::info!("Tracing water reflections");
+                    None
+                }
+                None => match water_level(&scene.objects) {
+                    Some(level) => {
+                        log::info!("Reflecting water at y = {:.2} from a mirrored pass", level);
+                        Some(ReflectionPlane::new(level, REFLECTION_PLANE_RESOLUTION))
+                    }
+                    None => {
+                        log::warn!("No flat water to use a reflection plane for");
+                        None
+                    }
+                },
+            };
+        }
+        // T keeps each frame within a time budget, finishing the picture over several frames.
+        if display.window.is_key_pressed(Key::T, KeyRepeat::No) {
+            time_slicer = match time_slicer {
+                Some(_) => None,
+                None => Some(TimeSlicer::new(Duration::from_secs_f32(config.render.frame_budget_ms / 1000.0), TIME_SLICE_TILE)),
+            };
+            log::info!("Time-sliced rendering {}", if time_slicer.is_some() { "on" } else { "off" });
+        }
+        // K turns clicks into block edits with the palette block picked by [ and ];
+        // Ctrl+Z and Ctrl+Y undo and redo them.
+        if display.window.is_key_pressed(Key::K, KeyRepeat::No) {
+            painter.toggle();
+        }
+        if painter.enabled {
+            if display.window.is_key_pressed(Key::LeftBracket, KeyRepeat::Yes) {
+                painter.select(-1);
+            }
+            if display.window.is_key_pressed(Key::RightBracket, KeyRepeat::Yes) {
+                painter.select(1);
+            }
+        }
+        if ctrl {
+            let replayed = if display.window.is_key_pressed(Key::Z, KeyRepeat::Yes) {
+                history.undo(&mut scene.objects)
+            } else if display.window.is_key_pressed(Key::Y, KeyRepeat::Yes) {
+                history.redo(&mut scene.objects)
+            } else {
+                None
+            };
+            if let Some(edits) = replayed {
+                let structural = edits.iter().any(Edit::is_structural);
+                blocks_edited(&mut scene, &mut snow, structural);
+            }
+        }
+        // Fill and replace, from the console or the hotkeys below, each make one undo step.
+        for command in console.poll() {
+            let made = match &command {
+                Command::Fill(block) => match selection.corners() {
+                    Some((a, b)) => painter.fill(&scene.objects, &a, &b, block),
+                    None => {
+                        log::info!("Mark two corners with J in painting mode first");
+                        continue;
+                    }
+                },
+                Command::Replace(from, to) => painter.replace(&scene.objects, from, to),
+                Command::Blocks => {
+                    log::info!("Blocks: {}", painter.palette().names().collect::<Vec<_>>().join(", "));
+                    continue;
+                }
+                Command::Stamp(name) => {
+                    match library.get(name) {
+                        Some(structure) => {
+                            clipboard = structure.clone();
+                            log::info!("Copied {} ({} blocks); Ctrl+V in painting mode places it", name, clipboard.blocks.len());
+                        }
+                        None => log::info!("Unknown structure; `structures` lists them"),
+                    }
+                    continue;
+                }
+                Command::Schedule(event) => {
+                    events.add(event.clone());
+                    continue;
+                }
+                Command::Structures => {
+                    log::info!("Structures: {}", library.names().collect::<Vec<_>>().join(", "));
+                    continue;
+                }
+                Command::Preset(name) => {
+                    match quality::find(&presets, name) {
+                        Some(index) => {
+                            preset = Some(index);
+                            use_preset(&presets[index], &mut renderer, &mut scene, &mut crop_samples, &mut reflection_probes);
+                            use_preset_textures(&presets[index], &textures, &grass_texture, &snowy_grass_texture, &scene);
+                        }
+                        None => log::info!("Unknown preset; `presets` lists them"),
+                    }
+                    continue;
+                }
+                Command::Presets => {
+                    log::info!("Presets: {}", preset_names(&presets));
+                    continue;
+                }
+                Command::Memory => {
+                    memory::report(&textures, &scene);
+                    continue;
+                }
+                Command::Watchdog(enabled) => {
+                    renderer.settings.nan_watchdog = *enabled;
+                    if *enabled {
+                        renderer.watchdog.reset();
+                        log::info!("NaN watchdog on: bad pixels show in magenta");
+                    } else {
+                        log::info!("NaN watchdog off after {} bad samples", renderer.watchdog.found());
+                    }
+                    continue;
+                }
+                Command::Retro(name) => {
+                    match name.as_deref().map(Retro::named) {
+                        Some(None) => log::info!("Unknown palette; palettes: {}", Retro::names()),
+                        Some(Some(palette)) => {
+                            log::info!("Retro palette {}", palette.name);
+                            retro = Some(palette);
+                        }
+                        None => {
+                            log::info!("Retro palette off");
+                            retro = None;
+                        }
+                    }
+                    continue;
+                }
+            };
+            match made {
+                Some(edits) => make_edits(&mut history, &mut scene, &mut snow, edits),
+                None => log::info!("Unknown block; `blocks` lists them"),
+            }
+        }
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        angle += rotation_speed;
+        // While painting, J marks the corners of a box of blocks. Ctrl+C copies the box and
+        // Ctrl+X cuts it; Ctrl+V pastes the copy against the face under the cursor, O turns it
+        // a quarter turn and Ctrl+S saves it as a structure file. Library structures are
+        // copied with Period or the `stamp` command and pasted the same way.
+        if painter.enabled {
+            let hovered = mouse_position(&display, &framebuffer).and_then(|(x, y)| pick(&scene.objects, &camera, x, y));
+            let hovered_block = hovered.and_then(|(index, point)| match &scene.objects[index] {
+                Object::Cube(cube, false) => Some((cube.clone(), point)),
+                _ => None,
+            });
+            if display.window.is_key_pressed(Key::J, KeyRepeat::No) {
+                if let Some((cube, _)) = &hovered_block {
+                    selection.mark(cube.center);
+                    log::info!("Marked a selection corner at ({:.1}, {:.1}, {:.1})", cube.center.x, cube.center.y, cube.center.z);
+                }
+            }
+            let cut = ctrl && display.window.is_key_pressed(Key::X, KeyRepeat::No);
+            if cut || (ctrl && display.window.is_key_pressed(Key::C, KeyRepeat::No)) {
+                match selection.corners() {
+                    Some((a, b)) => {
+                        let (structure, indices) = Structure::capture(&scene.objects, &a, &b);
+                        log::info!("{} {} blocks", if cut { "Cut" } else { "Copied" }, structure.blocks.len());
+                        if cut {
+                            let removal = Structure::removal(&scene.objects, &indices);
+                            make_edits(&mut history, &mut scene, &mut snow, removal);
+                        }
+                        clipboard = structure;
+                    }
+                    None => log::info!("Mark two corners with J first"),
+                }
+            }
+            if ctrl && display.window.is_key_pressed(Key::V, KeyRepeat::No) {
+                if let Some((cube, point)) = &hovered_block {
+                    let edits = clipboard.placement(&scene.objects, &cell_against(cube, point));
+                    make_edits(&mut history, &mut scene, &mut snow, edits);
+                }
+            }
+            // Ctrl+F fills the box with the selected block; Ctrl+R turns every block like the
+            // one under the cursor into it.
+            if ctrl && display.window.is_key_pressed(Key::F, KeyRepeat::No) {
+                match selection.corners() {
+                    Some((a, b)) => {
+                        let edits = painter.fill(&scene.objects, &a, &b, painter.selected_name()).unwrap_or_default();
+                        make_edits(&mut history, &mut scene, &mut snow, edits);
+                    }
+                    None => log::info!("Mark two corners with J first"),
+                }
+            }
+            if ctrl && display.window.is_key_pressed(Key::R, KeyRepeat::No) {
+                let from = hovered_block.as_ref().and_then(|(cube, _)| painter.palette().name_of(&cube.material));
+                match from {
+                    Some(from) => {
+                        let edits = painter.replace(&scene.objects, from, painter.selected_name()).unwrap_or_default();
+                        make_edits(&mut history, &mut scene, &mut snow, edits);
+                    }
+                    None => log::info!("The block under the cursor is not a palette block"),
+                }
+            }
+            // Period takes the next structure from the library into the clipboard.
+            if display.window.is_key_pressed(Key::Period, KeyRepeat::No) && !library.is_empty() {
+                let next = prefab.map_or(0, |index| (index + 1) % library.len());
+                let name = library.names().nth(next).unwrap_or_default();
+                if let Some(structure) = library.get(name) {
+                    clipboard = structure.clone();
+                    log::info!("Copied structure {} ({} blocks)", name, clipboard.blocks.len());
+                }
+                prefab = Some(next);
+            }
+            if display.window.is_key_pressed(Key::O, KeyRepeat::No) {
+                clipboard = clipboard.rotated();
+                log::info!("Turned the copied blocks a quarter turn");
+            }
+            if ctrl && display.window.is_key_pressed(Key::S, KeyRepeat::No) && !clipboard.is_empty() {
+                match save_structure(&clipboard, painter.palette()) {
+                    Ok(filename) => log::info!("Saved structure {}", filename),
+                    Err(err) => log::error!("{}", err),
+                }
+            }
+        }
+        // B freezes the view as one layer per light group; Tab picks a group and
+        // PageUp/PageDown rebalance it without rendering again.
+        if display.window.is_key_pressed(Key::B, KeyRepeat::No) {
+            balancing = !balancing;
+            layers = None;
+            log::info!("Light balancing {}", if balancing { "on" } else { "off" });
+        }
+        if let Some(layers) = layers.as_mut() {
+            if display.window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+                layers.select_next();
+            }
+            if display.window.is_key_pressed(Key::PageUp, KeyRepeat::Yes) {
+                layers.scale_selected(1.1);
+            }
+            if display.window.is_key_pressed(Key::PageDown, KeyRepeat::Yes) {
+                layers.scale_selected(1.0 / 1.1);
+            }
+        }
+        if display.window.is_key_pressed(Key::M, KeyRepeat::No) {
+            motion_blur = !motion_blur;
+            log::info!("Motion blur {}", if motion_blur { "on" } else { "off" });
+        }
+        if display.window.is_key_pressed(Key::P, KeyRepeat::No) {
+            clock.toggle_pause();
+        }
+        if display.window.is_key_pressed(Key::N, KeyRepeat::Yes) {
+            clock.request_step();
+        }
+        // H throws a block of the material selected for painting from the eye.
+        if display.window.is_key_pressed(Key::H, KeyRepeat::No) {
+            if let Some(material) = painter.palette().get(painter.selected_name()) {
+                let (forward, _, up) = camera.basis();
+                let velocity = forward * THROW_SPEED + up * THROW_LIFT;
+                let block = ThrownBlock::new(camera.eye + forward * 1.5, velocity, THROW_SPIN, material.clone());
+                entities.throw(&mut scene.objects, block);
+            }
+        }
+
+        let presets = [
+            (Key::Key1, daycycle::DAWN),
+            (Key::Key2, daycycle::NOON),
+            (Key::Key3, daycycle::DUSK),
+            (Key::Key4, daycycle::MIDNIGHT),
+        ];
+        for (key, hours) in presets {
+            if display.window.is_key_pressed(key, KeyRepeat::No) {
+                scene.day.transition_to(hours, time_of_day_transition);
+            }
+        }
 
-        let sun_position = Vec3::new(radius * angle.cos(), radius * angle.sin(), 0.0);
-        objects[0] = Object::Cube(Cube {
-            center: sun_position,
-            size: 1.0,
-            material: pale_yellow.clone(),
-        }, true);
+        // F1-F8 recall a bookmark, Shift+F1-F8 store the current view in it.
+        let shift = display.window.is_key_down(Key::LeftShift) || display.window.is_key_down(Key::RightShift);
+        if display.window.is_key_pressed(Key::L, KeyRepeat::No) {
+            if shift {
+                scene.sun.switches.toggle_shadows("Sun");
+            } else {
+                scene.sun.switches.toggle("Sun");
+            }
+        }
+        for (slot, key) in (1..).zip(BOOKMARK_KEYS) {
+            if !display.window.is_key_pressed(key, KeyRepeat::No) {
+                continue;
+            }
+            if shift {
+                bookmarks.set(Bookmark::capture(slot, &camera, scene.day.hours()));
+                match bookmarks.save(BOOKMARKS_FILE) {
+                    Ok(()) => log::info!("Saved bookmark {}", slot),
+                    Err(err) => log::error!("{}", err),
+                }
+            } else if let Some(bookmark) = bookmarks.get(slot) {
+                camera.transition_to(bookmark.pose(), config.camera.bookmark_transition);
+                scene.day.transition_to(bookmark.time_of_day, time_of_day_transition);
+            } else {
+                log::info!("Bookmark {} is empty", slot);
+            }
+        }
 
-        if window.is_key_down(Key::W) {
-            camera.move_camera("forward");
+        // Alt+drag orbits around the block under the crosshair; a plain click opens doors.
+        // When painting, a drag paints every block it passes over, or with Shift knocks them
+        // out, as one undo step; a middle click builds onto the face under the cursor.
+        let mouse_down = display.window.get_mouse_down(MouseButton::Left);
+        let alt = display.window.is_key_down(Key::LeftAlt) || display.window.is_key_down(Key::RightAlt);
+        let cursor_position = mouse_position(&display, &framebuffer);
+        if mouse_down && !mouse_was_down {
+            if alt {
+                let (center_x, center_y) = (framebuffer.width as f32 / 2.0, framebuffer.height as f32 / 2.0);
+                orbit_pivot = pick(&scene.objects, &camera, center_x, center_y)
+                    .map(|(index, point)| match &scene.objects[index] {
+                        Object::Cube(cube, _) => cube.center,
+                        _ => point,
+                    });
+            } else if painter.enabled {
+                history.begin_group();
+                stroke = Some(None);
+            } else if let Some((x, y)) = cursor_position {
+                if let Some((index, _)) = pick(&scene.objects, &camera, x, y) {
+                    if let Object::Door(door) = &mut scene.objects[index] {
+                        door.toggle();
+                    }
+                }
+            }
         }
-        if window.is_key_down(Key::S) {
-            camera.move_camera("backward");
+        if let (true, Some(last), Some((x, y))) = (mouse_down, stroke, cursor_position) {
+            if let Some((index, _)) = pick(&scene.objects, &camera, x, y).filter(|&(index, _)| Some(index) != last) {
+                let edit = if shift { painter.remove(&scene.objects, index) } else { painter.paint(&scene.objects, index) };
+                if let Some(edit) = edit {
+                    let structural = edit.is_structural();
+                    history.apply(&mut scene.objects, edit);
+                    blocks_edited(&mut scene, &mut snow, structural);
+                }
+                stroke = Some(Some(index));
+            }
         }
-        if window.is_key_down(Key::A) {
-            camera.move_camera("left");
+        if !mouse_down && stroke.take().is_some() {
+            history.end_group();
         }
-        if window.is_key_down(Key::D) {
-            camera.move_camera("right");
+        let middle_down = display.window.get_mouse_down(MouseButton::Middle);
+        if painter.enabled && middle_down && !middle_was_down {
+            let placed = cursor_position
+                .and_then(|(x, y)| pick(&scene.objects, &camera, x, y))
+                .and_then(|(index, point)| painter.place(&scene.objects, index, &point));
+            if let Some(edit) = placed {
+                history.apply(&mut scene.objects, edit);
+                blocks_edited(&mut scene, &mut snow, true);
+            }
+        }
+        middle_was_down = middle_down;
+        if !mouse_down {
+            orbit_pivot = None;
+        }
+        if let (Some(pivot), Some((x, y)), Some((last_x, last_y))) = (orbit_pivot, cursor_position, last_cursor) {
+            camera.orbit_around(&pivot, (x - last_x) * DRAG_ORBIT_SPEED, (last_y - y) * DRAG_ORBIT_SPEED);
+            if config.camera.collision {
+                push_camera_out(&mut camera, &scene.objects);
+            }
+        }
+        mouse_was_down = mouse_down;
+        last_cursor = cursor_position;
+
+        // Right-drag a marquee to re-render just that region at higher quality; a plain
+        // right click goes back to the full frame.
+        let marquee_down = display.window.get_mouse_down(MouseButton::Right);
+        let cursor = mouse_position(&display, &framebuffer).map(|(x, y)| (x as usize, y as usize));
+        match (marquee_down, marquee_start, cursor) {
+            (true, None, Some(cursor)) => marquee_start = Some(cursor),
+            (false, Some(start), Some(end)) => {
+                let region = marquee_rect(start, end);
+                crop = (region.width >= 4 && region.height >= 4).then_some(region);
+                frozen = None;
+                marquee_start = None;
+            }
+            (false, Some(_), None) => marquee_start = None,
+            _ => {}
+        }
+
+        let window = &display.window;
+        let ticks = clock.advance(pacer.last_frame_time());
+        let dt = clock.dt();
+        let mut snow_changed = false;
+        for _ in 0..ticks {
+            let previous_angle = scene.day.angle;
+            let day_length = config.sun.day_length.max(1.0) * seasons.state().map_or(1.0, |state| state.day_length.max(0.1));
+            scene.day.update(dt, 2.0 * PI * dt / day_length);
+            let turned = ((scene.day.angle - previous_angle + PI).rem_euclid(2.0 * PI) - PI).max(0.0);
+            if seasons.advance(turned / (2.0 * PI)) {
+                apply_weather(&mut entities.particles, seasons.weather());
+            }
+            snow_changed |= snow.update(dt, seasons.weather(), scene.day.hours());
+            entities.update(&mut scene.objects, dt);
+            scene.aurora.time += dt;
+            camera.update(dt);
         }
+        if ticks > 0 {
+            if let Some(state) = seasons.state() {
+                apply_season(&mut scene.objects, &state, &leaves_texture, &grass_texture, &snowy_grass_texture);
+            }
+        }
+        if snow_changed {
+            apply_snow(&mut scene.objects, &snow);
+        }
+        if let (Some(elapsed), Some(timeline)) = (&mut playback, &timeline) {
+            *elapsed += pacer.last_frame_time().as_secs_f32();
+            let state = timeline.at(*elapsed);
+            if let Some(weather) = state.weather.filter(|&weather| weather != seasons.weather()) {
+                seasons.set_weather(weather);
+                apply_weather(&mut entities.particles, weather);
+            }
+            follow_timeline(&state, &mut camera, &mut scene, &mut snow);
+            if *elapsed > timeline.duration() {
+                log::info!("Timeline finished; I plays it again");
+                playback = None;
+            }
+        }
+        // Days are counted from midnight; the scene starts at dawn.
+        let calendar_day = (seasons.days() + daycycle::DAWN / 24.0) as u32;
+        let hours = scene.day.hours();
+        scene.eclipse = events.strength(EventKind::Eclipse, calendar_day, hours);
+        scene.aurora.strength = if config.aurora.enabled { 1.0 } else { events.strength(EventKind::Aurora, calendar_day, hours) };
+        entities.particles.set_rate(EmitterKind::Meteor, METEOR_RATE * events.strength(EventKind::Meteors, calendar_day, hours));
+        if entities.settle(&mut scene) {
+            blocks_edited(&mut scene, &mut snow, true);
+        }
+        scene.follow_day();
+
+        camera.speed_scale = if shift {
+            SPRINT_SCALE
+        } else if alt {
+            SLOW_SCALE
+        } else {
+            1.0
+        };
+        // Ctrl makes chords (Ctrl+S, Ctrl+C, ...), so the keys it is held with do not move.
+        let movement = [
+            (Key::W, "forward"),
+            (Key::S, "backward"),
+            (Key::A, "left"),
+            (Key::D, "right"),
+            (Key::Space, "up"),
+            (Key::C, "down"),
+        ];
+        for (key, direction) in movement {
+            if !ctrl && window.is_key_down(key) {
+                camera.move_camera(direction);
+            }
+        }
+        if window.is_key_down(Key::Q) {
+            camera.roll(rotation_speed);
+        }
+        if window.is_key_down(Key::E) {
+            camera.roll(-rotation_speed);
+        }
+        let orbiting = [Key::Left, Key::Right, Key::Up, Key::Down].iter().any(|&key| window.is_key_down(key));
         if window.is_key_down(Key::Left) {
             camera.orbit(rotation_speed, 0.0);
         }
@@ -576,13 +1765,130 @@ fn main() {
         if window.is_key_down(Key::Down) {
             camera.orbit(0.0, rotation_speed);
         }
+        if orbiting && config.camera.collision {
+            push_camera_out(&mut camera, &scene.objects);
+        }
+        camera.set_viewport(framebuffer.width, framebuffer.height);
+        let view = smoothing.follow(&camera, pacer.last_frame_time().as_secs_f32());
+
+        for object in scene.objects.iter_mut() {
+            if let Object::Billboard(billboard) = object {
+                billboard.face_towards(&view.eye);
+            }
+        }
 
-        render(&mut framebuffer, &objects, &camera, &sun_position, sun_intensity);
+        if let Some(recording) = &mut recorder {
+            recording.update(pacer.last_frame_time().as_secs_f32(), view.pose(), scene.day.hours(), seasons.weather(), snow.depth);
+        }
+        // Reflections come from the probes while the view moves and are traced once it stops.
+        scene.probes.enabled = reflection_probes && crop.is_none() && previous_motion.camera != view.pose();
+        let render_start = Instant::now();
+        if let Some(plane) = &mut renderer.reflection_plane {
+            plane.begin_frame(&view);
+        }
+        match crop {
+            _ if balancing => {
+                let layers = layers.get_or_insert_with(|| {
+                    LightLayers::new(framebuffer.width, framebuffer.height, |group, layer| {
+                        renderer.render_group(layer, &scene, &view, group);
+                    })
+                });
+                layers.composite(&mut framebuffer, renderer.settings.dither);
+            }
+            // The rest of the frame stays as it was when the region was picked.
+            Some(region) => {
+                match &frozen {
+                    Some(background) => framebuffer.blit(background, framebuffer.bounds()),
+                    None => {
+                        renderer.render(&mut framebuffer, &scene, &view);
+                        let mut background = Framebuffer::new(framebuffer.width, framebuffer.height);
+                        background.blit(&framebuffer, background.bounds());
+                        frozen = Some(background);
+                    }
+                }
+                renderer.render_region(&mut framebuffer, region, &scene, &view, crop_samples);
+            }
+            None if motion_blur => {
+                renderer.render_motion_blur(&mut framebuffer, &mut scene, &view, &previous_motion, MOTION_BLUR_SAMPLES);
+            }
+            None => match &mut time_slicer {
+                Some(slicer) => {
+                    slicer.render(&mut framebuffer, |image, tile| {
+                        renderer.render_region(image, tile, &scene, &view, 1);
+                    });
+                }
+                None => renderer.render(&mut framebuffer, &scene, &view),
+            },
+        }
+        previous_motion = Motion { camera: view.pose(), sun: scene.sun.direction };
+        // Sprites hide behind whatever the frame shows, which is not always this view's
+        // geometry when only part of the frame was traced.
+        let occluded = |point: &Vec3| renderer.gbuffer().occludes(&view, point);
+        entities.draw(&mut framebuffer, &view, occluded);
+        if let Some(region) = crop {
+            framebuffer.set_current_color(MARQUEE_COLOR);
+            framebuffer.outline_rect(Rect::new(region.x.saturating_sub(1), region.y.saturating_sub(1), region.width + 2, region.height + 2));
+        }
+        if let (Some(start), Some(end)) = (marquee_start, cursor) {
+            framebuffer.set_current_color(MARQUEE_COLOR);
+            framebuffer.outline_rect(marquee_rect(start, end));
+        }
+        let shown = match &retro {
+            Some(retro) => {
+                if retro_frame.width != framebuffer.width || retro_frame.height != framebuffer.height {
+                    retro_frame = Framebuffer::new(framebuffer.width, framebuffer.height);
+                }
+                retro_frame.blit(&framebuffer, retro_frame.bounds());
+                retro.apply(&mut retro_frame);
+                &retro_frame
+            }
+            None => &framebuffer,
+        };
+        stats_render_time += render_start.elapsed();
+
+        if display.window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            let metadata = RenderMetadata {
+                retro: retro.as_ref().map(|retro| retro.name.to_string()),
+                ..snapshot(&view, &scene, &seasons, &snow, renderer.settings, config.seed)
+            };
+            let saved = take_screenshot(&config.screenshot, shown, &metadata).and_then(|path| {
+                log::info!("Saved screenshot {}", path);
+                match &layers {
+                    Some(layers) => save_light_layers(&path, layers, &metadata).map(|files| log::info!("Saved light layers {}", files.join(", "))),
+                    None => Ok(()),
+                }
+            });
+            // Ctrl+F12 also copies the frame, ready to paste into a chat.
+            let copied = if ctrl { saved.and_then(|_| frame_clipboard.copy(shown)) } else { saved };
+            if let Err(err) = copied {
+                log::error!("{}", err);
+            } else if ctrl {
+                log::info!("Copied the frame to the clipboard");
+            }
+        }
+        stats_frames += 1;
+
+        if stats_start.elapsed() >= Duration::from_secs(1) {
+            let rays = renderer.take_stats();
+            log::info!(
+                "{} frames in {:.2}s, avg render {:.1} ms, {:.2}M rays/s ({} primary, {} secondary, {} shadow)",
+                stats_frames,
+                stats_start.elapsed().as_secs_f32(),
+                stats_render_time.as_secs_f32() * 1000.0 / stats_frames as f32,
+                rays.total() as f32 / stats_start.elapsed().as_secs_f32() / 1e6,
+                rays.primary,
+                rays.secondary,
+                rays.shadow,
+            );
+            stats_start = Instant::now();
+            stats_frames = 0;
+            stats_render_time = Duration::ZERO;
+        }
 
-        window
-            .update_with_buffer(&framebuffer.buffer, framebuffer.width, framebuffer.height)
-            .unwrap();
+        display.present(shown)?;
 
-        std::thread::sleep(frame_delay);
+        pacer.end_frame();
     }
+
+    Ok(())
 }
\ No newline at end of file
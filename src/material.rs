@@ -1,41 +1,222 @@
 // material.rs
 use crate::color::Color;
 use crate::texture::Texture;
-use std::rc::Rc;
+use std::sync::Arc;
+
+// How a surface splits the light that reaches it. Every weight lies in [0, 1], and
+// reflection and transmission together never pass on more light than arrives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Albedo {
+    pub diffuse_weight: f32,
+    pub specular_weight: f32,
+    pub reflectivity: f32,
+    pub transparency: f32,
+}
+
+impl Albedo {
+    pub fn new(diffuse_weight: f32, specular_weight: f32, reflectivity: f32, transparency: f32) -> Self {
+        Albedo { diffuse_weight, specular_weight, reflectivity, transparency }.validated()
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.transparency == 0.0
+    }
+
+    fn validated(self) -> Self {
+        let clamp = |weight: f32| if weight.is_nan() { 0.0 } else { weight.clamp(0.0, 1.0) };
+        let mut albedo = Albedo {
+            diffuse_weight: clamp(self.diffuse_weight),
+            specular_weight: clamp(self.specular_weight),
+            reflectivity: clamp(self.reflectivity),
+            transparency: clamp(self.transparency),
+        };
+        let passed_on = albedo.reflectivity + albedo.transparency;
+        if passed_on > 1.0 {
+            albedo.reflectivity /= passed_on;
+            albedo.transparency /= passed_on;
+        }
+        if albedo != self {
+            log::warn!("Adjusted out of range albedo {:?} to {:?}", self, albedo);
+        }
+        albedo
+    }
+}
+
+// Shim for the old `[diffuse, specular, reflectivity, transparency]` arrays.
+impl From<[f32; 4]> for Albedo {
+    fn from([diffuse_weight, specular_weight, reflectivity, transparency]: [f32; 4]) -> Self {
+        Albedo::new(diffuse_weight, specular_weight, reflectivity, transparency)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Material {
     pub diffuse: Color,
     pub specular: f32,
-    pub albedo: [f32; 4],
+    pub albedo: Albedo,
     pub refractive_index: f32,
-    pub texture: Option<Rc<Texture>>, 
+    // How much more blue light bends than green, and green than red, so light refracting
+    // through splits into rainbow fringes where dispersion is traced; 0 bends every colour alike.
+    pub dispersion: f32,
+    pub texture: Option<Arc<Texture>>, 
+    pub bevel: f32,
+    pub tint: Color,
+    pub snow: f32,
+    // Applied to face UVs before sampling the texture: rotation in quarter turns about the
+    // centre of the face, then tiling, then offset.
+    pub uv_scale: (f32, f32),
+    pub uv_offset: (f32, f32),
+    pub uv_quarter_turns: u8,
+    // Share of sunlight arriving from behind that passes through, for thin blocks like leaves.
+    pub translucency: f32,
+    // Streaks the specular highlight along the texture's v axis (the grain) as it nears 1.
+    pub anisotropy: f32,
+    // Light given off regardless of lighting. The map, when there is one, masks and colours it
+    // texel by texel so only parts of the block glow.
+    pub emission: Color,
+    pub emission_map: Option<Arc<Texture>>,
+    // Light passing through shifts towards `deep_color` the further it travels inside before
+    // reaching the bottom; `depth_scale` is the distance where it is about two thirds there.
+    pub deep_color: Color,
+    pub depth_scale: f32,
+    // Strength of the foam band drawn on top faces next to solid blocks.
+    pub foam: f32,
 }
 
 impl Material {
     pub fn new(
         diffuse: Color,
         specular: f32,
-        albedo: [f32; 4],
+        albedo: impl Into<Albedo>,
         refractive_index: f32,
-        texture: Option<Rc<Texture>>, 
+        texture: Option<Arc<Texture>>, 
     ) -> Self {
         Material {
             diffuse,
             specular,
-            albedo,
+            albedo: albedo.into(),
             refractive_index,
+            dispersion: 0.0,
             texture,
+            bevel: 0.0,
+            tint: Color::new(255, 255, 255),
+            snow: 0.0,
+            uv_scale: (1.0, 1.0),
+            uv_offset: (0.0, 0.0),
+            uv_quarter_turns: 0,
+            translucency: 0.0,
+            anisotropy: 0.0,
+            emission: Color::black(),
+            emission_map: None,
+            deep_color: Color::black(),
+            depth_scale: 0.0,
+            foam: 0.0,
         }
     }
 
+    // Rounds shading normals within `margin` (in UV units) of a cube edge.
+    pub fn with_bevel(mut self, margin: f32) -> Self {
+        self.bevel = margin.clamp(0.0, 0.5);
+        self
+    }
+
+    pub fn with_translucency(mut self, translucency: f32) -> Self {
+        self.translucency = translucency.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy.clamp(0.0, 0.95);
+        self
+    }
+
+    pub fn with_emission(mut self, emission: Color, emission_map: Option<Arc<Texture>>) -> Self {
+        self.emission = emission;
+        self.emission_map = emission_map;
+        self
+    }
+
+    pub fn with_depth_tint(mut self, deep_color: Color, depth_scale: f32) -> Self {
+        self.deep_color = deep_color;
+        self.depth_scale = depth_scale.max(0.0);
+        self
+    }
+
+    pub fn with_foam(mut self, foam: f32) -> Self {
+        self.foam = foam.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_dispersion(mut self, dispersion: f32) -> Self {
+        self.dispersion = dispersion.max(0.0);
+        self
+    }
+
+    // Refractive indices for red, green and blue light.
+    pub fn channel_indices(&self) -> [f32; 3] {
+        [self.refractive_index - self.dispersion, self.refractive_index, self.refractive_index + self.dispersion]
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.to_hex() != 0
+    }
+
+    // Tiles the texture `scale` times across a face, shifts it by `offset` and turns it
+    // counter-clockwise by `quarter_turns` times 90 degrees.
+    pub fn with_uv_transform(mut self, scale: (f32, f32), offset: (f32, f32), quarter_turns: u8) -> Self {
+        self.uv_scale = scale;
+        self.uv_offset = offset;
+        self.uv_quarter_turns = quarter_turns % 4;
+        self
+    }
+
+    // Texture coordinates for a point at `(u, v)` on a face. The result may leave 0..1;
+    // textures wrap.
+    pub fn transform_uv(&self, (u, v): (f32, f32)) -> (f32, f32) {
+        let (mut x, mut y) = (u - 0.5, v - 0.5);
+        for _ in 0..self.uv_quarter_turns % 4 {
+            (x, y) = (-y, x);
+        }
+        (
+            (x + 0.5) * self.uv_scale.0 + self.uv_offset.0,
+            (y + 0.5) * self.uv_scale.1 + self.uv_offset.1,
+        )
+    }
+
+    // How much the transform stretches texels, for picking mip levels.
+    pub fn uv_stretch(&self) -> f32 {
+        self.uv_scale.0.abs().max(self.uv_scale.1.abs())
+    }
+
+    pub fn mirror() -> Self {
+        Material::new(Color::black(), 1.0, Albedo::new(0.0, 0.0, 1.0, 0.0), 0.0, None)
+    }
+
+    pub fn glass(tint: Color) -> Self {
+        Material::new(tint, 125.0, Albedo::new(0.05, 0.5, 0.0, 0.95), 1.5, None).with_dispersion(0.02)
+    }
+
     pub fn black() -> Self {
         Material {
             diffuse: Color::black(),
             specular: 0.0,
-            albedo: [0.0; 4],
+            albedo: Albedo::new(0.0, 0.0, 0.0, 0.0),
             refractive_index: 0.0,
+            dispersion: 0.0,
             texture: None,
+            bevel: 0.0,
+            tint: Color::new(255, 255, 255),
+            snow: 0.0,
+            uv_scale: (1.0, 1.0),
+            uv_offset: (0.0, 0.0),
+            uv_quarter_turns: 0,
+            translucency: 0.0,
+            anisotropy: 0.0,
+            emission: Color::black(),
+            emission_map: None,
+            deep_color: Color::black(),
+            depth_scale: 0.0,
+            foam: 0.0,
         }
     }
 }
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+use crate::scene::{Object, Scene};
+use crate::texture::Texture;
+
+// Logs roughly how much memory the textures and the scene's objects take. Textures count
+// their image and mip chain; objects count the list itself, as the materials inside share
+// their textures. There is no acceleration structure to count: rays test the list directly.
+pub fn report(textures: &[Arc<Texture>], scene: &Scene) {
+    let texture_bytes: usize = textures.iter().map(|texture| texture.memory()).sum();
+    let object_bytes = scene.objects.capacity() * std::mem::size_of::<Object>();
+    log::info!(
+        "Memory: {} in {} textures, {} in {} objects",
+        format_bytes(texture_bytes),
+        textures.len(),
+        format_bytes(object_bytes),
+        scene.objects.len()
+    );
+}
+
+// Like "512 B", "3.4 KiB" or "1.2 MiB".
+pub fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f32 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f32 / 1_048_576.0),
+    }
+}
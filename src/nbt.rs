@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::io::Read;
+use flate2::read::GzDecoder;
+
+// Lists and compounds nested deeper than this are refused rather than overflowing the stack,
+// as Minecraft does.
+const MAX_DEPTH: usize = 512;
+
+// Minimal reader for Minecraft's Named Binary Tag format (big-endian, usually gzipped).
+#[derive(Debug, Clone)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    LongArray(Vec<i64>),
+    // Floats, doubles and int arrays, which no importer reads: skipped over, not kept.
+    Other,
+}
+
+impl Tag {
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value as i64),
+            Tag::Short(value) => Some(value as i64),
+            Tag::Int(value) => Some(value as i64),
+            Tag::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+// Parses a (possibly gzipped) NBT file, returning the root tag's name and value.
+pub fn parse(bytes: &[u8]) -> Result<(String, Tag), String> {
+    let mut data = Vec::new();
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        GzDecoder::new(bytes).read_to_end(&mut data).map_err(|err| err.to_string())?;
+        &data[..]
+    } else {
+        bytes
+    };
+
+    let mut reader = Reader { bytes, position: 0, depth: 0 };
+    let kind = reader.u8()?;
+    if kind != 10 {
+        return Err(format!("root tag has type {}, expected a compound", kind));
+    }
+    let name = reader.string()?;
+    let root = reader.payload(kind)?;
+    Ok((name, root))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    // Lists and compounds currently open.
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len());
+        let Some(end) = end else {
+            return Err(format!("unexpected end of data at byte {}", self.position));
+        };
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        let length = self.i32()?;
+        usize::try_from(length).map_err(|_| format!("negative length {}", length))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.i16()? as u16 as usize;
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn payload(&mut self, kind: u8) -> Result<Tag, String> {
+        if kind == 9 || kind == 10 {
+            if self.depth == MAX_DEPTH {
+                return Err(format!("tags nested more than {} deep at byte {}", MAX_DEPTH, self.position));
+            }
+            self.depth += 1;
+            let tag = self.nested(kind);
+            self.depth -= 1;
+            return tag;
+        }
+        Ok(match kind {
+            1 => Tag::Byte(self.u8()? as i8),
+            2 => Tag::Short(self.i16()?),
+            3 => Tag::Int(self.i32()?),
+            4 => Tag::Long(self.i64()?),
+            5 => {
+                self.take(4)?;
+                Tag::Other
+            }
+            6 => {
+                self.take(8)?;
+                Tag::Other
+            }
+            7 => {
+                let length = self.length()?;
+                Tag::ByteArray(self.take(length)?.iter().map(|&b| b as i8).collect())
+            }
+            8 => Tag::String(self.string()?),
+            11 => {
+                let length = self.length()?;
+                self.take(length.saturating_mul(4))?;
+                Tag::Other
+            }
+            12 => {
+                let length = self.length()?;
+                let mut values = Vec::with_capacity(length.min(1 << 16));
+                for _ in 0..length {
+                    values.push(self.i64()?);
+                }
+                Tag::LongArray(values)
+            }
+            _ => return Err(format!("unknown tag type {} at byte {}", kind, self.position)),
+        })
+    }
+
+    // Lists and compounds, which hold further tags.
+    fn nested(&mut self, kind: u8) -> Result<Tag, String> {
+        Ok(match kind {
+            9 => {
+                let element = self.u8()?;
+                let length = self.length()?;
+                let mut items = Vec::with_capacity(length.min(1 << 16));
+                for _ in 0..length {
+                    items.push(self.payload(element)?);
+                }
+                Tag::List(items)
+            }
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let child = self.u8()?;
+                    if child == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    entries.insert(name, self.payload(child)?);
+                }
+                Tag::Compound(entries)
+            }
+            _ => unreachable!("tag type {} holds no tags", kind),
+        })
+    }
+}
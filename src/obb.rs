@@ -0,0 +1,78 @@
+use nalgebra_glm::{Vec3, rotate_y_vec3};
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::material::Material;
+
+// Box rotated by `yaw` radians around the vertical axis through its center.
+pub struct Obb {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub yaw: f32,
+    pub material: Material,
+}
+
+impl Obb {
+    pub fn new(center: Vec3, half_extents: Vec3, yaw: f32, material: Material) -> Self {
+        Obb { center, half_extents, yaw, material }
+    }
+
+    fn to_local(&self, point: &Vec3) -> Vec3 {
+        rotate_y_vec3(&(point - self.center), -self.yaw)
+    }
+
+    pub fn contains(&self, point: &Vec3, margin: f32) -> bool {
+        let local = self.to_local(point);
+        (0..3).all(|axis| local[axis].abs() <= self.half_extents[axis] + margin)
+    }
+}
+
+impl RayIntersect for Obb {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let origin = self.to_local(ray_origin);
+        let direction = rotate_y_vec3(ray_direction, -self.yaw);
+
+        let mut t_min = f32::NEG_INFINITY;
+        let mut t_max = f32::INFINITY;
+        let mut enter_axis = 0;
+        let mut exit_axis = 0;
+
+        for axis in 0..3 {
+            let mut t0 = (-self.half_extents[axis] - origin[axis]) / direction[axis];
+            let mut t1 = (self.half_extents[axis] - origin[axis]) / direction[axis];
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_min {
+                t_min = t0;
+                enter_axis = axis;
+            }
+            if t1 < t_max {
+                t_max = t1;
+                exit_axis = axis;
+            }
+            if t_min > t_max {
+                return Intersect::empty();
+            }
+        }
+
+        let (distance, axis) = if t_min >= 0.0 { (t_min, enter_axis) } else { (t_max, exit_axis) };
+        if distance < 0.0 {
+            return Intersect::empty();
+        }
+
+        let local_point = origin + direction * distance;
+        let mut local_normal = Vec3::zeros();
+        local_normal[axis] = local_point[axis].signum();
+
+        let (u_axis, v_axis) = match axis {
+            0 => (2, 1),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let u = (local_point[u_axis] + self.half_extents[u_axis]).rem_euclid(1.0);
+        let v = (local_point[v_axis] + self.half_extents[v_axis]).rem_euclid(1.0);
+
+        let point = ray_origin + ray_direction * distance;
+        let normal = rotate_y_vec3(&local_normal, self.yaw);
+        Intersect::new(point, normal, distance, self.material.clone(), Some((u, v)))
+    }
+}
@@ -0,0 +1,271 @@
+use nalgebra_glm::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use crate::cube::Cube;
+use crate::error::{Error, Result};
+use crate::material::Material;
+use crate::texture::Texture;
+
+// Face normals in the order x, y, z for each sign; `vn` indices are 1-based in OBJ.
+const NORMALS: [[f32; 3]; 6] = [
+    [1.0, 0.0, 0.0], [-1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0], [0.0, -1.0, 0.0],
+    [0.0, 0.0, 1.0], [0.0, 0.0, -1.0],
+];
+
+#[derive(Debug, Default)]
+pub struct ExportStats {
+    pub quads: usize,
+    pub materials: usize,
+    pub textures: usize,
+}
+
+struct Quad {
+    corners: [Vec3; 4],
+    uvs: [(f32, f32); 4],
+    normal: usize,
+    material: usize,
+}
+
+// Writes the cubes as a Wavefront OBJ with an .mtl and PNG textures next to it. Unit cubes
+// on the integer grid are greedy-meshed: hidden faces are dropped and coplanar faces of
+// the same material are merged into larger quads with tiling UVs.
+pub fn export_obj(filename: &str, cubes: &[&Cube]) -> Result<ExportStats> {
+    let path = Path::new(filename);
+    let stem = path.file_stem().map_or_else(|| String::from("scene"), |stem| stem.to_string_lossy().into_owned());
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let export_error = |source| Error::Export { path: filename.to_string(), source };
+
+    let mut materials: Vec<&Material> = Vec::new();
+    let material_ids: Vec<usize> = cubes
+        .iter()
+        .map(|cube| {
+            materials
+                .iter()
+                .position(|material| same_material(material, &cube.material))
+                .unwrap_or_else(|| {
+                    materials.push(&cube.material);
+                    materials.len() - 1
+                })
+        })
+        .collect();
+
+    let mut grid: HashMap<[i32; 3], usize> = HashMap::new();
+    let mut loose = Vec::new();
+    for (cube, &material) in cubes.iter().zip(&material_ids) {
+        match cube.grid_cell() {
+            Some(cell) => {
+                grid.insert(cell, material);
+            }
+            None => loose.push((*cube, material)),
+        }
+    }
+
+    let mut quads = greedy_quads(&grid, &materials);
+    for (cube, material) in loose {
+        quads.extend(box_quads(cube, material));
+    }
+
+    let mut obj = String::new();
+    let _ = writeln!(obj, "# Exported by Refractor\nmtllib {}.mtl", stem);
+    for normal in NORMALS {
+        let _ = writeln!(obj, "vn {} {} {}", normal[0], normal[1], normal[2]);
+    }
+    quads.sort_by_key(|quad| quad.material);
+    let mut current_material = None;
+    for (index, quad) in quads.iter().enumerate() {
+        if current_material != Some(quad.material) {
+            let _ = writeln!(obj, "usemtl material_{}", quad.material);
+            current_material = Some(quad.material);
+        }
+        // Merged quads span several blocks, so this matches the renderer's per-face transform
+        // exactly for whole-number tiling.
+        let uvs = quad.uvs.map(|uv| materials[quad.material].transform_uv(uv));
+        for (corner, (u, v)) in quad.corners.iter().zip(uvs) {
+            let _ = writeln!(obj, "v {} {} {}\nvt {} {}", corner.x, corner.y, corner.z, u, v);
+        }
+        let base = index * 4 + 1;
+        let normal = quad.normal + 1;
+        let _ = writeln!(
+            obj,
+            "f {0}/{0}/{4} {1}/{1}/{4} {2}/{2}/{4} {3}/{3}/{4}",
+            base, base + 1, base + 2, base + 3, normal,
+        );
+    }
+
+    let mut mtl = String::new();
+    let mut texture_files: Vec<(*const Texture, String)> = Vec::new();
+    // Each texture is written once, however many materials use it.
+    let texture_file = |texture: &Arc<Texture>, written: &mut Vec<(*const Texture, String)>| {
+        let key = Arc::as_ptr(texture);
+        if let Some((_, file)) = written.iter().find(|(ptr, _)| *ptr == key) {
+            return Ok(file.clone());
+        }
+        let file = format!("{}_texture{}.png", stem, written.len());
+        let texture_path = directory.join(&file);
+        texture.image().save(&texture_path).map_err(|source| Error::Texture {
+            path: texture_path.to_string_lossy().into_owned(),
+            source,
+        })?;
+        written.push((key, file.clone()));
+        Ok::<_, Error>(file)
+    };
+    for (index, material) in materials.iter().enumerate() {
+        let color = if material.texture.is_some() { material.tint } else { material.diffuse }.to_vec3() / 255.0;
+        let _ = writeln!(mtl, "newmtl material_{}", index);
+        let _ = writeln!(mtl, "Kd {:.4} {:.4} {:.4}", color.x, color.y, color.z);
+        let _ = writeln!(mtl, "Ks {0:.4} {0:.4} {0:.4}", material.albedo.specular_weight);
+        let _ = writeln!(mtl, "Ns {:.1}", material.specular.min(1000.0));
+        let _ = writeln!(mtl, "d {:.4}", 1.0 - material.albedo.transparency);
+        if material.refractive_index > 0.0 {
+            let _ = writeln!(mtl, "Ni {:.4}", material.refractive_index);
+        }
+
+        if material.is_emissive() {
+            let emission = material.emission.to_vec3() / 255.0;
+            let _ = writeln!(mtl, "Ke {:.4} {:.4} {:.4}", emission.x, emission.y, emission.z);
+        }
+        if let Some(texture) = &material.texture {
+            let _ = writeln!(mtl, "map_Kd {}", texture_file(texture, &mut texture_files)?);
+        }
+        if let (true, Some(map)) = (material.is_emissive(), &material.emission_map) {
+            let _ = writeln!(mtl, "map_Ke {}", texture_file(map, &mut texture_files)?);
+        }
+        mtl.push('\n');
+    }
+
+    std::fs::write(path, obj).map_err(export_error)?;
+    std::fs::write(directory.join(format!("{}.mtl", stem)), mtl).map_err(export_error)?;
+
+    Ok(ExportStats { quads: quads.len(), materials: materials.len(), textures: texture_files.len() })
+}
+
+fn same_texture(a: &Option<Arc<Texture>>, b: &Option<Arc<Texture>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_material(a: &Material, b: &Material) -> bool {
+    same_texture(&a.texture, &b.texture)
+        && a.diffuse.to_hex() == b.diffuse.to_hex()
+        && a.tint.to_hex() == b.tint.to_hex()
+        && a.albedo == b.albedo
+        && a.specular == b.specular
+        && a.refractive_index == b.refractive_index
+        && a.uv_scale == b.uv_scale
+        && a.uv_offset == b.uv_offset
+        && a.uv_quarter_turns == b.uv_quarter_turns
+        && a.emission.to_hex() == b.emission.to_hex()
+        && same_texture(&a.emission_map, &b.emission_map)
+}
+
+fn greedy_quads(grid: &HashMap<[i32; 3], usize>, materials: &[&Material]) -> Vec<Quad> {
+    let transparent = |id: usize| materials[id].albedo.transparency > 0.0;
+    let mut quads = Vec::new();
+
+    for axis in 0..3 {
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        for sign in [1i32, -1] {
+            // Visible faces grouped by slice, keyed by their (u, v) cell.
+            let mut slices: HashMap<i32, HashMap<(i32, i32), usize>> = HashMap::new();
+            for (&cell, &material) in grid {
+                let mut neighbour = cell;
+                neighbour[axis] += sign;
+                let hidden = grid.get(&neighbour).is_some_and(|&other| other == material || !transparent(other));
+                if !hidden {
+                    slices.entry(cell[axis]).or_default().insert((cell[u_axis], cell[v_axis]), material);
+                }
+            }
+
+            for (slice, faces) in slices {
+                let mut cells: Vec<(i32, i32)> = faces.keys().copied().collect();
+                cells.sort_by_key(|&(u, v)| (v, u));
+                let mut used = HashSet::new();
+
+                for (u0, v0) in cells {
+                    if used.contains(&(u0, v0)) {
+                        continue;
+                    }
+                    let material = faces[&(u0, v0)];
+                    let free = |u: i32, v: i32, used: &HashSet<(i32, i32)>| faces.get(&(u, v)) == Some(&material) && !used.contains(&(u, v));
+
+                    let mut width = 1;
+                    while free(u0 + width, v0, &used) {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    while (0..width).all(|du| free(u0 + du, v0 + height, &used)) {
+                        height += 1;
+                    }
+                    for dv in 0..height {
+                        for du in 0..width {
+                            used.insert((u0 + du, v0 + dv));
+                        }
+                    }
+
+                    let plane = slice as f32 + 0.5 * sign as f32;
+                    let (u_min, u_max) = (u0 as f32 - 0.5, (u0 + width) as f32 - 0.5);
+                    let (v_min, v_max) = (v0 as f32 - 0.5, (v0 + height) as f32 - 0.5);
+                    let point = |u: f32, v: f32| {
+                        let mut p = Vec3::zeros();
+                        p[axis] = plane;
+                        p[u_axis] = u;
+                        p[v_axis] = v;
+                        p
+                    };
+                    let mut corners = [point(u_min, v_min), point(u_max, v_min), point(u_max, v_max), point(u_min, v_max)];
+                    if sign < 0 {
+                        corners.reverse();
+                    }
+                    let uvs = corners.map(|corner| face_uv(&(corner + Vec3::new(0.5, 0.5, 0.5)), axis));
+                    let normal = axis * 2 + if sign > 0 { 0 } else { 1 };
+                    quads.push(Quad { corners, uvs, normal, material });
+                }
+            }
+        }
+    }
+
+    quads
+}
+
+fn box_quads(cube: &Cube, material: usize) -> Vec<Quad> {
+    let half = cube.size / 2.0;
+    let min = cube.center - Vec3::new(half, half, half);
+    let mut quads = Vec::new();
+
+    for axis in 0..3 {
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        for sign in [1.0f32, -1.0] {
+            let point = |u: f32, v: f32| {
+                let mut p = cube.center;
+                p[axis] += half * sign;
+                p[u_axis] += half * u;
+                p[v_axis] += half * v;
+                p
+            };
+            let mut corners = [point(-1.0, -1.0), point(1.0, -1.0), point(1.0, 1.0), point(-1.0, 1.0)];
+            if sign < 0.0 {
+                corners.reverse();
+            }
+            let uvs = corners.map(|corner| face_uv(&((corner - min) / cube.size), axis));
+            let normal = axis * 2 + if sign > 0.0 { 0 } else { 1 };
+            quads.push(Quad { corners, uvs, normal, material });
+        }
+    }
+
+    quads
+}
+
+// Same axis convention as `Cube::get_uv`, in units where one block spans 0..1.
+fn face_uv(local: &Vec3, axis: usize) -> (f32, f32) {
+    match axis {
+        0 => (local.z, local.y),
+        1 => (local.x, local.z),
+        _ => (local.x, local.y),
+    }
+}
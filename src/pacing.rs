@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameRate {
+    Capped(u32),
+    Uncapped,
+}
+
+pub struct FramePacer {
+    pub frame_rate: FrameRate,
+    frame_start: Instant,
+    last_frame_time: Duration,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: FrameRate) -> Self {
+        FramePacer {
+            frame_rate,
+            frame_start: Instant::now(),
+            last_frame_time: Duration::ZERO,
+        }
+    }
+
+    pub fn target_frame_time(&self) -> Option<Duration> {
+        match self.frame_rate {
+            FrameRate::Capped(fps) if fps > 0 => Some(Duration::from_secs_f64(1.0 / fps as f64)),
+            _ => None,
+        }
+    }
+
+    pub fn toggle_cap(&mut self, fps: u32) {
+        self.frame_rate = match self.frame_rate {
+            FrameRate::Capped(_) => FrameRate::Uncapped,
+            FrameRate::Uncapped => FrameRate::Capped(fps),
+        };
+        log::info!("Frame rate set to {:?}", self.frame_rate);
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    // Sleeps only for what is left of the target frame time after rendering and presenting.
+    pub fn end_frame(&mut self) {
+        if let Some(target) = self.target_frame_time() {
+            let elapsed = self.frame_start.elapsed();
+            if elapsed < target {
+                std::thread::sleep(target - elapsed);
+            }
+        }
+        self.last_frame_time = self.frame_start.elapsed();
+    }
+
+    pub fn last_frame_time(&self) -> Duration {
+        self.last_frame_time
+    }
+}
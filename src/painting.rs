@@ -0,0 +1,149 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use crate::cube::Cube;
+use crate::history::Edit;
+use crate::material::Material;
+use crate::palette::Palette;
+use crate::scene::Object;
+
+// Turns clicks into block edits with the selected palette material: repainting a block in
+// place, keeping its shape, so a texture or shading tweak can be tried on the scene itself,
+// or building and knocking out blocks. The edits are made through the edit history.
+pub struct Painter {
+    pub enabled: bool,
+    palette: Palette,
+    selected: usize,
+}
+
+impl Painter {
+    pub fn new(palette: Palette) -> Self {
+        Painter {
+            enabled: false,
+            palette,
+            selected: 0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        if self.enabled {
+            log::info!("Painting with {}", self.selected_name());
+        } else {
+            log::info!("Painting off");
+        }
+    }
+
+    // Moves the selection `step` entries along the palette, wrapping around.
+    pub fn select(&mut self, step: isize) {
+        let count = self.palette.names().count() as isize;
+        self.selected = (self.selected as isize + step).rem_euclid(count) as usize;
+        log::info!("Painting with {}", self.selected_name());
+    }
+
+    pub fn selected_name(&self) -> &'static str {
+        self.palette.names().nth(self.selected).unwrap_or("nothing")
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    fn selected_material(&self) -> Option<&Material> {
+        self.palette.get(self.selected_name())
+    }
+
+    // Gives the block at `index` the selected material; None if it is not a paintable block.
+    pub fn paint(&self, objects: &[Object], index: usize) -> Option<Edit> {
+        self.paint_with(objects, index, self.selected_material()?)
+    }
+
+    fn paint_with(&self, objects: &[Object], index: usize, material: &Material) -> Option<Edit> {
+        let Object::Cube(cube, false) = &objects[index] else {
+            return None;
+        };
+        // Snow lying on the block stays where it is.
+        let after = Material { snow: cube.material.snow, ..material.clone() };
+        Some(Edit::Paint { index, before: cube.material.clone(), after })
+    }
+
+    // A block of the selected material against the face of block `index` that `point` lies
+    // on, unless something is already there.
+    pub fn place(&self, objects: &[Object], index: usize, point: &Vec3) -> Option<Edit> {
+        let material = self.selected_material()?;
+        let Object::Cube(target, false) = &objects[index] else {
+            return None;
+        };
+        let center = cell_against(target, point);
+        let occupied = objects.iter().any(|object| matches!(object, Object::Cube(cube, false) if cube.contains(&center, -1e-3)));
+        if occupied {
+            return None;
+        }
+        let cube = Cube { center, size: target.size, material: material.clone() };
+        Some(Edit::Place { index: objects.len(), cube })
+    }
+
+    // Makes every cell of the box between block centres `a` and `b` a `block`: blocks already
+    // there are repainted and empty cells are built on. None if there is no such block.
+    pub fn fill(&self, objects: &[Object], a: &Vec3, b: &Vec3, block: &str) -> Option<Vec<Edit>> {
+        let material = self.palette.get(block)?;
+        let cells: HashMap<[i32; 3], usize> = objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| match object {
+                Object::Cube(cube, false) => Some((cell_of(&cube.center), index)),
+                _ => None,
+            })
+            .collect();
+        let (min, max) = (cell_of(&a.inf(b)), cell_of(&a.sup(b)));
+        let mut edits = Vec::new();
+        let mut placed = 0;
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    match cells.get(&[x, y, z]) {
+                        Some(&index) => edits.extend(self.paint_with(objects, index, material)),
+                        None => {
+                            let center = a.inf(b) + Vec3::new((x - min[0]) as f32, (y - min[1]) as f32, (z - min[2]) as f32);
+                            edits.push(Edit::Place { index: objects.len() + placed, cube: Cube { center, size: 1.0, material: material.clone() } });
+                            placed += 1;
+                        }
+                    }
+                }
+            }
+        }
+        Some(edits)
+    }
+
+    // Repaints every `from` block in the scene as a `to` block. None if either is unknown.
+    pub fn replace(&self, objects: &[Object], from: &str, to: &str) -> Option<Vec<Edit>> {
+        self.palette.get(from)?;
+        let material = self.palette.get(to)?;
+        let edits = objects
+            .iter()
+            .enumerate()
+            .filter(|(_, object)| matches!(object, Object::Cube(cube, false) if self.palette.name_of(&cube.material) == Some(from)))
+            .filter_map(|(index, _)| self.paint_with(objects, index, material))
+            .collect();
+        Some(edits)
+    }
+
+    pub fn remove(&self, objects: &[Object], index: usize) -> Option<Edit> {
+        match &objects[index] {
+            Object::Cube(cube, false) => Some(Edit::Remove { index, cube: cube.clone() }),
+            _ => None,
+        }
+    }
+}
+
+// Centre of the block-sized cell against the face of `cube` that `point` lies on.
+pub fn cell_against(cube: &Cube, point: &Vec3) -> Vec3 {
+    let offset = point - cube.center;
+    let axis = offset.abs().imax();
+    let mut normal = Vec3::zeros();
+    normal[axis] = offset[axis].signum();
+    cube.center + normal * cube.size
+}
+
+fn cell_of(point: &Vec3) -> [i32; 3] {
+    [point.x.round() as i32, point.y.round() as i32, point.z.round() as i32]
+}
@@ -0,0 +1,248 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use std::path::Path;
+use std::sync::Arc;
+use crate::color::Color;
+use crate::material::Material;
+use crate::registry::MaterialRegistry;
+use crate::rng::Rng;
+use crate::texture::Texture;
+
+const FALLBACK_SIZE: u32 = 16;
+// Texels this much brighter than the texture's average glow fully.
+const GLOW_CONTRAST: f32 = 0.25;
+
+struct BlockSpec {
+    name: &'static str,
+    // Texture file inside the palette's directory; a procedural one is used when it is missing.
+    texture: Option<&'static str>,
+    base_color: [u8; 3],
+    variation: u8,
+    albedo: [f32; 4],
+    specular: f32,
+    refractive_index: f32,
+    dispersion: f32,
+    // Tint of light passing through transparent blocks.
+    diffuse: Color,
+    bevel: f32,
+    translucency: f32,
+    anisotropy: f32,
+    // Glow of the block's brightest texels.
+    emission: Color,
+    // Colour seen through deep water, and the depth it takes to get there.
+    deep_color: Color,
+    depth_scale: f32,
+    foam: f32,
+    // Texture tiling, offset and quarter turns on every face (see `Material::with_uv_transform`).
+    uv_scale: (f32, f32),
+    uv_offset: (f32, f32),
+    uv_quarter_turns: u8,
+}
+
+const BLOCKS: [BlockSpec; 15] = [
+    block("grass", Some("Grass.png"), [95, 159, 53], 30, [0.9, 0.1, 0.0, 0.0], 1.0, 0.06),
+    block("dirt", Some("Dirt.png"), [134, 96, 67], 24, [0.9, 0.1, 0.0, 0.0], 1.0, 0.06),
+    block("stone", Some("Stone.png"), [125, 125, 125], 20, [0.9, 0.1, 0.0, 0.0], 1.0, 0.06),
+    block("polished_stone", None, [150, 150, 155], 6, [0.7, 0.4, 0.3, 0.0], 60.0, 0.03),
+    block("sand", None, [219, 207, 163], 18, [0.9, 0.1, 0.0, 0.0], 1.0, 0.06),
+    BlockSpec {
+        anisotropy: 0.8,
+        ..block("wood", Some("Trunk.png"), [102, 81, 51], 20, [0.9, 0.2, 0.0, 0.0], 20.0, 0.06)
+    },
+    block("planks", None, [162, 130, 78], 16, [0.9, 0.15, 0.0, 0.0], 5.0, 0.06),
+    BlockSpec {
+        translucency: 0.6,
+        ..block("leaves", Some("Leaves.png"), [60, 140, 40], 40, [0.9, 0.1, 0.0, 0.0], 1.0, 0.0)
+    },
+    block("hive", Some("Hive.png"), [200, 150, 60], 24, [0.9, 0.1, 0.0, 0.0], 1.0, 0.0),
+    BlockSpec {
+        emission: Color::new(255, 120, 30),
+        ..block("lava", None, [207, 92, 15], 50, [1.0, 0.05, 0.0, 0.0], 1.0, 0.0)
+    },
+    block("snow", None, [240, 245, 250], 8, [0.95, 0.05, 0.0, 0.0], 10.0, 0.06),
+    BlockSpec {
+        texture: Some("Water.png"),
+        refractive_index: 1.33,
+        dispersion: 0.01,
+        diffuse: Color::new(200, 225, 255),
+        deep_color: Color::new(18, 52, 96),
+        depth_scale: 2.5,
+        foam: 0.8,
+        // Ripples a block across look stretched; two to a block read as water.
+        uv_scale: (2.0, 2.0),
+        ..block("water", None, [63, 118, 228], 12, [0.4, 0.5, 0.1, 0.5], 80.0, 0.0)
+    },
+    BlockSpec {
+        refractive_index: 1.31,
+        dispersion: 0.01,
+        diffuse: Color::new(220, 235, 255),
+        ..block("ice", None, [160, 190, 240], 10, [0.5, 0.4, 0.1, 0.4], 100.0, 0.0)
+    },
+    BlockSpec {
+        refractive_index: 1.5,
+        dispersion: 0.02,
+        diffuse: Color::new(210, 240, 230),
+        ..block("glass", None, [210, 240, 230], 0, [0.05, 0.5, 0.0, 0.95], 125.0, 0.0)
+    },
+    BlockSpec {
+        diffuse: Color::new(200, 205, 210),
+        ..block("mirror", None, [200, 205, 210], 0, [0.1, 0.6, 0.9, 0.0], 300.0, 0.0)
+    },
+];
+
+// Names blocks commonly go by in schematics, as registry patterns.
+const ALIASES: [(&str, &str); 21] = [
+    ("grass_block", "grass"),
+    ("coarse_dirt", "dirt"),
+    ("cobblestone", "stone"),
+    ("stone_bricks", "stone"),
+    ("polished_andesite", "polished_stone"),
+    ("polished_diorite", "polished_stone"),
+    ("polished_granite", "polished_stone"),
+    ("smooth_stone", "polished_stone"),
+    ("red_sand", "sand"),
+    ("*_log", "wood"),
+    ("*_wood", "wood"),
+    ("*_planks", "planks"),
+    ("*_leaves", "leaves"),
+    ("beehive", "hive"),
+    ("bee_nest", "hive"),
+    ("snow_block", "snow"),
+    ("packed_ice", "ice"),
+    ("blue_ice", "ice"),
+    ("*_stained_glass", "glass"),
+    ("magma_block", "lava"),
+    ("sandstone", "sand"),
+];
+
+const fn block(
+    name: &'static str,
+    texture: Option<&'static str>,
+    base_color: [u8; 3],
+    variation: u8,
+    albedo: [f32; 4],
+    specular: f32,
+    bevel: f32,
+) -> BlockSpec {
+    BlockSpec {
+        name,
+        texture,
+        base_color,
+        variation,
+        albedo,
+        specular,
+        refractive_index: 0.0,
+        dispersion: 0.0,
+        diffuse: Color::black(),
+        bevel,
+        translucency: 0.0,
+        anisotropy: 0.0,
+        emission: Color::black(),
+        deep_color: Color::black(),
+        depth_scale: 0.0,
+        foam: 0.0,
+        uv_scale: (1.0, 1.0),
+        uv_offset: (0.0, 0.0),
+        uv_quarter_turns: 0,
+    }
+}
+
+// Built-in blocks with sensible shading, so scenes and generators can ask for "sand" or
+// "lava" without defining materials. Textures come from the given directory when the
+// file is there, and are generated otherwise.
+pub struct Palette {
+    blocks: Vec<(&'static str, Material)>,
+}
+
+impl Palette {
+    pub fn builtin(texture_directory: &str) -> Self {
+        let blocks = BLOCKS
+            .iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                let texture = (spec.variation > 0 || spec.texture.is_some())
+                    .then(|| load_or_generate(texture_directory, spec, index as u64 + 1));
+                let emission_map = texture
+                    .as_ref()
+                    .filter(|_| spec.emission.to_hex() != 0)
+                    .map(|texture| Arc::new(glow_mask(texture)));
+                let material = Material::new(spec.diffuse, spec.specular, spec.albedo, spec.refractive_index, texture)
+                    .with_emission(spec.emission, emission_map)
+                    .with_bevel(spec.bevel)
+                    .with_translucency(spec.translucency)
+                    .with_anisotropy(spec.anisotropy)
+                    .with_dispersion(spec.dispersion)
+                    .with_depth_tint(spec.deep_color, spec.depth_scale)
+                    .with_foam(spec.foam)
+                    .with_uv_transform(spec.uv_scale, spec.uv_offset, spec.uv_quarter_turns);
+                (spec.name, material)
+            })
+            .collect();
+        Palette { blocks }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Material> {
+        self.blocks.iter().find(|(block, _)| *block == name).map(|(_, material)| material)
+    }
+
+    // The block a material was taken from or loaded like: the same texture, or the same
+    // texture file, or for untextured blocks the same optics.
+    pub fn name_of(&self, material: &Material) -> Option<&'static str> {
+        let same = |block: &Material| match (&block.texture, &material.texture) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b) || (a.source().is_some() && a.source() == b.source()),
+            (None, None) => block.refractive_index == material.refractive_index && block.albedo == material.albedo,
+            _ => false,
+        };
+        self.blocks.iter().find(|(_, block)| same(block)).map(|(name, _)| *name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.blocks.iter().map(|(name, _)| *name)
+    }
+
+    // Every block under its own name and its usual schematic names.
+    pub fn registry(&self) -> MaterialRegistry {
+        let mut registry = MaterialRegistry::new();
+        for (name, material) in &self.blocks {
+            registry.register(name, material);
+        }
+        for (alias, name) in ALIASES {
+            if let Some(material) = self.get(name) {
+                registry.register(alias, material);
+            }
+        }
+        registry
+    }
+}
+
+fn load_or_generate(directory: &str, spec: &BlockSpec, seed: u64) -> Arc<Texture> {
+    if let Some(file) = spec.texture {
+        let path = Path::new(directory).join(file);
+        match Texture::new(&path.to_string_lossy()) {
+            Ok(texture) => return Arc::new(texture),
+            Err(err) => log::debug!("Generating a texture for {}: {}", spec.name, err),
+        }
+    }
+    Arc::new(noise_texture(spec.base_color, spec.variation, seed))
+}
+
+// Small texture of the base colour with per-pixel brightness noise.
+fn noise_texture(base_color: [u8; 3], variation: u8, seed: u64) -> Texture {
+    let mut rng = Rng::new(seed * 0x9E37_79B9);
+    let image = RgbImage::from_fn(FALLBACK_SIZE, FALLBACK_SIZE, |_, _| {
+        let offset = rng.range(-1.0, 1.0) * variation as f32;
+        Rgb(base_color.map(|channel| (channel as f32 + offset).clamp(0.0, 255.0) as u8))
+    });
+    Texture::from_image(DynamicImage::ImageRgb8(image))
+}
+
+// Emission map keeping only the texels brighter than the texture's average, so cracks and
+// veins glow while the rest of the block does not.
+fn glow_mask(texture: &Texture) -> Texture {
+    let luma = |[r, g, b]: [u8; 3]| (r as f32 + g as f32 + b as f32) / (3.0 * 255.0);
+    let average = luma(texture.average_color()).max(1e-3);
+    texture.map_pixels(|[r, g, b, a]| {
+        let glow = ((luma([r, g, b]) / average - 1.0) / GLOW_CONTRAST).clamp(0.0, 1.0);
+        let level = (glow * 255.0) as u8;
+        [level, level, level, a]
+    })
+}
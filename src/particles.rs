@@ -0,0 +1,225 @@
+use nalgebra_glm::Vec3;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::rng::Rng;
+
+const GRAVITY: f32 = -9.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitterKind {
+    Splash,
+    Smoke,
+    Rain,
+    Snow,
+    Meteor,
+}
+
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub age: f32,
+    pub lifetime: f32,
+    pub color: Color,
+    pub size: f32,
+    kind: EmitterKind,
+}
+
+impl Particle {
+    fn alpha(&self) -> f32 {
+        let t = (self.age / self.lifetime).clamp(0.0, 1.0);
+        match self.kind {
+            EmitterKind::Smoke => (t * 5.0).min(1.0) * (1.0 - t) * 0.7,
+            _ => 1.0 - t * t,
+        }
+    }
+
+    fn current_size(&self) -> f32 {
+        match self.kind {
+            EmitterKind::Smoke => self.size * (1.0 + 3.0 * self.age / self.lifetime),
+            _ => self.size,
+        }
+    }
+}
+
+pub struct Emitter {
+    pub position: Vec3,
+    pub kind: EmitterKind,
+    pub rate: f32,
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(position: Vec3, kind: EmitterKind, rate: f32) -> Self {
+        Emitter { position, kind, rate, accumulator: 0.0 }
+    }
+}
+
+pub struct ParticleSystem {
+    pub particles: Vec<Particle>,
+    pub emitters: Vec<Emitter>,
+    pub max_particles: usize,
+    rng: Rng,
+}
+
+impl ParticleSystem {
+    pub fn new(max_particles: usize, seed: u64) -> Self {
+        ParticleSystem {
+            particles: Vec::new(),
+            emitters: Vec::new(),
+            max_particles,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    pub fn set_rate(&mut self, kind: EmitterKind, rate: f32) {
+        for emitter in self.emitters.iter_mut().filter(|emitter| emitter.kind == kind) {
+            emitter.rate = rate;
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for i in 0..self.emitters.len() {
+            self.emitters[i].accumulator += self.emitters[i].rate * dt;
+            while self.emitters[i].accumulator >= 1.0 {
+                self.emitters[i].accumulator -= 1.0;
+                if self.particles.len() < self.max_particles {
+                    let particle = self.spawn(self.emitters[i].kind, self.emitters[i].position);
+                    self.particles.push(particle);
+                }
+            }
+        }
+
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+            match particle.kind {
+                EmitterKind::Splash => particle.velocity.y += GRAVITY * 0.5 * dt,
+                EmitterKind::Smoke | EmitterKind::Snow => {
+                    particle.velocity.x += self.rng.range(-0.2, 0.2) * dt;
+                    particle.velocity.z += self.rng.range(-0.2, 0.2) * dt;
+                }
+                EmitterKind::Rain | EmitterKind::Meteor => {}
+            }
+            particle.position += particle.velocity * dt;
+        }
+
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    fn spawn(&mut self, kind: EmitterKind, origin: Vec3) -> Particle {
+        let rng = &mut self.rng;
+        let (velocity, lifetime, color, size) = match kind {
+            EmitterKind::Splash => (
+                Vec3::new(rng.range(-0.4, 0.4), rng.range(1.5, 2.5), rng.range(-0.4, 0.4)),
+                rng.range(0.5, 0.9),
+                Color::new(200, 230, 255),
+                0.04,
+            ),
+            EmitterKind::Smoke => (
+                Vec3::new(rng.range(-0.05, 0.05), rng.range(0.4, 0.7), rng.range(-0.05, 0.05)),
+                rng.range(2.5, 3.5),
+                Color::new(150, 150, 150),
+                0.08,
+            ),
+            EmitterKind::Rain => (
+                Vec3::new(0.0, rng.range(-9.0, -7.0), 0.0),
+                1.2,
+                Color::new(170, 190, 220),
+                0.015,
+            ),
+            EmitterKind::Snow => (
+                Vec3::new(rng.range(-0.1, 0.1), rng.range(-0.9, -0.6), rng.range(-0.1, 0.1)),
+                rng.range(10.0, 12.0),
+                Color::new(250, 250, 255),
+                0.03,
+            ),
+            EmitterKind::Meteor => (
+                Vec3::new(rng.range(-16.0, -10.0), rng.range(-6.0, -3.0), rng.range(-4.0, 4.0)),
+                rng.range(0.4, 0.8),
+                Color::new(255, 246, 220),
+                0.06,
+            ),
+        };
+
+        let spread = match kind {
+            EmitterKind::Splash => 0.4,
+            EmitterKind::Rain | EmitterKind::Snow => 6.0,
+            EmitterKind::Meteor => 20.0,
+            _ => 0.1,
+        };
+        let position = origin + Vec3::new(rng.range(-spread, spread), 0.0, rng.range(-spread, spread));
+
+        Particle {
+            position,
+            velocity,
+            age: 0.0,
+            lifetime,
+            color,
+            size,
+            kind,
+        }
+    }
+
+    // Splats particles as screen-space discs; `occluded(point)` hides those behind geometry.
+    pub fn draw(&self, framebuffer: &mut Framebuffer, camera: &Camera, occluded: impl Fn(&Vec3) -> bool) {
+        for particle in &self.particles {
+            let splat = Splat {
+                position: particle.position,
+                size: particle.current_size(),
+                color: particle.color,
+                alpha: particle.alpha(),
+            };
+            splat.draw(framebuffer, camera, &occluded);
+        }
+    }
+}
+
+// A world-space disc drawn over the rendered frame; shared by particles and the bee swarm.
+pub struct Splat {
+    pub position: Vec3,
+    pub size: f32,
+    pub color: Color,
+    pub alpha: f32,
+}
+
+impl Splat {
+    pub fn draw(&self, framebuffer: &mut Framebuffer, camera: &Camera, occluded: &impl Fn(&Vec3) -> bool) {
+        let Some((sx, sy, depth)) = camera.project(&self.position) else {
+            return;
+        };
+        if sx < 0.0 || sy < 0.0 || sx >= framebuffer.width as f32 || sy >= framebuffer.height as f32 {
+            return;
+        }
+        if occluded(&self.position) {
+            return;
+        }
+
+        let radius = (self.size * camera.projection.pixels_per_unit(depth)).max(0.75);
+        let reach = radius.ceil() as i32;
+
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if distance > radius {
+                    continue;
+                }
+                let x = sx as i32 + dx;
+                let y = sy as i32 + dy;
+                if x < 0 || y < 0 {
+                    continue;
+                }
+                let (x, y) = (x as usize, y as usize);
+                if let Some(existing) = framebuffer.get_pixel(x, y) {
+                    let coverage = self.alpha * (radius - distance).clamp(0.0, 1.0);
+                    let blended = Color::from_hex(existing).lerp(self.color, coverage);
+                    framebuffer.set_current_color(blended.to_hex());
+                    framebuffer.point(x, y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use nalgebra_glm::Vec3;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::material::Material;
+
+const FRAME_WIDTH: f32 = 0.08;
+
+// A one-sided square opening. Rays entering its front leave through the front of `link`,
+// the index of the paired portal in the scene's object list.
+pub struct Portal {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub up: Vec3,
+    pub size: f32,
+    pub link: usize,
+    pub frame: Material,
+}
+
+impl Portal {
+    pub fn new(center: Vec3, normal: Vec3, up: Vec3, size: f32, link: usize, frame: Material) -> Self {
+        Portal {
+            center,
+            normal: normal.normalize(),
+            up: up.normalize(),
+            size,
+            link,
+            frame,
+        }
+    }
+
+    fn right(&self) -> Vec3 {
+        self.up.cross(&self.normal).normalize()
+    }
+
+    pub fn is_opening(&self, uv: (f32, f32)) -> bool {
+        let (u, v) = uv;
+        u > FRAME_WIDTH && u < 1.0 - FRAME_WIDTH && v > FRAME_WIDTH && v < 1.0 - FRAME_WIDTH
+    }
+
+    // Maps a ray hitting this portal to the equivalent ray leaving `target`.
+    pub fn teleport(&self, target: &Portal, point: &Vec3, direction: &Vec3) -> (Vec3, Vec3) {
+        let offset = point - self.center;
+        let (right, up, forward) = (self.right(), self.up, -self.normal);
+        let local_point = (offset.dot(&right), offset.dot(&up));
+        let local_direction = Vec3::new(direction.dot(&right), direction.dot(&up), direction.dot(&forward));
+
+        let scale = target.size / self.size;
+        let target_right = -target.right();
+        let origin = target.center
+            + target_right * (local_point.0 * scale)
+            + target.up * (local_point.1 * scale)
+            + target.normal * 1e-3;
+        let direction = target_right * local_direction.x + target.up * local_direction.y + target.normal * local_direction.z;
+
+        (origin, direction.normalize())
+    }
+}
+
+impl RayIntersect for Portal {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let denom = ray_direction.dot(&self.normal);
+        if denom >= -1e-6 {
+            return Intersect::empty();
+        }
+
+        let distance = (self.center - ray_origin).dot(&self.normal) / denom;
+        if distance < 0.0 {
+            return Intersect::empty();
+        }
+
+        let point = ray_origin + ray_direction * distance;
+        let offset = point - self.center;
+        let half_size = self.size / 2.0;
+        let x = offset.dot(&self.right());
+        let y = offset.dot(&self.up);
+        if x.abs() > half_size || y.abs() > half_size {
+            return Intersect::empty();
+        }
+
+        let uv = (x / self.size + 0.5, y / self.size + 0.5);
+        let mut intersect = Intersect::new(point, self.normal, distance, self.frame.clone(), Some(uv));
+        intersect.uv_size = self.size;
+        intersect
+    }
+}
@@ -0,0 +1,153 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::camera::Camera;
+use crate::error::{Error, Result};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+// Side of the squares an offline frame is traced in, so progress can be told between them.
+const TILE_SIZE: usize = 64;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Makes the first Ctrl+C ask the offline render to stop after the tile or pass it is on, so
+// what was traced can still be saved; a second one quits straight away.
+pub fn catch_interrupt() {
+    let handler = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        log::warn!("Interrupted, saving after the current tile; press Ctrl+C again to quit now");
+    });
+    if let Err(err) = handler {
+        log::warn!("Cannot catch Ctrl+C: {}", err);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// How far an offline render has got, counted in pixel samples traced, with an estimate of
+// the time left from the pace so far. Reports go to the log at most every REPORT_INTERVAL
+// and, when a file is given, into it as JSON, replaced on each report so a render left
+// running over SSH can be watched from another shell.
+pub struct Progress {
+    // What is being rendered, e.g. "frame 3/24", put in front of each report.
+    pub stage: String,
+    total: u64,
+    done: u64,
+    start: Instant,
+    last_report: Option<Instant>,
+    file: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    stage: &'a str,
+    done: u64,
+    total: u64,
+    percent: f32,
+    elapsed_seconds: f32,
+    eta_seconds: Option<f32>,
+    finished: bool,
+}
+
+impl Progress {
+    pub fn new(total: u64, file: Option<String>) -> Self {
+        Progress {
+            stage: String::new(),
+            total: total.max(1),
+            done: 0,
+            start: Instant::now(),
+            last_report: None,
+            file,
+        }
+    }
+
+    // Counts `work` more pixel samples done; `step` says where within the stage, like
+    // "tile 4/12".
+    pub fn advance(&mut self, work: u64, step: &str) -> Result<()> {
+        self.done = (self.done + work).min(self.total);
+        if self.last_report.is_some_and(|last| last.elapsed() < REPORT_INTERVAL) {
+            return Ok(());
+        }
+        self.last_report = Some(Instant::now());
+
+        let stage = [self.stage.as_str(), step].into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join(", ");
+        let elapsed = self.start.elapsed();
+        let left = self.eta().map_or_else(String::new, |eta| format!(", about {} left", format_duration(eta)));
+        log::info!("{}{}{:.1}% after {}{}", stage, if stage.is_empty() { "" } else { ": " }, self.fraction() * 100.0, format_duration(elapsed), left);
+        self.write(&stage, false)
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        if interrupted() {
+            log::warn!("Stopped at {:.1}% after {}", self.fraction() * 100.0, format_duration(self.start.elapsed()));
+            return self.write("interrupted", false);
+        }
+        self.done = self.total;
+        log::info!("Rendered in {}", format_duration(self.start.elapsed()));
+        self.write("done", true)
+    }
+
+    // Traces `region` of the frame tile by tile, reporting after each one. Tiles left when
+    // Ctrl+C is pressed are not traced.
+    pub fn render_tiles(&mut self, renderer: &Renderer, framebuffer: &mut Framebuffer, region: Rect, scene: &Scene, camera: &Camera, samples: usize) -> Result<()> {
+        let columns = region.width.div_ceil(TILE_SIZE);
+        let tiles = columns * region.height.div_ceil(TILE_SIZE);
+        for tile in 0..tiles {
+            if interrupted() {
+                break;
+            }
+            let (x, y) = (region.x + tile % columns * TILE_SIZE, region.y + tile / columns * TILE_SIZE);
+            let bounds = Rect::new(x, y, TILE_SIZE.min(region.x + region.width - x), TILE_SIZE.min(region.y + region.height - y));
+            renderer.render_region(framebuffer, bounds, scene, camera, samples);
+            self.advance((bounds.width * bounds.height * samples) as u64, &format!("tile {}/{}", tile + 1, tiles))?;
+        }
+        Ok(())
+    }
+
+    fn fraction(&self) -> f32 {
+        self.done as f32 / self.total as f32
+    }
+
+    // The time left if the rest goes at the pace so far.
+    fn eta(&self) -> Option<Duration> {
+        (self.done > 0).then(|| self.start.elapsed().mul_f64((self.total - self.done) as f64 / self.done as f64))
+    }
+
+    // Written next to the file and renamed over it, so a reader never sees half a report.
+    fn write(&self, stage: &str, finished: bool) -> Result<()> {
+        let Some(filename) = &self.file else {
+            return Ok(());
+        };
+        let report = Report {
+            stage,
+            done: self.done,
+            total: self.total,
+            percent: self.fraction() * 100.0,
+            elapsed_seconds: self.start.elapsed().as_secs_f32(),
+            eta_seconds: self.eta().map(|eta| eta.as_secs_f32()),
+            finished,
+        };
+        let temporary = format!("{}.tmp", filename);
+        let write_error = |source| Error::Export { path: filename.clone(), source };
+        let json = serde_json::to_string_pretty(&report).map_err(|err| write_error(err.into()))?;
+        std::fs::write(&temporary, json).map_err(write_error)?;
+        std::fs::rename(&temporary, filename).map_err(write_error)
+    }
+}
+
+// Like "1h02m", "3m05s" or "12s".
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{:02}s", m, s),
+        (h, m, _) => format!("{}h{:02}m", h, m),
+    }
+}
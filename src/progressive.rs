@@ -0,0 +1,151 @@
+use nalgebra_glm::Vec3;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use crate::dither;
+use crate::error::{Error, Result};
+use crate::framebuffer::Framebuffer;
+use crate::worker_pool::WorkerPool;
+
+const MAGIC: &[u8; 4] = b"RFCK";
+const VERSION: u32 = 1;
+
+// Per-pixel colour sums for an offline render that adds one jittered sample per pixel per
+// pass. Sample positions follow a fixed sequence, so a resumed render continues exactly
+// where the interrupted one stopped.
+pub struct ProgressiveRender {
+    pub width: usize,
+    pub height: usize,
+    pub passes: usize,
+    scene: String,
+    sums: Vec<Vec3>,
+}
+
+impl ProgressiveRender {
+    // `scene` identifies what is being rendered; a checkpoint is only resumed if it matches.
+    pub fn new(width: usize, height: usize, scene: &str) -> Self {
+        ProgressiveRender {
+            width,
+            height,
+            passes: 0,
+            scene: scene.to_string(),
+            sums: vec![Vec3::zeros(); width * height],
+        }
+    }
+
+    // Picks up an earlier checkpoint for the same scene and size, or starts from scratch.
+    pub fn resume(filename: &str, width: usize, height: usize, scene: &str) -> Result<Self> {
+        let bytes = match std::fs::read(filename) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(ProgressiveRender::new(width, height, scene));
+            }
+            Err(source) => return Err(Error::ImportIo { path: filename.to_string(), source }),
+        };
+
+        let checkpoint = ProgressiveRender::decode(&bytes)
+            .map_err(|reason| Error::Import { path: filename.to_string(), reason })?;
+        if checkpoint.width != width || checkpoint.height != height || checkpoint.scene != scene {
+            log::warn!("Checkpoint {} is for a different render, starting over", filename);
+            return Ok(ProgressiveRender::new(width, height, scene));
+        }
+        log::info!("Resuming {} after {} passes", filename, checkpoint.passes);
+        Ok(checkpoint)
+    }
+
+    // Sub-pixel offset of a pass on the R2 sequence; pass 0 samples pixel centres.
+    pub fn jitter(pass: usize) -> (f32, f32) {
+        const A1: f64 = 0.754_877_666_246_692_7;
+        const A2: f64 = 0.569_840_290_998_053_3;
+        let n = pass as f64;
+        (((0.5 + A1 * n).fract() - 0.5) as f32, ((0.5 + A2 * n).fract() - 0.5) as f32)
+    }
+
+    // Adds one sample to every pixel, a row per job on `pool`; `shade` gets the pixel and
+    // this pass's sub-pixel jitter.
+    pub fn add_pass(&mut self, pool: &WorkerPool, shade: impl Fn(usize, usize, (f32, f32)) -> Vec3 + Sync) {
+        let jitter = ProgressiveRender::jitter(self.passes);
+        let width = self.width;
+        let rows: Vec<Mutex<&mut [Vec3]>> = self.sums.chunks_mut(width.max(1)).map(Mutex::new).collect();
+        pool.run(rows.len(), &|y| {
+            let mut sums = rows[y].lock().unwrap();
+            for (x, sum) in sums.iter_mut().enumerate() {
+                *sum += shade(x, y, jitter);
+            }
+        });
+        self.passes += 1;
+    }
+
+    pub fn resolve(&self, framebuffer: &mut Framebuffer, dither: bool) {
+        let passes = self.passes.max(1) as f32;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = dither::to_color(self.sums[y * self.width + x] / passes, x, y, dither);
+                framebuffer.set_current_color(color.to_hex());
+                framebuffer.point(x, y);
+            }
+        }
+    }
+
+    // Written next to the target and renamed over it, so an interrupted write never
+    // leaves a broken checkpoint behind.
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let temporary = format!("{}.tmp", filename);
+        let write_error = |source| Error::Export { path: filename.to_string(), source };
+
+        let file = std::fs::File::create(&temporary).map_err(write_error)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&self.encode()).map_err(write_error)?;
+        writer.flush().map_err(write_error)?;
+        drop(writer);
+        std::fs::rename(&temporary, filename).map_err(write_error)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(24 + self.scene.len() + self.sums.len() * 12);
+        bytes.extend_from_slice(MAGIC);
+        for value in [VERSION, self.width as u32, self.height as u32, self.passes as u32, self.scene.len() as u32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(self.scene.as_bytes());
+        for sum in &self.sums {
+            for channel in [sum.x, sum.y, sum.z] {
+                bytes.extend_from_slice(&channel.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> std::result::Result<Self, String> {
+        let truncated = || String::from("checkpoint is truncated");
+        if bytes.get(..4) != Some(MAGIC.as_slice()) {
+            return Err(String::from("not a render checkpoint"));
+        }
+        let word = |index: usize| -> std::result::Result<usize, String> {
+            let start = 4 + index * 4;
+            let raw = bytes.get(start..start + 4).ok_or_else(truncated)?;
+            Ok(u32::from_le_bytes(raw.try_into().unwrap()) as usize)
+        };
+        if word(0)? != VERSION as usize {
+            return Err(format!("unsupported checkpoint version {}", word(0)?));
+        }
+        let (width, height, passes, scene_length) = (word(1)?, word(2)?, word(3)?, word(4)?);
+
+        let scene_start = 24;
+        let scene = bytes.get(scene_start..scene_start + scene_length).ok_or_else(truncated)?;
+        let scene = String::from_utf8(scene.to_vec()).map_err(|_| String::from("scene description is not UTF-8"))?;
+
+        let data = &bytes[scene_start + scene_length..];
+        if data.len() != width * height * 12 {
+            return Err(truncated());
+        }
+        let sums = data
+            .chunks_exact(12)
+            .map(|pixel| {
+                let channel = |i: usize| f32::from_le_bytes(pixel[i * 4..i * 4 + 4].try_into().unwrap());
+                Vec3::new(channel(0), channel(1), channel(2))
+            })
+            .collect();
+
+        Ok(ProgressiveRender { width, height, passes, scene, sums })
+    }
+}
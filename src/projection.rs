@@ -0,0 +1,195 @@
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use crate::ray_cone::RayCone;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Lens {
+    #[default]
+    Perspective,
+    // `height` is the world-space height the view covers.
+    Orthographic { height: f32 },
+    // Equidistant fisheye; `angle` (degrees) spans the height of the image.
+    Fisheye { angle: f32 },
+    // Panini projection for wide shots; `angle` (degrees) spans the width of the image and
+    // `distance` trades straight verticals (1.0, the classic form) against rectilinear (0.0).
+    Panini { angle: f32, distance: f32 },
+}
+
+// A ray to trace. `far` is how far along it geometry is still visible; only primary rays
+// are clipped.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub far: f32,
+    pub cone: RayCone,
+    // The one colour a ray split off by dispersion carries (0 red, 1 green, 2 blue); None
+    // for white light.
+    pub channel: Option<usize>,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3, cone: RayCone) -> Self {
+        Ray { origin, direction, far: f32::INFINITY, cone, channel: None }
+    }
+}
+
+// How pixels of a `width` x `height` viewport map to rays in camera space, where x points
+// right, y up and the camera looks down -z.
+#[derive(Debug, Clone, Copy)]
+pub struct Projection {
+    pub lens: Lens,
+    // Vertical field of view in radians.
+    pub fov: f32,
+    pub near: f32,
+    pub far: Option<f32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            lens: Lens::Perspective,
+            fov: PI / 3.0,
+            near: 0.0,
+            far: None,
+            width: 1,
+            height: 1,
+        }
+    }
+}
+
+impl Projection {
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    // Ray through pixel position (x, y) offset by `jitter` pixels, in camera space.
+    pub fn local_ray(&self, x: f32, y: f32, jitter: (f32, f32)) -> Ray {
+        let screen_x = (2.0 * (x + jitter.0)) / self.width as f32 - 1.0;
+        let screen_y = 1.0 - (2.0 * (y + jitter.1)) / self.height as f32;
+
+        match self.lens {
+            Lens::Perspective => {
+                let scale = (self.fov * 0.5).tan();
+                let on_plane = Vec3::new(screen_x * self.aspect_ratio() * scale, screen_y * scale, -1.0);
+                let stretch = on_plane.magnitude();
+                let spread = 2.0 * scale / self.height as f32;
+                Ray {
+                    origin: on_plane * self.near,
+                    direction: on_plane / stretch,
+                    far: self.far.map_or(f32::INFINITY, |far| (far - self.near) * stretch),
+                    cone: RayCone::new(spread * self.near * stretch, spread),
+                    channel: None,
+                }
+            }
+            Lens::Orthographic { height } => {
+                let half_height = height * 0.5;
+                Ray {
+                    origin: Vec3::new(screen_x * self.aspect_ratio() * half_height, screen_y * half_height, -self.near),
+                    direction: Vec3::new(0.0, 0.0, -1.0),
+                    far: self.far.map_or(f32::INFINITY, |far| far - self.near),
+                    cone: RayCone::new(height / self.height as f32, 0.0),
+                    channel: None,
+                }
+            }
+            Lens::Fisheye { angle } => {
+                let half_angle = angle.to_radians() * 0.5;
+                let (x, y) = (screen_x * self.aspect_ratio(), screen_y);
+                let theta = (x * x + y * y).sqrt() * half_angle;
+                let phi = y.atan2(x);
+                let direction = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), -theta.cos());
+                self.radial_ray(direction, 2.0 * half_angle / self.height as f32)
+            }
+            Lens::Panini { angle, distance } => {
+                let extent = panini_extent(angle, distance);
+                let x = screen_x * extent;
+                let y = screen_y * extent / self.aspect_ratio();
+
+                // Inverse mapping from Sharpless et al., "Pannini: A New Projection for
+                // Rendering Wide Angle Perspective Images".
+                let k = x * x / ((distance + 1.0) * (distance + 1.0));
+                let discriminant = k * k * distance * distance - (k + 1.0) * (k * distance * distance - 1.0);
+                let cos_phi = (-k * distance + discriminant.max(0.0).sqrt()) / (k + 1.0);
+                let scale = (distance + 1.0) / (distance + cos_phi);
+                let phi = x.atan2(scale * cos_phi);
+                let direction = Vec3::new(phi.sin(), y / scale, -phi.cos()).normalize();
+                self.radial_ray(direction, 2.0 * extent / self.width as f32)
+            }
+        }
+    }
+
+    // Ray from the eye for lenses where the near and far planes become spheres.
+    fn radial_ray(&self, direction: Vec3, spread: f32) -> Ray {
+        Ray {
+            origin: direction * self.near,
+            direction,
+            far: self.far.map_or(f32::INFINITY, |far| far - self.near),
+            cone: RayCone::new(spread * self.near, spread),
+            channel: None,
+        }
+    }
+
+    // Pixel position and view depth of a camera-space point, or None if it cannot be seen.
+    pub fn project_local(&self, point: &Vec3) -> Option<(f32, f32, f32)> {
+        let depth = match self.lens {
+            Lens::Perspective | Lens::Orthographic { .. } => -point.z,
+            Lens::Fisheye { .. } | Lens::Panini { .. } => point.magnitude(),
+        };
+        if depth <= self.near.max(1e-3) || self.far.is_some_and(|far| depth > far) {
+            return None;
+        }
+
+        let (screen_x, screen_y) = match self.lens {
+            Lens::Perspective => {
+                let scale = (self.fov * 0.5).tan();
+                (point.x / depth / (self.aspect_ratio() * scale), point.y / depth / scale)
+            }
+            Lens::Orthographic { height } => {
+                let half_height = height * 0.5;
+                (point.x / (self.aspect_ratio() * half_height), point.y / half_height)
+            }
+            Lens::Fisheye { angle } => {
+                let theta = (-point.z / depth).clamp(-1.0, 1.0).acos();
+                let phi = point.y.atan2(point.x);
+                let radius = theta / (angle.to_radians() * 0.5);
+                (radius * phi.cos() / self.aspect_ratio(), radius * phi.sin())
+            }
+            Lens::Panini { angle, distance } => {
+                let horizontal = point.x.hypot(point.z);
+                let phi = point.x.atan2(-point.z);
+                if horizontal < 1e-6 || distance + phi.cos() <= 1e-3 {
+                    return None;
+                }
+                let scale = (distance + 1.0) / (distance + phi.cos());
+                let extent = panini_extent(angle, distance);
+                (scale * phi.sin() / extent, scale * point.y / horizontal * self.aspect_ratio() / extent)
+            }
+        };
+
+        Some((
+            (screen_x + 1.0) * 0.5 * self.width as f32,
+            (1.0 - screen_y) * 0.5 * self.height as f32,
+            depth,
+        ))
+    }
+
+    // Screen size in pixels of one world unit seen at `depth`.
+    pub fn pixels_per_unit(&self, depth: f32) -> f32 {
+        match self.lens {
+            Lens::Perspective => self.height as f32 / (2.0 * (self.fov * 0.5).tan() * depth),
+            Lens::Orthographic { height } => self.height as f32 / height,
+            Lens::Fisheye { angle } => self.height as f32 / (angle.to_radians() * depth),
+            Lens::Panini { angle, distance } => self.width as f32 / (2.0 * panini_extent(angle, distance) * depth),
+        }
+    }
+}
+
+// Panini x coordinate of the image's right edge, half of `angle` degrees off-axis.
+fn panini_extent(angle: f32, distance: f32) -> f32 {
+    let phi = (angle.to_radians() * 0.5).min(PI * 0.5);
+    phi.sin() * (distance + 1.0) / (distance + phi.cos())
+}
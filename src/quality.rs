@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::texture::Reduction;
+
+// A named bundle of the knobs that trade speed for quality. Fields left out of a preset in
+// the config take the balanced preset's values.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QualityPreset {
+    pub name: String,
+    // Rays per pixel for regions refined on screen, and for offline renders unless
+    // --samples says otherwise.
+    pub samples: usize,
+    pub shadows: bool,
+    // Reflect from cubemap probes while the camera moves instead of tracing.
+    pub reflection_probes: bool,
+    pub sky_samples: usize,
+    pub corner_occlusion: bool,
+    pub global_illumination: bool,
+    pub texture_filtering: bool,
+    // Split light refracting through glass and water into colours (see `Material::dispersion`).
+    pub dispersion: bool,
+    // Longest side block textures are loaded at and colours in their palettes, each 0 for
+    // the files' own; anything else reloads them cut down, for quick previews.
+    pub texture_size: u32,
+    pub texture_colors: usize,
+}
+
+impl QualityPreset {
+    fn fast() -> Self {
+        QualityPreset {
+            name: "fast".to_string(),
+            samples: 1,
+            shadows: false,
+            reflection_probes: true,
+            sky_samples: 0,
+            corner_occlusion: true,
+            global_illumination: false,
+            texture_filtering: false,
+            dispersion: false,
+            texture_size: 128,
+            texture_colors: 0,
+        }
+    }
+
+    fn balanced() -> Self {
+        QualityPreset { name: "balanced".to_string(), ..QualityPreset::default() }
+    }
+
+    fn final_render() -> Self {
+        QualityPreset {
+            name: "final".to_string(),
+            samples: 16,
+            shadows: true,
+            reflection_probes: false,
+            sky_samples: 64,
+            corner_occlusion: true,
+            global_illumination: true,
+            texture_filtering: true,
+            dispersion: true,
+            texture_size: 0,
+            texture_colors: 0,
+        }
+    }
+
+    pub fn texture_reduction(&self) -> Reduction {
+        Reduction { max_side: self.texture_size, colors: self.texture_colors }
+    }
+
+    // Puts the preset's render settings, shadows and global illumination in place. Samples,
+    // reflection probes and textures are up to the caller, as only it knows how it renders
+    // and which textures it loaded.
+    pub fn apply(&self, settings: &mut RenderSettings, scene: &mut Scene) {
+        if settings.sky_samples != self.sky_samples {
+            scene.sky_occlusion.clear();
+        }
+        settings.sky_samples = self.sky_samples;
+        settings.corner_occlusion = self.corner_occlusion;
+        settings.texture_filtering = self.texture_filtering;
+        settings.dispersion = self.dispersion;
+        scene.sun.switches.casts_shadows = self.shadows;
+        scene.irradiance.enabled = self.global_illumination;
+    }
+}
+
+impl Default for QualityPreset {
+    fn default() -> Self {
+        QualityPreset {
+            name: String::new(),
+            samples: 4,
+            shadows: true,
+            reflection_probes: true,
+            sky_samples: 16,
+            corner_occlusion: true,
+            global_illumination: false,
+            texture_filtering: true,
+            dispersion: false,
+            texture_size: 0,
+            texture_colors: 0,
+        }
+    }
+}
+
+// The built-in presets followed by `custom`; a custom preset named like a built-in one
+// replaces it.
+pub fn presets(custom: &[QualityPreset]) -> Vec<QualityPreset> {
+    let mut presets = vec![QualityPreset::fast(), QualityPreset::balanced(), QualityPreset::final_render()];
+    for preset in custom {
+        match presets.iter_mut().find(|builtin| builtin.name.eq_ignore_ascii_case(&preset.name)) {
+            Some(builtin) => *builtin = preset.clone(),
+            None => presets.push(preset.clone()),
+        }
+    }
+    presets
+}
+
+// Index of the preset called `name`, ignoring case.
+pub fn find(presets: &[QualityPreset], name: &str) -> Option<usize> {
+    presets.iter().position(|preset| preset.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,26 @@
+// Ray-cone approximation of ray differentials: tracks how wide a pixel's footprint grows
+// along a ray so texture lookups can pick a matching mip level.
+#[derive(Debug, Clone, Copy)]
+pub struct RayCone {
+    pub width: f32,
+    pub spread: f32,
+}
+
+impl RayCone {
+    pub fn new(width: f32, spread: f32) -> Self {
+        RayCone { width, spread }
+    }
+
+    pub fn pinpoint() -> Self {
+        RayCone::new(0.0, 0.0)
+    }
+
+    pub fn width_at(&self, distance: f32) -> f32 {
+        self.width + self.spread * distance
+    }
+
+    // Cone continuing from a hit at `distance`, e.g. after a mirror bounce or a refraction.
+    pub fn propagate(&self, distance: f32) -> RayCone {
+        RayCone::new(self.width_at(distance), self.spread)
+    }
+}
@@ -9,6 +9,7 @@ pub struct Intersect {
     pub is_intersecting: bool,
     pub material: Material,
     pub uv: Option<(f32, f32)>,
+    pub uv_size: f32,
 }
 
 impl Intersect {
@@ -26,6 +27,7 @@ impl Intersect {
             is_intersecting: true,
             material,
             uv,
+            uv_size: 1.0,
         }
     }
 
@@ -37,6 +39,7 @@ impl Intersect {
             is_intersecting: false,
             material: Material::black(),
             uv: None,
+            uv_size: 1.0,
         }
     }
 }
@@ -0,0 +1,78 @@
+use nalgebra_glm::Vec3;
+use std::sync::Mutex;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::projection::Ray;
+
+// Reflections in a flat horizontal water surface, seen by a camera mirrored below it. Each
+// mirrored pixel is traced the first time a water hit needs it and reused for the rest of
+// the frame, so neighbouring water pixels share one reflection ray instead of tracing their
+// own. Reflections lose detail at the pass's lower resolution.
+pub struct ReflectionPlane {
+    pub height: f32,
+    // Mirrored pixels per frame pixel along each axis.
+    pub resolution: f32,
+    mirror: Camera,
+    pixels: Mutex<Vec<Option<Color>>>,
+}
+
+impl ReflectionPlane {
+    pub fn new(height: f32, resolution: f32) -> Self {
+        ReflectionPlane {
+            height,
+            resolution: resolution.clamp(0.1, 1.0),
+            mirror: Camera::new(Vec3::zeros(), -Vec3::z(), Vec3::y()),
+            pixels: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Mirrors `camera` for the frame about to be rendered and forgets the previous frame.
+    pub fn begin_frame(&mut self, camera: &Camera) {
+        let height = self.height;
+        let mirror = |v: &Vec3| Vec3::new(v.x, 2.0 * height - v.y, v.z);
+        let mut view = Camera::new(mirror(&camera.eye), mirror(&camera.center), Vec3::new(camera.up.x, -camera.up.y, camera.up.z));
+        view.projection = camera.projection;
+        let scaled = |size: usize| ((size as f32 * self.resolution).round() as usize).max(1);
+        view.set_viewport(scaled(camera.projection.width), scaled(camera.projection.height));
+
+        let pixels = self.pixels.get_mut().unwrap();
+        pixels.clear();
+        pixels.resize(view.projection.width * view.projection.height, None);
+        self.mirror = view;
+    }
+
+    // Whether a hit lies on the plane, facing up.
+    pub fn covers(&self, point: &Vec3, normal: &Vec3) -> bool {
+        normal.y > 0.99 && (point.y - self.height).abs() < 1e-3
+    }
+
+    // The reflection at `point` on the plane, or None where the mirrored view does not reach.
+    // `trace` follows a ray leaving the plane.
+    pub fn sample(&self, point: &Vec3, trace: impl FnOnce(&Ray) -> Color) -> Option<Color> {
+        let (x, y, _) = self.mirror.project(point)?;
+        let (width, height) = (self.mirror.projection.width, self.mirror.projection.height);
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            return None;
+        }
+        let index = y as usize * width + x as usize;
+        if let Some(color) = self.pixels.lock().unwrap()[index] {
+            return Some(color);
+        }
+
+        let ray = self.mirror.generate_ray(x.floor(), y.floor(), (0.0, 0.0));
+        if ray.direction.y <= 1e-4 {
+            return None;
+        }
+        // Start just above the water so the mirrored eye does not see what lies below it.
+        let distance = (self.height - ray.origin.y) / ray.direction.y + 1e-3;
+        let from_plane = Ray {
+            origin: ray.origin + ray.direction * distance.max(0.0),
+            far: ray.far - distance,
+            cone: ray.cone.propagate(distance.max(0.0)),
+            ..ray
+        };
+        let color = trace(&from_plane);
+        self.pixels.lock().unwrap()[index] = Some(color);
+        Some(color)
+    }
+}
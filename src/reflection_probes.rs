@@ -0,0 +1,145 @@
+use nalgebra_glm::Vec3;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use crate::color::Color;
+use crate::scene::Object;
+
+const REFRESH_ANGLE: f32 = 0.01;
+// How far above the highest block of its column a probe floats.
+const PROBE_HEIGHT: f32 = 1.5;
+
+// Low resolution cubemaps of the surroundings, seen from points spread over the ground, that
+// stand in for reflection rays while the camera moves. Each probe is traced the first time a
+// reflection needs it and kept until the sun moves enough or blocks change, so reflections
+// lose detail and parallax in exchange for a handful of rays per frame.
+pub struct ReflectionProbes {
+    pub enabled: bool,
+    // Blocks between neighbouring probes, and texels along each cubemap face's side.
+    pub spacing: f32,
+    pub resolution: usize,
+    points: Vec<Vec3>,
+    cubemaps: RwLock<Vec<Option<Vec<Color>>>>,
+    sun_direction: Vec3,
+}
+
+impl ReflectionProbes {
+    pub fn new(spacing: f32, resolution: usize) -> Self {
+        ReflectionProbes {
+            enabled: false,
+            spacing: spacing.max(1.0),
+            resolution: resolution.max(1),
+            points: Vec::new(),
+            cubemaps: RwLock::new(Vec::new()),
+            sun_direction: Vec3::zeros(),
+        }
+    }
+
+    // Spreads the probes over the blocks of `objects`, one per `spacing` square above the
+    // column nearest its middle, and forgets what they saw.
+    pub fn place(&mut self, objects: &[Object]) {
+        let mut tops: BTreeMap<(i32, i32), f32> = BTreeMap::new();
+        for object in objects {
+            if let Object::Cube(cube, false) = object {
+                if let Some([x, _, z]) = cube.grid_cell() {
+                    let top = tops.entry((x, z)).or_insert(f32::MIN);
+                    *top = top.max(cube.center.y + cube.size / 2.0);
+                }
+            }
+        }
+
+        let spacing = self.spacing;
+        let mut nearest: BTreeMap<(i32, i32), (f32, Vec3)> = BTreeMap::new();
+        for (&(x, z), &top) in &tops {
+            let square = ((x as f32 / spacing).floor() as i32, (z as f32 / spacing).floor() as i32);
+            let middle = ((square.0 as f32 + 0.5) * spacing, (square.1 as f32 + 0.5) * spacing);
+            let distance = (x as f32 - middle.0).powi(2) + (z as f32 - middle.1).powi(2);
+            let point = Vec3::new(x as f32, top + PROBE_HEIGHT, z as f32);
+            let entry = nearest.entry(square).or_insert((f32::INFINITY, point));
+            if distance < entry.0 {
+                *entry = (distance, point);
+            }
+        }
+
+        self.points = nearest.into_values().map(|(_, point)| point).collect();
+        *self.cubemaps.get_mut().unwrap() = vec![None; self.points.len()];
+        log::debug!("Placed {} reflection probes", self.points.len());
+    }
+
+    pub fn update(&mut self, sun_direction: &Vec3) {
+        if sun_direction.dot(&self.sun_direction) < REFRESH_ANGLE.cos() {
+            self.clear();
+            self.sun_direction = *sun_direction;
+        }
+    }
+
+    pub fn clear(&self) {
+        self.cubemaps.write().unwrap().iter_mut().for_each(|cubemap| *cubemap = None);
+    }
+
+    // What the probe nearest `point` sees along `direction`, or None without probes.
+    // `trace` follows a ray from a probe when its cubemap has not been traced yet.
+    pub fn lookup(&self, point: &Vec3, direction: &Vec3, trace: impl Fn(&Vec3, &Vec3) -> Color) -> Option<Color> {
+        let (index, origin) = self
+            .points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - point).norm_squared().total_cmp(&(*b - point).norm_squared()))?;
+        if let Some(cubemap) = &self.cubemaps.read().unwrap()[index] {
+            return Some(self.sample(cubemap, direction));
+        }
+
+        let size = self.resolution;
+        let cubemap: Vec<Color> = (0..6 * size * size)
+            .map(|texel| {
+                let (face, y, x) = (texel / (size * size), texel / size % size, texel % size);
+                let (normal, u, v) = face_axes(face);
+                let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                trace(origin, &(normal + u * s + v * t).normalize())
+            })
+            .collect();
+        let color = self.sample(&cubemap, direction);
+        self.cubemaps.write().unwrap()[index] = Some(cubemap);
+        Some(color)
+    }
+
+    // Bilinear lookup within the face `direction` points at.
+    fn sample(&self, cubemap: &[Color], direction: &Vec3) -> Color {
+        let face = face_of(direction);
+        let (normal, u, v) = face_axes(face);
+        let size = self.resolution;
+        let along = direction.dot(&normal);
+        let texel = |s: f32| ((s / along * 0.5 + 0.5) * size as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+        let (x, y) = (texel(direction.dot(&u)), texel(direction.dot(&v)));
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(size - 1), (y0 + 1).min(size - 1));
+        let at = |x: usize, y: usize| cubemap[(face * size + y) * size + x];
+        let top = at(x0, y0).lerp(at(x1, y0), x - x0 as f32);
+        let bottom = at(x0, y1).lerp(at(x1, y1), x - x0 as f32);
+        top.lerp(bottom, y - y0 as f32)
+    }
+}
+
+// Faces are +x, -x, +y, -y, +z and -z, each with the two axes its texels run along.
+fn face_axes(face: usize) -> (Vec3, Vec3, Vec3) {
+    let axis = |i: usize| {
+        let mut unit = Vec3::zeros();
+        unit[i % 3] = 1.0;
+        unit
+    };
+    let sign = if face.is_multiple_of(2) { 1.0 } else { -1.0 };
+    (axis(face / 2) * sign, axis(face / 2 + 1), axis(face / 2 + 2))
+}
+
+fn face_of(direction: &Vec3) -> usize {
+    let abs = direction.abs();
+    let axis = if abs.x >= abs.y && abs.x >= abs.z {
+        0
+    } else if abs.y >= abs.z {
+        1
+    } else {
+        2
+    };
+    axis * 2 + (direction[axis] < 0.0) as usize
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use crate::material::Material;
+
+// Maps block names to scene materials. Names may be exact ("grass_block") or a suffix
+// pattern ("*_leaves"); namespaces and block-state properties are ignored on lookup.
+// Registering a name again replaces its material.
+#[derive(Default)]
+pub struct MaterialRegistry {
+    exact: HashMap<String, Material>,
+    suffixes: Vec<(String, Material)>,
+}
+
+impl MaterialRegistry {
+    pub fn new() -> Self {
+        MaterialRegistry::default()
+    }
+
+    pub fn register(&mut self, name: &str, material: &Material) {
+        match name.strip_prefix('*') {
+            Some(suffix) => match self.suffixes.iter_mut().find(|(existing, _)| existing == suffix) {
+                Some((_, existing)) => *existing = material.clone(),
+                None => self.suffixes.push((suffix.to_string(), material.clone())),
+            },
+            None => {
+                self.exact.insert(name.to_string(), material.clone());
+            }
+        }
+    }
+
+    pub fn get(&self, block: &str) -> Option<&Material> {
+        let name = base_name(block);
+        self.exact.get(name).or_else(|| {
+            self.suffixes
+                .iter()
+                .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+                .map(|(_, material)| material)
+        })
+    }
+}
+
+// "minecraft:oak_log[axis=y]" -> "oak_log"
+pub fn base_name(block: &str) -> &str {
+    let block = block.split('[').next().unwrap_or(block);
+    block.rsplit(':').next().unwrap_or(block)
+}
+
+#[cfg(feature = "schematic")]
+pub fn is_air(block: &str) -> bool {
+    matches!(base_name(block), "air" | "cave_air" | "void_air" | "structure_void")
+}
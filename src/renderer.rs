@@ -0,0 +1,899 @@
+use nalgebra_glm::Vec3;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use crate::camera::{Camera, CameraPose};
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::daycycle::moon_illumination;
+use crate::dither;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::irradiance::hemisphere_directions;
+use crate::light::{DirectionalLight, Light};
+use crate::light_groups::LightGroup;
+use crate::progressive::ProgressiveRender;
+use crate::projection::Ray;
+use crate::ray_cone::RayCone;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::reflection_plane::ReflectionPlane;
+use crate::rng::Rng;
+use crate::sky::moon_over;
+use crate::settings::RenderSettings;
+use crate::shading_math::{fresnel, offset_origin, reflect, refract, schlick};
+use crate::snow::SNOW_COLOR;
+use crate::sun_visibility::SunOcclusion;
+use crate::texture::Texture;
+use crate::watchdog::{self, Watchdog};
+use crate::worker_pool::WorkerPool;
+use crate::scene::{Lighting, Object, Scene, closest_intersect, first_occluder};
+
+// How far past edge-on the light behind a translucent face still reaches it.
+const TRANSLUCENCY_WRAP: f32 = 0.5;
+// Width of the shoreline foam band, as a fraction of a block.
+const FOAM_WIDTH: f32 = 0.2;
+const FOAM_COLOR: Color = Color::new(232, 242, 248);
+// Ambient light by day, and at night under a new and a full moon.
+const DAY_AMBIENT: f32 = 0.2;
+const NEW_MOON_AMBIENT: f32 = 0.12;
+const FULL_MOON_AMBIENT: f32 = 0.3;
+// Lamps dimmer than this where they reach a surface are left out, sparing their shadow rays.
+const LAMP_CUTOFF: f32 = 0.01;
+
+// Traces frames with the settings they were asked for. Rows are handed to a pool of threads
+// kept for the renderer's lifetime, so threads that draw cheap rows (sky) pick up more of
+// them. Buffers that outlive a single frame live here too, so they are not reallocated.
+// Sampling needs no random state: soft shadows and foam draw their noise from the hit point,
+// which keeps images stable from frame to frame and across threads.
+pub struct Renderer {
+    pub settings: RenderSettings,
+    // Stands in for reflection rays off flat water seen directly by the camera.
+    pub reflection_plane: Option<ReflectionPlane>,
+    pool: WorkerPool,
+    // Running sums of the frames averaged for motion blur.
+    accumulation: Mutex<Vec<Vec3>>,
+    gbuffer: Mutex<GBuffer>,
+    stats: RayCounters,
+    // Flags primary rays shaded into NaN or infinity when `settings.nan_watchdog` is on.
+    pub watchdog: Watchdog,
+}
+
+// What the primary rays of the latest render saw, per pixel. So far that is only how deep the
+// nearest hit lies, which lets sprites drawn over the frame hide behind geometry without
+// tracing rays of their own.
+pub struct GBuffer {
+    pub width: usize,
+    pub height: usize,
+    depth: Vec<f32>,
+}
+
+impl GBuffer {
+    fn empty() -> Self {
+        GBuffer { width: 0, height: 0, depth: Vec::new() }
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        if (self.width, self.height) != (width, height) {
+            *self = GBuffer { width, height, depth: vec![f32::INFINITY; width * height] };
+        }
+    }
+
+    // Whether the geometry shown where `point` lands on screen lies in front of it. `camera`
+    // must be the one the frame was rendered with.
+    pub fn occludes(&self, camera: &Camera, point: &Vec3) -> bool {
+        let Some((x, y, depth)) = camera.project(point) else {
+            return false;
+        };
+        if x < 0.0 || y < 0.0 || x as usize >= self.width || y as usize >= self.height {
+            return false;
+        }
+        self.depth[y as usize * self.width + x as usize] < depth - 0.01
+    }
+}
+
+#[derive(Default)]
+struct RayCounters {
+    primary: AtomicU64,
+    secondary: AtomicU64,
+    shadow: AtomicU64,
+}
+
+// Rays traced since the stats were last taken.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub primary: u64,
+    // Reflection, refraction and portal rays.
+    pub secondary: u64,
+    pub shadow: u64,
+}
+
+impl RenderStats {
+    pub fn total(&self) -> u64 {
+        self.primary + self.secondary + self.shadow
+    }
+}
+
+// Where the camera and sun were when the previous frame was rendered.
+pub struct Motion {
+    pub camera: CameraPose,
+    pub sun: Vec3,
+}
+
+impl Renderer {
+    // `threads` of 0 uses every core.
+    pub fn new(settings: RenderSettings, threads: usize) -> Self {
+        Renderer {
+            settings,
+            reflection_plane: None,
+            pool: WorkerPool::new(threads),
+            accumulation: Mutex::new(Vec::new()),
+            gbuffer: Mutex::new(GBuffer::empty()),
+            stats: RayCounters::default(),
+            watchdog: Watchdog::default(),
+        }
+    }
+
+    pub fn gbuffer(&self) -> MutexGuard<'_, GBuffer> {
+        self.gbuffer.lock().unwrap()
+    }
+
+    // The render threads, for per-pixel work done outside the renderer.
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+
+    // Ray counts since the last call.
+    pub fn take_stats(&self) -> RenderStats {
+        RenderStats {
+            primary: self.stats.primary.swap(0, Ordering::Relaxed),
+            secondary: self.stats.secondary.swap(0, Ordering::Relaxed),
+            shadow: self.stats.shadow.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    // The camera's viewport must match the framebuffer size (see `Camera::set_viewport`).
+    pub fn render(&self, framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera) {
+        let region = framebuffer.bounds();
+        self.render_region(framebuffer, region, scene, camera, 1);
+    }
+
+    // Renders only the pixels inside `region`, averaging exactly `samples` primary rays per
+    // pixel spread over it on the R2 sequence. Pixels outside the region are left untouched.
+    pub fn render_region(&self, framebuffer: &mut Framebuffer, region: Rect, scene: &Scene, camera: &Camera, samples: usize) {
+        self.render_lit(framebuffer, region, &scene.objects, camera, &self.lighting(scene), samples);
+    }
+
+    // Renders only `group`'s share of the light.
+    pub fn render_group(&self, framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, group: LightGroup) {
+        // The reflection plane and probes keep one colour per pixel or texel, so they cannot
+        // serve several groups.
+        let lighting = Lighting { group: Some(group), probes: None, ..scene.lighting() };
+        let region = framebuffer.bounds();
+        self.render_lit(framebuffer, region, &scene.objects, camera, &lighting, 1);
+    }
+
+    // Averages `samples` renders spread over the shutter interval between the previous frame
+    // and this one, moving the camera and the sun (light and cube) along the way so they streak.
+    pub fn render_motion_blur(&self, framebuffer: &mut Framebuffer, scene: &mut Scene, camera: &Camera, previous: &Motion, samples: usize) {
+        let mut sums = self.accumulation.lock().unwrap();
+        sums.clear();
+        sums.resize(framebuffer.buffer.len(), Vec3::zeros());
+        let mut moment = Camera::new(camera.eye, camera.center, camera.up);
+        moment.projection = camera.projection;
+        let sun_direction = scene.sun.direction;
+
+        for sample in 0..samples.max(1) {
+            let t = (sample as f32 + 0.5) / samples.max(1) as f32;
+            moment.set_pose(&previous.camera.lerp(&camera.pose(), t));
+            scene.point_sun(previous.sun.lerp(&sun_direction, t).normalize());
+            self.render(framebuffer, scene, &moment);
+            for (sum, &pixel) in sums.iter_mut().zip(&framebuffer.buffer) {
+                *sum += Color::from_hex(pixel).to_vec3();
+            }
+        }
+
+        scene.point_sun(sun_direction);
+        let width = framebuffer.width;
+        for (index, (pixel, sum)) in framebuffer.buffer.iter_mut().zip(sums.iter()).enumerate() {
+            *pixel = dither::to_color(sum / samples.max(1) as f32, index % width, index / width, self.settings.dither).to_hex();
+        }
+    }
+
+    // Shades the primary ray through pixel position (x, y) of the camera's viewport, offset by
+    // `jitter` pixels; anything past its far clipping distance shows the sky or background
+    // instead. The colour is in levels from 0 to 255, not yet rounded (see `dither::to_color`).
+    pub fn trace_pixel(&self, camera: &Camera, pixel: (f32, f32), jitter: (f32, f32), scene: &Scene) -> Vec3 {
+        self.trace_through(camera, pixel, jitter, &scene.objects, &self.lighting(scene)).1
+    }
+
+    fn lighting<'a>(&'a self, scene: &'a Scene) -> Lighting<'a> {
+        Lighting { reflections: self.reflection_plane.as_ref(), ..scene.lighting() }
+    }
+
+    fn render_lit(
+        &self,
+        framebuffer: &mut Framebuffer,
+        region: Rect,
+        objects: &[Object],
+        camera: &Camera,
+        lighting: &Lighting,
+        samples: usize,
+    ) {
+        // Exactly `samples` rays per pixel, spread like the passes of a progressive render.
+        let offsets: Vec<(f32, f32)> = (0..samples.max(1)).map(ProgressiveRender::jitter).collect();
+
+        let region = region.clipped(framebuffer.width, framebuffer.height);
+        if region.is_empty() {
+            return;
+        }
+        let width = framebuffer.width;
+        let rows = region.y * width..(region.y + region.height) * width;
+        let pixel_rows: Vec<Mutex<&mut [u32]>> = framebuffer.buffer[rows.clone()].chunks_mut(width).map(Mutex::new).collect();
+        let mut gbuffer = self.gbuffer.lock().unwrap();
+        gbuffer.resize(framebuffer.width, framebuffer.height);
+        let depth_rows: Vec<Mutex<&mut [f32]>> = gbuffer.depth[rows].chunks_mut(width).map(Mutex::new).collect();
+
+        self.pool.run(pixel_rows.len(), &|row| {
+            let y = region.y + row;
+            let mut pixels = pixel_rows[row].lock().unwrap();
+            let mut depths = depth_rows[row].lock().unwrap();
+            for x in region.x..region.x + region.width {
+                let mut sum = Vec3::zeros();
+                let mut nearest = f32::INFINITY;
+                let mut flagged = false;
+                for &jitter in &offsets {
+                    let (ray, color, distance) = self.trace_through(camera, (x as f32, y as f32), jitter, objects, lighting);
+                    // One bad sample marks the whole pixel, rather than being averaged away.
+                    flagged |= self.settings.nan_watchdog && color == watchdog::MAGENTA;
+                    sum += color;
+                    if distance.is_finite() {
+                        let depth = camera.project(&(ray.origin + ray.direction * distance)).map_or(f32::INFINITY, |(_, _, depth)| depth);
+                        nearest = nearest.min(depth);
+                    }
+                }
+                let color = if flagged { watchdog::MAGENTA } else { sum / offsets.len() as f32 };
+                pixels[x] = dither::to_color(color, x, y, self.settings.dither).to_hex();
+                depths[x] = nearest;
+            }
+        });
+    }
+
+    // Like `trace_pixel`, also returning the ray and how far along it the geometry shown is
+    // (infinite for sky). Misses show a background pinned to the screen where it lands.
+    fn trace_through(
+        &self,
+        camera: &Camera,
+        (x, y): (f32, f32),
+        jitter: (f32, f32),
+        objects: &[Object],
+        lighting: &Lighting,
+    ) -> (Ray, Vec3, f32) {
+        let ray = camera.generate_ray(x, y, jitter);
+        let (color, distance) = self.trace_primary(&ray, objects, lighting);
+        let (width, height) = (camera.projection.width as f32, camera.projection.height as f32);
+        let plate = lighting
+            .background
+            .filter(|_| distance.is_infinite() && lighting.includes(LightGroup::Sky))
+            .and_then(|plate| plate.at_screen((x + jitter.0) / width, (y + jitter.1) / height, width / height));
+        (ray, plate.unwrap_or(color), distance)
+    }
+
+    // Shades a primary ray, also returning how far along it the geometry shown is (infinite for sky).
+    fn trace_primary(&self, ray: &Ray, objects: &[Object], lighting: &Lighting) -> (Vec3, f32) {
+        self.stats.primary.fetch_add(1, Ordering::Relaxed);
+        if self.settings.nan_watchdog {
+            watchdog::take_taint();
+        }
+        let (intersect, hit_object) = closest_intersect(&ray.origin, &ray.direction, objects);
+        if !intersect.is_intersecting || intersect.distance > ray.far {
+            let color = sky_radiance(&ray.direction, lighting);
+            return (if self.settings.nan_watchdog { self.watchdog.check(ray, None, color) } else { color }, f32::INFINITY);
+        }
+        let color = self.shade(ray, &intersect, hit_object, objects, lighting, 0).to_vec3();
+        (if self.settings.nan_watchdog { self.watchdog.check(ray, Some((&intersect, hit_object)), color) } else { color }, intersect.distance)
+    }
+
+    // Colour seen along a secondary ray, e.g. a reflection, or the sky once `depth` gets too deep.
+    fn cast_ray(&self, ray: &Ray, objects: &[Object], lighting: &Lighting, depth: u32) -> Color {
+        self.stats.secondary.fetch_add(1, Ordering::Relaxed);
+        if depth > 3 {
+            return sky_sample(&ray.direction, lighting);
+        }
+        let (intersect, hit_object) = closest_intersect(&ray.origin, &ray.direction, objects);
+        if !intersect.is_intersecting {
+            return sky_sample(&ray.direction, lighting);
+        }
+        self.shade(ray, &intersect, hit_object, objects, lighting, depth)
+    }
+
+    // Lights the hit of `ray` on `hit_object` and follows the rays it spawns.
+    fn shade(
+        &self,
+        ray: &Ray,
+        intersect: &Intersect,
+        hit_object: Option<&Object>,
+        objects: &[Object],
+        lighting: &Lighting,
+        depth: u32,
+    ) -> Color {
+        let (ray_origin, ray_direction, cone) = (&ray.origin, &ray.direction, &ray.cone);
+        let sun = lighting.sun;
+
+        if let Some(Object::Portal(portal)) = hit_object {
+            if let (Some(uv), Some(Object::Portal(target))) = (intersect.uv, objects.get(portal.link)) {
+                if portal.is_opening(uv) {
+                    let (origin, direction) = portal.teleport(target, &intersect.point, ray_direction);
+                    let through = Ray { channel: ray.channel, ..Ray::new(origin, direction, cone.propagate(intersect.distance)) };
+                    return self.cast_ray(&through, objects, lighting, depth + 1);
+                }
+            }
+        }
+
+        // Secondary hits are only checked here; `max` and the rounding to colours below would
+        // quietly drop a broken normal.
+        self.watch(intersect.normal.as_slice());
+        let light_dir = sun.direction;
+        let view_dir = (ray_origin - intersect.point).normalize();
+        let reflect_dir = reflect(&-light_dir, &intersect.normal).normalize();
+
+        let light_intensity = if lighting.includes(LightGroup::Sun) { self.settings.sunlight.intensity(sun) } else { 0.0 };
+        let shadow_intensity = if light_intensity > 0.0 { self.sun_shadow(intersect, hit_object, objects, lighting) } else { 0.0 };
+
+        let diffuse_intensity = intersect.normal.dot(&light_dir).abs().max(0.5);
+        let specular_intensity = if intersect.material.anisotropy > 0.0 {
+            anisotropic_specular(intersect, &light_dir, &view_dir)
+        } else {
+            view_dir.dot(&reflect_dir).max(0.0).powf(intersect.material.specular)
+        };
+
+        // A pinpoint cone always picks the full size texture.
+        let texture_cone = if self.settings.texture_filtering { *cone } else { RayCone::pinpoint() };
+        let foam = shore_foam(intersect, hit_object, objects);
+        let diffuse_color = surface_color(intersect, ray_direction, &texture_cone).lerp(FOAM_COLOR, foam);
+
+        let ambient_light = if !lighting.includes(LightGroup::Fill) {
+            0.0
+        } else if self.settings.sky_samples == 0 {
+            ambient_level(sun, lighting.moon_phase) * lighting.daylight
+        } else {
+            let openness = lighting.sky_occlusion.lookup(&intersect.point, &intersect.normal, || {
+                self.sky_openness(intersect, objects)
+            });
+            ambient_level(sun, lighting.moon_phase) * lighting.daylight * openness
+        };
+        let ambient_occlusion = match hit_object {
+            Some(Object::Cube(cube, false)) if self.settings.corner_occlusion && ambient_light > 0.0 => cube
+                .grid_cell()
+                .zip(intersect.uv)
+                .map_or(1.0, |(cell, uv)| lighting.corners.factor(cell, &intersect.normal, uv)),
+            _ => 1.0,
+        };
+        let ambient_light = ambient_light * ambient_occlusion;
+        self.watch(&[diffuse_intensity, specular_intensity, light_intensity, shadow_intensity, ambient_light]);
+
+        let diffuse = diffuse_color * intersect.material.albedo.diffuse_weight * diffuse_intensity * light_intensity * (1.0 - shadow_intensity);
+        let specular = Color::new(255, 255, 255) * intersect.material.albedo.specular_weight * specular_intensity * light_intensity * (1.0 - shadow_intensity);
+        let ambient = diffuse_color * ambient_light;
+
+        let mut color = diffuse + specular + ambient;
+
+        if intersect.material.is_emissive() && lighting.includes(LightGroup::Glow) {
+            color = color + emitted_color(intersect, ray_direction, &texture_cone);
+        }
+
+        if lighting.includes(LightGroup::Glow) {
+            for light in lighting.lights {
+                color = color + self.lamp_light(light, intersect, &view_dir, diffuse_color, objects);
+            }
+        }
+
+        let translucency = intersect.material.translucency;
+        if translucency > 0.0 && light_intensity > 0.0 {
+            // Wrapped diffuse for light coming through the block from behind, fading out as the
+            // face turns towards the sun.
+            let backlight = ((TRANSLUCENCY_WRAP - intersect.normal.dot(&light_dir)) / (1.0 + TRANSLUCENCY_WRAP)).min(1.0);
+            if backlight > 0.0 {
+                let blocked = if sun.switches.casts_shadows {
+                    self.cast_shadow_past(intersect, &light_dir, objects, hit_object, f32::INFINITY)
+                } else {
+                    0.0
+                };
+                color = color + diffuse_color * translucency * backlight * light_intensity * (1.0 - blocked);
+            }
+        }
+
+        let irradiance = lighting.irradiance;
+        if irradiance.enabled && lighting.includes(LightGroup::Fill) {
+            let bounce = irradiance.lookup(&intersect.point, &intersect.normal, || {
+                self.bounce_irradiance(intersect, objects, sun, irradiance.samples)
+            });
+            let bounce = diffuse_color.to_vec3().component_mul(&bounce) / 255.0;
+            self.watch(bounce.as_slice());
+            color = color + Color::from_vec3(bounce * intersect.material.albedo.diffuse_weight * irradiance.strength * ambient_occlusion);
+        }
+
+        // Polished surfaces give up that much of their own shading for what they mirror, so a
+        // perfect mirror shows only the scene around it. The reflectivity is what they mirror
+        // seen head on; like water and glass they mirror more at glancing angles.
+        let reflectivity = if intersect.material.albedo.reflectivity > 0.0 {
+            schlick(intersect.material.albedo.reflectivity, ray_direction.dot(&intersect.normal))
+        } else {
+            0.0
+        };
+        self.watch(&[reflectivity]);
+        // Foam hides the water underneath.
+        let transparency = intersect.material.albedo.transparency * (1.0 - foam);
+
+        // Traced once and shared by the opaque and the see-through share of the surface.
+        let reflection = if reflectivity > 0.0 || transparency > 0.0 {
+            self.reflection(intersect, ray, objects, lighting, depth)
+        } else {
+            Color::black()
+        };
+        if reflectivity > 0.0 {
+            color = color * (1.0 - reflectivity) + reflection * reflectivity;
+        }
+
+        if transparency > 0.0 {
+            let material = &intersect.material;
+            let next_cone = cone.propagate(intersect.distance);
+
+            // What leaves the surface for light bending at `refractive_index`: the reflection,
+            // and what shows through weighted by how much the surface lets through.
+            let passed = |refractive_index: f32, channel: Option<usize>| {
+                // An index of 0, as materials that never meant to be see-through have, would
+                // bend rays into NaN; treat it as no bending at all.
+                let refractive_index = if refractive_index > 0.0 { refractive_index } else { 1.0 };
+                let reflectance = fresnel(ray_direction, &intersect.normal, refractive_index);
+                self.watch(&[reflectance]);
+                // Polished see-through surfaces mirror at least as much as their reflectivity says.
+                let reflectance = reflectance.max(reflectivity);
+                let transmission = match refract(ray_direction, &intersect.normal, refractive_index) {
+                    Some(refraction_dir) if reflectance < 1.0 => {
+                        let refraction_origin = offset_origin(intersect, &refraction_dir, &self.settings.shadow_bias);
+                        let refracted = Ray { channel, ..Ray::new(refraction_origin, refraction_dir, next_cone) };
+                        let refraction = self.cast_ray(&refracted, objects, lighting, depth + 1);
+                        let tint = material.diffuse.to_vec3() / 255.0;
+                        let transmitted = Color::from_vec3(refraction.to_vec3().component_mul(&tint));
+
+                        // Tint once, where the ray enters from outside, by the whole depth below it.
+                        let entering = ray_direction.dot(&intersect.normal) < 0.0;
+                        if material.depth_scale > 0.0 && entering && !in_depth_tinted(&(intersect.point - ray_direction * 1e-3), objects) {
+                            let depth = depth_below(&refraction_origin, &refraction_dir, objects);
+                            transmitted.lerp(material.deep_color, 1.0 - (-depth / material.depth_scale).exp())
+                        } else {
+                            transmitted
+                        }
+                    }
+                    _ => Color::black(),
+                };
+                reflection * reflectance + transmission * (1.0 - reflectance)
+            };
+
+            let passed = match ray.channel {
+                // Light split into colours further up keeps to its own colour's index and is
+                // not split again.
+                Some(channel) if material.dispersion > 0.0 => passed(material.channel_indices()[channel], Some(channel)),
+                None if self.settings.dispersion && material.dispersion > 0.0 => {
+                    // Each colour bends by its own index along a ray of its own, splitting
+                    // edges seen through the material into rainbow fringes.
+                    let mut channels = Vec3::zeros();
+                    for (channel, refractive_index) in material.channel_indices().into_iter().enumerate() {
+                        channels[channel] = passed(refractive_index, Some(channel)).to_vec3()[channel];
+                    }
+                    Color::from_vec3(channels)
+                }
+                channel => passed(material.refractive_index, channel),
+            };
+
+            color = color * (1.0 - transparency) + passed * transparency;
+        }
+
+        color
+    }
+
+    // What a surface mirrors along the reflection of `ray`. Primary hits on the reflection
+    // plane read it from there instead of tracing, and other hits read the reflection probes
+    // when they are in use.
+    fn reflection(&self, intersect: &Intersect, ray: &Ray, objects: &[Object], lighting: &Lighting, depth: u32) -> Color {
+        let plane = lighting.reflections.filter(|plane| depth == 0 && plane.covers(&intersect.point, &intersect.normal));
+        let mirrored = plane.and_then(|plane| {
+            plane.sample(&intersect.point, |ray| self.cast_ray(ray, objects, lighting, depth + 1))
+        });
+        if let Some(color) = mirrored {
+            return color;
+        }
+
+        let reflection_dir = reflect(&ray.direction, &intersect.normal).normalize();
+        if let Some(probes) = lighting.probes {
+            let baking = Lighting { probes: None, ..*lighting };
+            let probed = probes.lookup(&intersect.point, &reflection_dir, |origin, direction| {
+                self.cast_ray(&Ray::new(*origin, *direction, RayCone::pinpoint()), objects, &baking, depth + 1)
+            });
+            if let Some(color) = probed {
+                return color;
+            }
+        }
+        let reflection_origin = offset_origin(intersect, &reflection_dir, &self.settings.shadow_bias);
+        let reflected = Ray { channel: ray.channel, ..Ray::new(reflection_origin, reflection_dir, ray.cone.propagate(intersect.distance)) };
+        self.cast_ray(&reflected, objects, lighting, depth + 1)
+    }
+
+    // Diffuse and specular light from a lamp, unless something lies between it and the hit.
+    fn lamp_light(&self, light: &Light, intersect: &Intersect, view_dir: &Vec3, diffuse_color: Color, objects: &[Object]) -> Color {
+        let to_light = light.position - intersect.point;
+        let distance = to_light.norm();
+        let light_dir = to_light / distance;
+        let facing = intersect.normal.dot(&light_dir);
+        let falloff = light.intensity / (distance * distance).max(1.0);
+        if !light.switches.enabled || facing <= 0.0 || falloff < LAMP_CUTOFF {
+            return Color::black();
+        }
+        let shadow = if light.switches.casts_shadows { self.cast_shadow_past(intersect, &light_dir, objects, None, distance) } else { 0.0 };
+        let lit = falloff * (1.0 - shadow);
+        if lit <= 0.0 {
+            return Color::black();
+        }
+
+        let material = &intersect.material;
+        let tint = light.color.to_vec3() / 255.0;
+        let diffuse = diffuse_color.to_vec3().component_mul(&tint) * material.albedo.diffuse_weight * facing;
+        let highlight = view_dir.dot(&reflect(&-light_dir, &intersect.normal)).max(0.0).powf(material.specular);
+        let specular = tint * 255.0 * material.albedo.specular_weight * highlight;
+        let radiance = (diffuse + specular) * lit;
+        self.watch(radiance.as_slice());
+        Color::from_vec3(radiance)
+    }
+
+    // Shows shading values to the watchdog, when it is on, before they are rounded into colours.
+    fn watch(&self, values: &[f32]) {
+        if self.settings.nan_watchdog {
+            watchdog::watch(values);
+        }
+    }
+
+    // Shadow from the sun, reusing the cached occluder of grid top faces where there is one.
+    // Each point aims at its own spot on the sun's disc, so shadow edges soften as samples add up.
+    fn sun_shadow(&self, intersect: &Intersect, hit_object: Option<&Object>, objects: &[Object], lighting: &Lighting) -> f32 {
+        let sun = lighting.sun;
+        if !sun.switches.casts_shadows {
+            return 0.0;
+        }
+        let (u, v) = point_noise(&intersect.point);
+        let mut light_dir = sun.sample_direction(u, v);
+        // Faces edge-on to the sun would otherwise shadow themselves with half their samples.
+        if light_dir.dot(&intersect.normal) < 0.0 && sun.direction.dot(&intersect.normal) >= 0.0 {
+            light_dir = sun.direction;
+        }
+        let cell = match hit_object {
+            Some(Object::Cube(cube, false)) if intersect.normal.y > 0.5 => cube.grid_cell(),
+            _ => None,
+        };
+        let Some(cell) = cell else {
+            return self.cast_shadow(intersect, &light_dir, objects);
+        };
+
+        match lighting.visibility.top_face(cell, |point| first_occluder(point, &sun.direction, objects)) {
+            SunOcclusion::Clear => 0.0,
+            SunOcclusion::Blocked(index) => self.cast_shadow(intersect, &light_dir, &objects[index..=index]),
+            SunOcclusion::Mixed => self.cast_shadow(intersect, &light_dir, objects),
+        }
+    }
+
+    // Shadow from a directional light along `light_dir`. The sun's own cube is only a marker
+    // in the sky and never blocks its light.
+    fn cast_shadow(&self, intersect: &Intersect, light_dir: &Vec3, objects: &[Object]) -> f32 {
+        self.cast_shadow_past(intersect, light_dir, objects, None, f32::INFINITY)
+    }
+
+    // Like `cast_shadow`, but ignoring `skip`, the object the shadow ray starts inside, and
+    // anything `reach` or further along the ray, past the light.
+    fn cast_shadow_past(
+        &self,
+        intersect: &Intersect,
+        light_dir: &Vec3,
+        objects: &[Object],
+        skip: Option<&Object>,
+        reach: f32,
+    ) -> f32 {
+        self.stats.shadow.fetch_add(1, Ordering::Relaxed);
+        let shadow_ray_origin = offset_origin(intersect, light_dir, &self.settings.shadow_bias);
+
+        for object in objects {
+            if skip.is_some_and(|skip| std::ptr::eq(skip, object)) {
+                continue;
+            }
+            let shadow_intersect = match object {
+                Object::Cube(_, true) | Object::Portal(_) | Object::Wall(_) => continue,
+                Object::Cube(cube, false) => cube.ray_intersect(&shadow_ray_origin, light_dir),
+                Object::Csg(csg) => csg.ray_intersect(&shadow_ray_origin, light_dir),
+                Object::Billboard(billboard) => billboard.ray_intersect(&shadow_ray_origin, light_dir),
+                Object::Door(door) => door.ray_intersect(&shadow_ray_origin, light_dir),
+                Object::Thrown(block) => block.ray_intersect(&shadow_ray_origin, light_dir),
+            };
+            if shadow_intersect.is_intersecting && shadow_intersect.distance < reach {
+                return 1.0 - shadow_intersect.material.albedo.transparency;
+            }
+        }
+
+        0.0
+    }
+
+    // Share of cosine-weighted rays from `intersect` that reach the sky without hitting anything.
+    fn sky_openness(&self, intersect: &Intersect, objects: &[Object]) -> f32 {
+        let directions = hemisphere_directions(&intersect.normal, self.settings.sky_samples);
+        let open = directions
+            .iter()
+            .filter(|direction| {
+                let origin = offset_origin(intersect, direction, &self.settings.shadow_bias);
+                first_occluder(&origin, direction, objects).is_none()
+            })
+            .count();
+        open as f32 / directions.len() as f32
+    }
+
+    // Average sunlight re-emitted by the surfaces seen from `intersect`, i.e. one diffuse bounce.
+    fn bounce_irradiance(&self, intersect: &Intersect, objects: &[Object], sun: &DirectionalLight, samples: usize) -> Vec3 {
+        let light_intensity = self.settings.sunlight.intensity(sun);
+        if light_intensity <= 0.0 {
+            return Vec3::zeros();
+        }
+
+        let mut irradiance = Vec3::zeros();
+        for direction in hemisphere_directions(&intersect.normal, samples) {
+            let origin = offset_origin(intersect, &direction, &self.settings.shadow_bias);
+            let (hit, _) = closest_intersect(&origin, &direction, objects);
+            if !hit.is_intersecting {
+                continue;
+            }
+
+            let incidence = hit.normal.dot(&sun.direction).max(0.0);
+            if incidence <= 0.0 {
+                continue;
+            }
+            let shadow_intensity = if sun.switches.casts_shadows { self.cast_shadow(&hit, &sun.direction, objects) } else { 0.0 };
+            irradiance += surface_color(&hit, &direction, &RayCone::pinpoint()).to_vec3() * hit.material.albedo.diffuse_weight * incidence * light_intensity * (1.0 - shadow_intensity);
+        }
+
+        irradiance / samples as f32
+    }
+}
+
+// Pseudo-random pair in [0, 1) that stays the same for a given surface point.
+fn point_noise(point: &Vec3) -> (f32, f32) {
+    let bits = [point.x, point.y, point.z].map(|c| c.to_bits() as u64);
+    let mut rng = Rng::new(bits[0] ^ bits[1].rotate_left(21) ^ bits[2].rotate_left(42));
+    rng.next_u64();
+    (rng.next_f32(), rng.next_f32())
+}
+
+// Texel of `texture` under the hit point, filtered for the ray's footprint there.
+fn sample_texture(texture: &Texture, intersect: &Intersect, ray_direction: &Vec3, cone: &RayCone) -> Color {
+    let (u, v) = intersect.material.transform_uv(intersect.uv.unwrap());
+    let cos = intersect.normal.dot(ray_direction).abs().max(0.05);
+    let footprint = cone.width_at(intersect.distance) * intersect.material.uv_stretch() / (cos * intersect.uv_size);
+    let [r, g, b] = texture.get_color_lod(u, v, texture.lod_for_footprint(footprint));
+    Color::new(r, g, b)
+}
+
+fn surface_color(intersect: &Intersect, ray_direction: &Vec3, cone: &RayCone) -> Color {
+    if let Some(texture) = &intersect.material.texture {
+        let tint = intersect.material.tint.to_vec3() / 255.0;
+        let texel = sample_texture(texture, intersect, ray_direction, cone);
+        let color = Color::from_vec3(texel.to_vec3().component_mul(&tint));
+        snow_over(color, intersect)
+    } else {
+        snow_over(intersect.material.diffuse, intersect)
+    }
+}
+
+fn emitted_color(intersect: &Intersect, ray_direction: &Vec3, cone: &RayCone) -> Color {
+    let material = &intersect.material;
+    match (&material.emission_map, intersect.uv) {
+        (Some(map), Some(_)) => {
+            let mask = sample_texture(map, intersect, ray_direction, cone).to_vec3() / 255.0;
+            Color::from_vec3(material.emission.to_vec3().component_mul(&mask))
+        }
+        _ => material.emission,
+    }
+}
+
+// Foam on the top face of a water block, fading out over `FOAM_WIDTH` from each side that
+// touches an opaque block at the same level. Blocks that foam themselves are water, not shore.
+fn shore_foam(intersect: &Intersect, hit_object: Option<&Object>, objects: &[Object]) -> f32 {
+    let strength = intersect.material.foam;
+    if strength <= 0.0 || intersect.normal.y < 0.5 {
+        return 0.0;
+    }
+    let Some(cell) = (match hit_object {
+        Some(Object::Cube(cube, false)) => cube.grid_cell(),
+        _ => None,
+    }) else {
+        return 0.0;
+    };
+
+    let solid = |dx: i32, dz: i32| {
+        let neighbour = [cell[0] + dx, cell[1], cell[2] + dz];
+        objects.iter().any(|object| match object {
+            Object::Cube(cube, false) => {
+                cube.material.albedo.is_opaque() && cube.material.foam <= 0.0 && cube.grid_cell() == Some(neighbour)
+            }
+            _ => false,
+        })
+    };
+    let local = intersect.point - Vec3::new(cell[0] as f32, 0.0, cell[2] as f32);
+    let sides = [(-1, 0, local.x + 0.5), (1, 0, 0.5 - local.x), (0, -1, local.z + 0.5), (0, 1, 0.5 - local.z)];
+    let edge = sides
+        .iter()
+        .filter(|&&(_, _, distance)| distance < FOAM_WIDTH)
+        .filter(|&&(dx, dz, _)| solid(dx, dz))
+        .map(|&(_, _, distance)| distance)
+        .fold(FOAM_WIDTH, f32::min);
+    // Ragged rather than a clean stripe.
+    let (noise, _) = point_noise(&intersect.point);
+    strength * ((1.0 - edge / FOAM_WIDTH) * (0.75 + 0.5 * noise)).clamp(0.0, 1.0)
+}
+
+fn in_depth_tinted(point: &Vec3, objects: &[Object]) -> bool {
+    objects.iter().any(|object| match object {
+        Object::Cube(cube, false) => cube.material.depth_scale > 0.0 && cube.contains(point, 0.0),
+        _ => false,
+    })
+}
+
+// How far a ray goes through depth-tinted blocks like water before reaching something else.
+fn depth_below(origin: &Vec3, direction: &Vec3, objects: &[Object]) -> f32 {
+    objects
+        .iter()
+        .filter(|object| match object {
+            Object::Cube(cube, false) => cube.material.depth_scale <= 0.0,
+            Object::Cube(_, true) | Object::Portal(_) => false,
+            _ => true,
+        })
+        .map(|object| object.ray_intersect(origin, direction))
+        .filter(|hit| hit.is_intersecting)
+        .fold(f32::INFINITY, |nearest, hit| nearest.min(hit.distance))
+}
+
+fn snow_over(color: Color, intersect: &Intersect) -> Color {
+    let snow = intersect.material.snow;
+    if snow <= 0.0 || intersect.normal.y < 0.5 {
+        return color;
+    }
+    color.lerp(SNOW_COLOR, snow)
+}
+
+fn ambient_level(sun: &DirectionalLight, moon_phase: f32) -> f32 {
+    if sun.direction.y < 0.0 {
+        NEW_MOON_AMBIENT + (FULL_MOON_AMBIENT - NEW_MOON_AMBIENT) * moon_illumination(moon_phase)
+    } else {
+        DAY_AMBIENT
+    }
+}
+
+// Ashikhmin-Shirley lobe with a lower exponent along the grain, so the highlight stretches
+// along it. The grain follows the face's texture axes, including the material's rotation.
+fn anisotropic_specular(intersect: &Intersect, light_dir: &Vec3, view_dir: &Vec3) -> f32 {
+    let material = &intersect.material;
+    let normal = intersect.normal;
+    let half = (light_dir + view_dir).normalize();
+    let cos_half = normal.dot(&half);
+    if cos_half <= 0.0 {
+        return 0.0;
+    }
+
+    let (u_axis, v_axis) = Cube::uv_axes(&normal);
+    let grain = if material.uv_quarter_turns.is_multiple_of(2) { v_axis } else { u_axis };
+    let tangent = (grain - normal * grain.dot(&normal)).normalize();
+    let bitangent = normal.cross(&tangent);
+
+    // A half-vector lobe needs about four times the Phong exponent for the same highlight size.
+    let along = 4.0 * material.specular * (1.0 - material.anisotropy);
+    let across = 4.0 * material.specular / (1.0 - material.anisotropy);
+    let exponent = (along * half.dot(&tangent).powi(2) + across * half.dot(&bitangent).powi(2))
+        / (1.0 - cos_half * cos_half).max(1e-6);
+    cos_half.powf(exponent)
+}
+
+fn sky_sample(direction: &Vec3, lighting: &Lighting) -> Color {
+    if let Some(plate) = lighting.background.filter(|_| lighting.includes(LightGroup::Sky)).and_then(|plate| plate.along(direction)) {
+        return Color::from_vec3(plate);
+    }
+    if lighting.includes(LightGroup::Sky) {
+        let sky = lighting.aurora.over(lighting.sky.sample(direction) * lighting.daylight, direction, &lighting.sun.direction);
+        moon_over(sky, direction, &lighting.sun.direction, lighting.moon_phase)
+    } else {
+        Color::black()
+    }
+}
+
+// The sky seen directly, kept between whole levels so its gradient can be dithered. The moon
+// and aurora are drawn on the rounded gradient and only what they change is added back.
+fn sky_radiance(direction: &Vec3, lighting: &Lighting) -> Vec3 {
+    if !lighting.includes(LightGroup::Sky) {
+        return Vec3::zeros();
+    }
+    if let Some(plate) = lighting.background.and_then(|plate| plate.along(direction)) {
+        return plate;
+    }
+    let gradient = lighting.sky.radiance(direction) * lighting.daylight;
+    let rounded = Color::from_vec3(gradient);
+    let overlaid = moon_over(lighting.aurora.over(rounded, direction, &lighting.sun.direction), direction, &lighting.sun.direction, lighting.moon_phase);
+    gradient + overlaid.to_vec3() - rounded.to_vec3()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daycycle::DayCycle;
+    use crate::material::Material;
+    use crate::settings::ShadowBias;
+
+    // A lamp three blocks above a grey floor point, facing it.
+    fn lamp_over_floor() -> (Renderer, Light, Intersect) {
+        let settings = RenderSettings {
+            shadow_bias: ShadowBias { constant: 1e-3, distance_scale: 1e-4, slope_scale: 1e-4 },
+            ..RenderSettings::default()
+        };
+        let renderer = Renderer::new(settings, 1);
+        let light = Light::new(Vec3::new(0.0, 3.0, 0.0), Color::new(255, 255, 255), 9.0);
+        let intersect = Intersect::new(Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0), 5.0, grey(), None);
+        (renderer, light, intersect)
+    }
+
+    fn blocker() -> Vec<Object> {
+        let material = Material::new(Color::new(100, 100, 100), 1.0, [0.9, 0.1, 0.0, 0.0], 0.0, None);
+        vec![Object::Cube(Cube { center: Vec3::new(0.0, 1.5, 0.0), size: 1.0, material }, false)]
+    }
+
+    fn lit(renderer: &Renderer, light: &Light, intersect: &Intersect, objects: &[Object]) -> Color {
+        let view_dir = Vec3::new(0.0, 1.0, 0.0);
+        renderer.lamp_light(light, intersect, &view_dir, Color::new(200, 200, 200), objects)
+    }
+
+    #[test]
+    fn disabled_lamps_give_no_light() {
+        let (renderer, mut light, intersect) = lamp_over_floor();
+        assert_ne!(lit(&renderer, &light, &intersect, &[]).to_hex(), 0);
+        light.switches.enabled = false;
+        assert_eq!(lit(&renderer, &light, &intersect, &[]).to_hex(), 0);
+    }
+
+    #[test]
+    fn shadowless_lamps_shine_through_blockers() {
+        let (renderer, mut light, intersect) = lamp_over_floor();
+        let objects = blocker();
+        assert_eq!(lit(&renderer, &light, &intersect, &objects).to_hex(), 0);
+        light.switches.casts_shadows = false;
+        assert_eq!(lit(&renderer, &light, &intersect, &objects).to_hex(), lit(&renderer, &light, &intersect, &[]).to_hex());
+    }
+
+    fn grey() -> Material {
+        Material::new(Color::new(200, 200, 200), 1.0, [0.9, 0.1, 0.0, 0.0], 0.0, None)
+    }
+
+    #[test]
+    fn the_watchdog_catches_nan_normals_that_shading_would_hide() {
+        let renderer = Renderer::new(RenderSettings { nan_watchdog: true, ..RenderSettings::default() }, 1);
+        // The scene keeps the sun's marker cube in the first slot.
+        let sun = Object::Cube(Cube { center: Vec3::zeros(), size: 1.0, material: Material::black() }, true);
+        let scene = Scene::new(vec![sun], DayCycle::new(10.0, 12.0), Material::black());
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), RayCone::pinpoint());
+        let broken = Intersect::new(Vec3::zeros(), Vec3::new(f32::NAN, f32::NAN, f32::NAN), 1.0, grey(), None);
+
+        watchdog::take_taint();
+        let color = renderer.shade(&ray, &broken, None, &[], &scene.lighting(), 0).to_vec3();
+        assert!(color.iter().all(|channel| channel.is_finite()), "8-bit colours cannot hold NaN");
+        // Without the hit, as for a secondary hit that went wrong.
+        assert_eq!(renderer.watchdog.check(&ray, None, color), watchdog::MAGENTA);
+    }
+
+    #[test]
+    fn the_watchdog_catches_lamps_inside_the_surface_they_light() {
+        let (mut renderer, mut light, intersect) = lamp_over_floor();
+        renderer.settings.nan_watchdog = true;
+        light.position = intersect.point;
+
+        watchdog::take_taint();
+        lit(&renderer, &light, &intersect, &[]);
+        assert!(watchdog::take_taint());
+    }
+}
@@ -0,0 +1,73 @@
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::dither;
+use crate::framebuffer::Framebuffer;
+
+const PALETTES: [(&str, &[u32]); 4] = [
+    (
+        "pico8",
+        &[
+            0x000000, 0x1D2B53, 0x7E2553, 0x008751, 0xAB5236, 0x5F574F, 0xC2C3C7, 0xFFF1E8, 0xFF004D, 0xFFA300, 0xFFEC27, 0x00E436,
+            0x29ADFF, 0x83769C, 0xFF77A8, 0xFFCCAA,
+        ],
+    ),
+    (
+        "ega16",
+        &[
+            0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA, 0x555555, 0x5555FF, 0x55FF55, 0x55FFFF,
+            0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+        ],
+    ),
+    // Every mix of four levels per channel.
+    (
+        "ega64",
+        &[
+            0x000000, 0x000055, 0x0000AA, 0x0000FF, 0x005500, 0x005555, 0x0055AA, 0x0055FF, 0x00AA00, 0x00AA55, 0x00AAAA, 0x00AAFF,
+            0x00FF00, 0x00FF55, 0x00FFAA, 0x00FFFF, 0x550000, 0x550055, 0x5500AA, 0x5500FF, 0x555500, 0x555555, 0x5555AA, 0x5555FF,
+            0x55AA00, 0x55AA55, 0x55AAAA, 0x55AAFF, 0x55FF00, 0x55FF55, 0x55FFAA, 0x55FFFF, 0xAA0000, 0xAA0055, 0xAA00AA, 0xAA00FF,
+            0xAA5500, 0xAA5555, 0xAA55AA, 0xAA55FF, 0xAAAA00, 0xAAAA55, 0xAAAAAA, 0xAAAAFF, 0xAAFF00, 0xAAFF55, 0xAAFFAA, 0xAAFFFF,
+            0xFF0000, 0xFF0055, 0xFF00AA, 0xFF00FF, 0xFF5500, 0xFF5555, 0xFF55AA, 0xFF55FF, 0xFFAA00, 0xFFAA55, 0xFFAAAA, 0xFFAAFF,
+            0xFFFF00, 0xFFFF55, 0xFFFFAA, 0xFFFFFF,
+        ],
+    ),
+    ("gameboy", &[0x0F380F, 0x306230, 0x8BAC0F, 0x9BBC0F]),
+];
+
+// The finished image cut down to one of a few old hardware palettes. An ordered dither
+// nudges each pixel by up to half the usual gap between palette colours first, so gradients
+// become a regular pattern of the two nearest colours rather than flat bands.
+pub struct Retro {
+    pub name: &'static str,
+    colors: Vec<Vec3>,
+    spread: f32,
+}
+
+impl Retro {
+    pub fn named(name: &str) -> Option<Self> {
+        let (name, palette) = PALETTES.iter().find(|(palette, _)| palette.eq_ignore_ascii_case(name))?;
+        let colors: Vec<Vec3> = palette.iter().map(|&hex| Color::from_hex(hex).to_vec3()).collect();
+        // The mean distance from each colour to its nearest neighbour.
+        let spread = colors
+            .iter()
+            .map(|a| colors.iter().filter(|b| *b != a).map(|b| (a - b).norm()).fold(f32::INFINITY, f32::min))
+            .sum::<f32>()
+            / colors.len() as f32;
+        Some(Retro { name, colors, spread })
+    }
+
+    pub fn names() -> String {
+        PALETTES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+    }
+
+    pub fn apply(&self, framebuffer: &mut Framebuffer) {
+        let width = framebuffer.width;
+        for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+            let offset = dither::ordered(index % width, index / width) * self.spread;
+            let color = Color::from_hex(*pixel).to_vec3().add_scalar(offset);
+            let nearest = self.colors.iter().min_by(|a, b| (*a - color).norm_squared().total_cmp(&(*b - color).norm_squared()));
+            if let Some(nearest) = nearest {
+                *pixel = Color::from_vec3(*nearest).to_hex();
+            }
+        }
+    }
+}
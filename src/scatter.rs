@@ -0,0 +1,55 @@
+use nalgebra_glm::Vec3;
+use crate::config::{BiomeConfig, Decoration};
+use crate::palette::Palette;
+use crate::rng::Rng;
+use crate::scene::{Object, exposed_tops};
+
+// A decoration picked for the open cell above a block of ground.
+pub struct Spot<'a> {
+    pub cell: Vec3,
+    pub decoration: &'a Decoration,
+}
+
+// Poisson-disk sampling over the ground: every block whose top sees the sky and is made of
+// a biome's surface is a candidate. Candidates are visited in an order shuffled from `seed`
+// and kept unless a spot already kept lies closer than the biome's spacing, measured across
+// the ground, so decorations never bunch up yet leave no regular pattern.
+pub fn scatter<'a>(objects: &[Object], palette: &Palette, biomes: &'a [BiomeConfig], seed: u64) -> Vec<Spot<'a>> {
+    let mut candidates: Vec<(Vec3, &BiomeConfig)> = exposed_tops(objects)
+        .into_iter()
+        .filter_map(|index| match &objects[index] {
+            Object::Cube(cube, false) if cube.material.albedo.is_opaque() => {
+                let surface = palette.name_of(&cube.material)?;
+                let biome = biomes.iter().find(|biome| biome.surface == surface && !biome.decorations.is_empty())?;
+                Some((cube.center + Vec3::new(0.0, cube.size, 0.0), biome))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut rng = Rng::new(seed);
+    for i in (1..candidates.len()).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        candidates.swap(i, j);
+    }
+
+    let mut spots: Vec<Spot> = Vec::new();
+    for (cell, biome) in candidates {
+        let crowded = spots.iter().any(|spot| {
+            let (dx, dz) = (spot.cell.x - cell.x, spot.cell.z - cell.z);
+            dx * dx + dz * dz < biome.spacing * biome.spacing
+        });
+        if crowded {
+            continue;
+        }
+        let total: f32 = biome.decorations.iter().map(|decoration| decoration.weight).sum();
+        let mut roll = rng.next_f32() * total;
+        let picked = biome.decorations.iter().find(|decoration| {
+            roll -= decoration.weight;
+            roll < 0.0
+        });
+        let Some(picked) = picked.or(biome.decorations.last()) else { continue };
+        spots.push(Spot { cell, decoration: &picked.decoration });
+    }
+    spots
+}
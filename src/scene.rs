@@ -0,0 +1,283 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashSet;
+use crate::background::Background;
+use crate::billboard::Billboard;
+use crate::corner_occlusion::CornerOcclusion;
+use crate::csg::Csg;
+use crate::cube::Cube;
+use crate::daycycle::DayCycle;
+use crate::door::Door;
+use crate::irradiance::IrradianceCache;
+use crate::light::{DirectionalLight, Light};
+use crate::light_groups::LightGroup;
+use crate::material::Material;
+use crate::obb::Obb;
+use crate::portal::Portal;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::reflection_plane::ReflectionPlane;
+use crate::reflection_probes::ReflectionProbes;
+use crate::sky::{Aurora, SkyCache};
+use crate::sky_occlusion::SkyOcclusion;
+use crate::sun_visibility::SunVisibility;
+use crate::thrown::ThrownBlock;
+
+// The real sun's disc is about half a degree across.
+const SUN_ANGULAR_DIAMETER: f32 = 0.0093;
+const SKY_OCCLUSION_CELL: f32 = 0.25;
+const SUN_INTENSITY: f32 = 2.0;
+// Share of sunlight, skylight and daylight ambient a total eclipse takes away.
+const ECLIPSE_DIMMING: f32 = 0.9;
+const PROBE_SPACING: f32 = 8.0;
+const PROBE_RESOLUTION: usize = 16;
+
+// Everything a frame is rendered from except the camera and render settings: the objects,
+// the time of day, the sun and sky it puts overhead, and the lighting caches built from
+// them. The first object is the sun's marker cube, kept where the sun is.
+pub struct Scene {
+    pub objects: Vec<Object>,
+    pub day: DayCycle,
+    pub sun: DirectionalLight,
+    // Lamps lighting the scene besides the sun.
+    pub lights: Vec<Light>,
+    pub sky: SkyCache,
+    pub irradiance: IrradianceCache,
+    pub visibility: SunVisibility,
+    pub sky_occlusion: SkyOcclusion,
+    pub corners: CornerOcclusion,
+    pub probes: ReflectionProbes,
+    // How far an eclipse has covered the sun, 0 to 1.
+    pub eclipse: f32,
+    pub aurora: Aurora,
+    // Shown instead of the sky where rays miss the scene.
+    pub background: Option<Background>,
+    sun_material: Material,
+}
+
+impl Scene {
+    pub fn new(objects: Vec<Object>, day: DayCycle, sun_material: Material) -> Self {
+        let sun = DirectionalLight::new(day.sun_direction(), SUN_INTENSITY, SUN_ANGULAR_DIAMETER);
+        let mut scene = Scene {
+            sky: SkyCache::new(&sun.direction),
+            irradiance: IrradianceCache::new(0.25, 32, 1.0),
+            visibility: SunVisibility::new(&sun.direction),
+            sky_occlusion: SkyOcclusion::new(SKY_OCCLUSION_CELL),
+            corners: CornerOcclusion::bake(&solid_cells(&objects)),
+            probes: ReflectionProbes::new(PROBE_SPACING, PROBE_RESOLUTION),
+            objects,
+            day,
+            sun,
+            lights: Vec::new(),
+            eclipse: 0.0,
+            aurora: Aurora::default(),
+            background: None,
+            sun_material,
+        };
+        scene.probes.place(&scene.objects);
+        scene.follow_day();
+        scene
+    }
+
+    // Puts the sun where the time of day says and refreshes what depends on its direction.
+    pub fn follow_day(&mut self) {
+        self.objects[0] = Object::Cube(Cube {
+            center: self.day.sun_position(),
+            size: 1.0,
+            material: self.sun_material.clone(),
+        }, true);
+        self.sun.direction = self.day.sun_direction();
+        self.sun.intensity = SUN_INTENSITY * (1.0 - ECLIPSE_DIMMING * self.eclipse);
+        self.sky.update(&self.sun.direction);
+        self.irradiance.update(&self.sun.direction);
+        self.visibility.update(&self.sun.direction);
+        self.probes.update(&self.sun.direction);
+    }
+
+    // Turns the sun light and its marker cube without touching the sky or caches, e.g. for
+    // the in-between moments of a motion-blurred frame.
+    pub fn point_sun(&mut self, direction: Vec3) {
+        self.sun.direction = direction;
+        if let Object::Cube(cube, true) = &mut self.objects[0] {
+            cube.center = direction * cube.center.magnitude();
+        }
+    }
+
+    // Drops cached occlusion after geometry moved.
+    pub fn geometry_changed(&self) {
+        self.visibility.clear();
+        self.sky_occlusion.clear();
+        self.probes.clear();
+    }
+
+    // Drops everything cached from the objects' materials after blocks were repainted,
+    // including which ones light passes through.
+    pub fn materials_changed(&mut self) {
+        self.irradiance.clear();
+        self.geometry_changed();
+        self.corners = CornerOcclusion::bake(&solid_cells(&self.objects));
+        self.probes.place(&self.objects);
+    }
+
+    pub fn lighting(&self) -> Lighting<'_> {
+        Lighting {
+            sun: &self.sun,
+            lights: &self.lights,
+            sky: &self.sky,
+            irradiance: &self.irradiance,
+            visibility: &self.visibility,
+            sky_occlusion: &self.sky_occlusion,
+            corners: &self.corners,
+            moon_phase: self.day.moon_phase,
+            daylight: 1.0 - ECLIPSE_DIMMING * self.eclipse,
+            aurora: &self.aurora,
+            background: self.background.as_ref(),
+            reflections: None,
+            probes: self.probes.enabled.then_some(&self.probes),
+            group: None,
+        }
+    }
+}
+
+// Index of the first object, in list order, that blocks light arriving along `light_dir`.
+pub fn first_occluder(origin: &Vec3, light_dir: &Vec3, objects: &[Object]) -> Option<usize> {
+    objects.iter().position(|object| {
+        !matches!(object, Object::Cube(_, true) | Object::Portal(_) | Object::Wall(_)) && object.ray_intersect(origin, light_dir).is_intersecting
+    })
+}
+
+pub enum Object {
+    Cube(Cube, bool),
+    Portal(Portal),
+    Csg(Csg),
+    Billboard(Billboard),
+    Door(Door),
+    // See-through panel at the world border; it casts no shadow.
+    Wall(Obb),
+    Thrown(ThrownBlock),
+}
+
+impl Object {
+    // Moves the object so it is centred on `center`; false for objects that cannot be moved
+    // this way, like the sun's marker or doors, which swing about their hinge.
+    pub fn set_center(&mut self, center: Vec3) -> bool {
+        match self {
+            Object::Cube(cube, false) => cube.center = center,
+            Object::Billboard(billboard) => billboard.center = center,
+            Object::Wall(wall) => wall.center = center,
+            Object::Portal(portal) => portal.center = center,
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl RayIntersect for Object {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        match self {
+            Object::Cube(cube, _) => cube.ray_intersect(ray_origin, ray_direction),
+            Object::Portal(portal) => portal.ray_intersect(ray_origin, ray_direction),
+            Object::Csg(csg) => csg.ray_intersect(ray_origin, ray_direction),
+            Object::Billboard(billboard) => billboard.ray_intersect(ray_origin, ray_direction),
+            Object::Door(door) => door.ray_intersect(ray_origin, ray_direction),
+            Object::Wall(wall) => wall.ray_intersect(ray_origin, ray_direction),
+            Object::Thrown(block) => block.ray_intersect(ray_origin, ray_direction),
+        }
+    }
+}
+
+pub struct Lighting<'a> {
+    pub sun: &'a DirectionalLight,
+    pub lights: &'a [Light],
+    pub sky: &'a SkyCache,
+    pub irradiance: &'a IrradianceCache,
+    pub visibility: &'a SunVisibility,
+    pub sky_occlusion: &'a SkyOcclusion,
+    pub corners: &'a CornerOcclusion,
+    // 0 new moon, 0.5 full; sets how bright nights are and how the moon looks.
+    pub moon_phase: f32,
+    // Share of skylight and ambient light left by an eclipse.
+    pub daylight: f32,
+    pub aurora: &'a Aurora,
+    pub background: Option<&'a Background>,
+    // Stands in for reflection rays off flat water seen directly by the camera.
+    pub reflections: Option<&'a ReflectionPlane>,
+    // Stand in for the other reflection rays when set.
+    pub probes: Option<&'a ReflectionProbes>,
+    // Renders only this group's share of the light when set.
+    pub group: Option<LightGroup>,
+}
+
+impl Lighting<'_> {
+    pub fn includes(&self, group: LightGroup) -> bool {
+        self.group.is_none_or(|only| only == group)
+    }
+}
+
+pub fn closest_intersect<'a>(ray_origin: &Vec3, ray_direction: &Vec3, objects: &'a [Object]) -> (Intersect, Option<&'a Object>) {
+    let mut intersect = Intersect::empty();
+    let mut hit_object = None;
+    let mut zbuffer = f32::INFINITY;
+
+    for object in objects {
+        let i = object.ray_intersect(ray_origin, ray_direction);
+        if i.is_intersecting && i.distance < zbuffer {
+            zbuffer = i.distance;
+            intersect = i;
+            hit_object = Some(object);
+        }
+    }
+
+    (intersect, hit_object)
+}
+
+// Height of the surface most water blocks share, if any of them mirror their surroundings.
+pub fn water_level(objects: &[Object]) -> Option<f32> {
+    let mut surfaces: Vec<(f32, usize)> = Vec::new();
+    for object in objects {
+        let Object::Cube(cube, false) = object else { continue };
+        let albedo = cube.material.albedo;
+        if cube.material.depth_scale <= 0.0 || (albedo.reflectivity <= 0.0 && albedo.transparency <= 0.0) {
+            continue;
+        }
+        let top = cube.center.y + cube.size / 2.0;
+        match surfaces.iter_mut().find(|(height, _)| (height - top).abs() < 1e-3) {
+            Some((_, count)) => *count += 1,
+            None => surfaces.push((top, 1)),
+        }
+    }
+    surfaces.into_iter().max_by_key(|&(_, count)| count).map(|(height, _)| height)
+}
+
+// Whether nothing but the sun itself lies straight above `point`.
+fn sky_visible(point: &Vec3, objects: &[Object]) -> bool {
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    objects
+        .iter()
+        .filter(|object| !matches!(object, Object::Cube(_, true)))
+        .all(|object| !object.ray_intersect(point, &up).is_intersecting)
+}
+
+// Cells of the unit grid blocks that light cannot pass through.
+pub fn solid_cells(objects: &[Object]) -> HashSet<[i32; 3]> {
+    objects
+        .iter()
+        .filter_map(|object| match object {
+            Object::Cube(cube, false) if cube.material.albedo.is_opaque() => cube.grid_cell(),
+            _ => None,
+        })
+        .collect()
+}
+
+// Cubes whose top face sees the open sky, i.e. the ones that can collect snow.
+pub fn exposed_tops(objects: &[Object]) -> Vec<usize> {
+    objects
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| match object {
+            Object::Cube(cube, false) => {
+                let top = cube.center + Vec3::new(0.0, cube.size / 2.0 + 1e-3, 0.0);
+                sky_visible(&top, objects).then_some(index)
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,253 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use crate::cube::Cube;
+use crate::error::{Error, Result};
+use crate::nbt::{self, Tag};
+use crate::registry::{MaterialRegistry, is_air};
+
+const AIR: &str = "minecraft:air";
+// Litematics say how big they are rather than storing every block, so cap what one may ask
+// for: as much as 512 blocks along every side.
+const MAX_VOLUME: usize = 1 << 27;
+
+// A box of block names read from a Sponge .schem (v2/v3) or Litematica .litematic file.
+// Blocks are stored x-fastest, then z, then y.
+pub struct Schematic {
+    pub width: usize,
+    pub height: usize,
+    pub length: usize,
+    palette: Vec<String>,
+    blocks: Vec<usize>,
+}
+
+impl Schematic {
+    pub fn load(filename: &str) -> Result<Self> {
+        let bytes = std::fs::read(filename)
+            .map_err(|source| Error::ImportIo { path: filename.to_string(), source })?;
+        let import_error = |reason: String| Error::Import { path: filename.to_string(), reason };
+
+        let (_, root) = nbt::parse(&bytes).map_err(import_error)?;
+        let schematic = if root.get("Regions").is_some() {
+            Schematic::from_litematic(&root)
+        } else {
+            Schematic::from_sponge(root.get("Schematic").unwrap_or(&root))
+        }
+        .map_err(import_error)?;
+
+        log::info!(
+            "Loaded schematic {} ({}x{}x{}, {} block types)",
+            filename, schematic.width, schematic.height, schematic.length, schematic.palette.len(),
+        );
+        Ok(schematic)
+    }
+
+    fn from_sponge(schematic: &Tag) -> std::result::Result<Self, String> {
+        let dimension = |name: &str| {
+            schematic
+                .get(name)
+                .and_then(Tag::as_i64)
+                .map(|value| value as u16 as usize)
+                .ok_or_else(|| format!("missing {}", name))
+        };
+        let (width, height, length) = (dimension("Width")?, dimension("Height")?, dimension("Length")?);
+
+        // Version 3 nests the palette and data in a "Blocks" compound.
+        let container = schematic.get("Blocks").unwrap_or(schematic);
+        let Some(Tag::Compound(entries)) = container.get("Palette") else {
+            return Err(String::from("missing block palette"));
+        };
+        let mut palette = vec![String::new(); entries.len()];
+        for (name, id) in entries {
+            let id = id.as_i64().ok_or("palette entry is not a number")?;
+            let index = usize::try_from(id).ok().filter(|&index| index < palette.len());
+            let Some(index) = index else {
+                return Err(format!("palette id {} of {} is out of range", id, name));
+            };
+            palette[index] = name.clone();
+        }
+
+        let data = match container.get("BlockData").or_else(|| container.get("Data")) {
+            Some(Tag::ByteArray(data)) => data,
+            _ => return Err(String::from("missing block data")),
+        };
+
+        // Every block takes at least a byte, so data too short for the size is caught below
+        // without reserving room for blocks that are not there.
+        let volume = width * height * length;
+        let mut blocks = Vec::with_capacity(volume.min(data.len()));
+        let mut value = 0usize;
+        let mut shift = 0;
+        for &byte in data {
+            let byte = byte as u8;
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                if value >= palette.len() {
+                    return Err(format!("block id {} is not in the palette", value));
+                }
+                blocks.push(value);
+                value = 0;
+                shift = 0;
+            } else {
+                // Ids are varints of at most five bytes, like any int.
+                shift += 7;
+                if shift >= 35 {
+                    return Err(String::from("block id is longer than five bytes"));
+                }
+            }
+        }
+        if blocks.len() != volume {
+            return Err(format!("expected {} blocks, found {}", volume, blocks.len()));
+        }
+
+        Ok(Schematic { width, height, length, palette, blocks })
+    }
+
+    fn from_litematic(root: &Tag) -> std::result::Result<Self, String> {
+        let Some(Tag::Compound(regions)) = root.get("Regions") else {
+            return Err(String::from("missing regions"));
+        };
+
+        // Positions and sizes are ints in Minecraft; holding them to that keeps the sums below
+        // from overflowing.
+        let vector = |tag: Option<&Tag>| -> std::result::Result<[i64; 3], String> {
+            let tag = tag.ok_or("missing region position or size")?;
+            let axis = |name: &str| {
+                let value = tag.get(name).and_then(Tag::as_i64).ok_or(format!("missing {}", name))?;
+                i32::try_from(value).map(i64::from).map_err(|_| format!("{} {} is out of range", name, value))
+            };
+            Ok([axis("x")?, axis("y")?, axis("z")?])
+        };
+
+        // Negative sizes extend from the position towards -axis.
+        let mut boxes = Vec::new();
+        for region in regions.values() {
+            let position = vector(region.get("Position"))?;
+            let size = vector(region.get("Size"))?;
+            let min: [i64; 3] = std::array::from_fn(|i| if size[i] < 0 { position[i] + size[i] + 1 } else { position[i] });
+            let mut extent = [0; 3];
+            for (extent, size) in extent.iter_mut().zip(size) {
+                *extent = size.checked_abs().ok_or(format!("region size {} is out of range", size))?;
+            }
+            boxes.push((region, min, extent));
+        }
+
+        let low: [i64; 3] = std::array::from_fn(|i| boxes.iter().map(|(_, min, _)| min[i]).min().unwrap_or(0));
+        let high: [i64; 3] = std::array::from_fn(|i| boxes.iter().map(|(_, min, extent)| min[i] + extent[i]).max().unwrap_or(0));
+        let (width, height, length) = ((high[0] - low[0]) as usize, (high[1] - low[1]) as usize, (high[2] - low[2]) as usize);
+        let volume = width
+            .checked_mul(height)
+            .and_then(|area| area.checked_mul(length))
+            .filter(|&volume| volume <= MAX_VOLUME)
+            .ok_or(format!("{}x{}x{} blocks is too big to import", width, height, length))?;
+
+        let mut palette = vec![AIR.to_string()];
+        let mut palette_ids = HashMap::from([(AIR.to_string(), 0)]);
+        let mut blocks = vec![0; volume];
+
+        for (region, min, extent) in boxes {
+            let Some(Tag::List(states)) = region.get("BlockStatePalette") else {
+                return Err(String::from("region without a block palette"));
+            };
+            let Some(Tag::LongArray(packed)) = region.get("BlockStates") else {
+                return Err(String::from("region without block states"));
+            };
+
+            let local_ids: Vec<usize> = states
+                .iter()
+                .map(|state| {
+                    let name = state.get("Name").and_then(Tag::as_str).unwrap_or(AIR).to_string();
+                    *palette_ids.entry(name.clone()).or_insert_with(|| {
+                        palette.push(name);
+                        palette.len() - 1
+                    })
+                })
+                .collect();
+
+            let bits = (usize::BITS - (local_ids.len().max(2) - 1).leading_zeros()).max(2) as usize;
+            let [size_x, size_y, size_z] = extent.map(|value| value as usize);
+            for y in 0..size_y {
+                for z in 0..size_z {
+                    for x in 0..size_x {
+                        let index = (y * size_z + z) * size_x + x;
+                        let state = unpack(packed, index, bits).ok_or("block states are truncated")?;
+                        let id = *local_ids.get(state).ok_or("block state is not in the palette")?;
+                        let gx = (min[0] - low[0]) as usize + x;
+                        let gy = (min[1] - low[1]) as usize + y;
+                        let gz = (min[2] - low[2]) as usize + z;
+                        blocks[(gy * length + gz) * width + gx] = id;
+                    }
+                }
+            }
+        }
+
+        Ok(Schematic { width, height, length, palette, blocks })
+    }
+
+    pub fn block(&self, x: usize, y: usize, z: usize) -> &str {
+        &self.palette[self.blocks[(y * self.length + z) * self.width + x]]
+    }
+
+    // Unit cubes for every block with a registered material, with the schematic's minimum
+    // corner at `origin`. Blocks buried on all six sides by opaque neighbours are skipped.
+    pub fn to_cubes(&self, origin: Vec3, registry: &MaterialRegistry) -> Vec<Cube> {
+        let opaque = |x: i64, y: i64, z: i64| {
+            if x < 0 || y < 0 || z < 0 || x >= self.width as i64 || y >= self.height as i64 || z >= self.length as i64 {
+                return false;
+            }
+            registry
+                .get(self.block(x as usize, y as usize, z as usize))
+                .is_some_and(|material| material.albedo.is_opaque())
+        };
+
+        let mut cubes = Vec::new();
+        let mut unknown: HashMap<&str, usize> = HashMap::new();
+        for y in 0..self.height {
+            for z in 0..self.length {
+                for x in 0..self.width {
+                    let block = self.block(x, y, z);
+                    if is_air(block) {
+                        continue;
+                    }
+                    let Some(material) = registry.get(block) else {
+                        *unknown.entry(block).or_default() += 1;
+                        continue;
+                    };
+
+                    let (xi, yi, zi) = (x as i64, y as i64, z as i64);
+                    let neighbours = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+                    if neighbours.iter().all(|(dx, dy, dz)| opaque(xi + dx, yi + dy, zi + dz)) {
+                        continue;
+                    }
+
+                    cubes.push(Cube {
+                        center: origin + Vec3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5),
+                        size: 1.0,
+                        material: material.clone(),
+                    });
+                }
+            }
+        }
+
+        for (block, count) in unknown {
+            log::warn!("No material for {} ({} blocks skipped)", block, count);
+        }
+        cubes
+    }
+}
+
+// Litematica packs palette indices tightly, letting entries span two longs.
+fn unpack(packed: &[i64], index: usize, bits: usize) -> Option<usize> {
+    let mask = (1u64 << bits) - 1;
+    let start_bit = index * bits;
+    let start = start_bit / 64;
+    let offset = start_bit % 64;
+    let end = (start_bit + bits - 1) / 64;
+
+    let low = *packed.get(start)? as u64 >> offset;
+    let value = if start == end {
+        low
+    } else {
+        low | (*packed.get(end)? as u64) << (64 - offset)
+    };
+    Some((value & mask) as usize)
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::ScreenshotConfig;
+use crate::error::{Error, Result};
+use crate::framebuffer::Framebuffer;
+use crate::light::LightSwitches;
+use crate::light_groups::{LightGroup, LightLayers};
+use crate::projection::Lens;
+use crate::season::Weather;
+use crate::settings::RenderSettings;
+
+pub const SOFTWARE: &str = "Refractor";
+pub const METADATA_KEY: &str = "Refractor";
+
+// Everything needed to render the same frame again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderMetadata {
+    pub eye: [f32; 3],
+    pub center: [f32; 3],
+    pub up: [f32; 3],
+    pub fov_degrees: f32,
+    #[serde(default)]
+    pub lens: Lens,
+    #[serde(default)]
+    pub near: f32,
+    #[serde(default)]
+    pub far: Option<f32>,
+    pub time_of_day: f32,
+    #[serde(default = "full_moon")]
+    pub moon_phase: f32,
+    #[serde(default)]
+    pub eclipse: f32,
+    #[serde(default)]
+    pub aurora: f32,
+    #[serde(default)]
+    pub aurora_time: f32,
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub global_illumination: bool,
+    #[serde(default)]
+    pub sun: LightSwitches,
+    pub season_day: f32,
+    pub weather: Weather,
+    pub snow_depth: f32,
+    pub settings: RenderSettings,
+    // Sub-rectangle [x, y, width, height] of the frame when only part of it was rendered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<[usize; 4]>,
+    // Retro palette the image was cut down to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retro: Option<String>,
+}
+
+// Screenshots from before the moon had phases were lit as under a full moon.
+fn full_moon() -> f32 {
+    0.5
+}
+
+// Saves the frame as an 8-bit RGB PNG with the metadata as JSON in a tEXt chunk.
+pub fn save_png(filename: &str, framebuffer: &Framebuffer, metadata: &RenderMetadata) -> Result<()> {
+    let png_error = |source| Error::Png { path: filename.to_string(), source };
+    let file = File::create(filename).map_err(|source| Error::Export { path: filename.to_string(), source })?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), framebuffer.width as u32, framebuffer.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk(String::from("Software"), String::from(SOFTWARE)).map_err(png_error)?;
+    encoder.add_text_chunk(String::from(METADATA_KEY), metadata_json(metadata, false)).map_err(png_error)?;
+
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    let data: Vec<u8> = framebuffer
+        .buffer
+        .iter()
+        .flat_map(|&pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
+        .collect();
+    writer.write_image_data(&data).map_err(png_error)?;
+    writer.finish().map_err(png_error)
+}
+
+pub fn save_sidecar(png_filename: &str, metadata: &RenderMetadata) -> Result<String> {
+    let path = Path::new(png_filename).with_extension("json");
+    let filename = path.to_string_lossy().into_owned();
+    std::fs::write(&path, metadata_json(metadata, true))
+        .map_err(|source| Error::Export { path: filename.clone(), source })?;
+    Ok(filename)
+}
+
+// Writes each light group's layer next to a screenshot, e.g. `shot-sun.png`, unscaled.
+pub fn save_light_layers(png_filename: &str, layers: &LightLayers, metadata: &RenderMetadata) -> Result<Vec<String>> {
+    let stem = Path::new(png_filename).with_extension("");
+    LightGroup::ALL
+        .iter()
+        .map(|&group| {
+            let filename = format!("{}-{}.png", stem.to_string_lossy(), group.name());
+            save_png(&filename, layers.layer(group), metadata)?;
+            Ok(filename)
+        })
+        .collect()
+}
+
+pub fn metadata_json(metadata: &RenderMetadata, pretty: bool) -> String {
+    let json = if pretty { serde_json::to_string_pretty(metadata) } else { serde_json::to_string(metadata) };
+    json.expect("render metadata is plain data")
+}
+
+// Writes a timestamped screenshot into the configured directory and returns its path.
+pub fn take_screenshot(config: &ScreenshotConfig, framebuffer: &Framebuffer, metadata: &RenderMetadata) -> Result<String> {
+    std::fs::create_dir_all(&config.directory)
+        .map_err(|source| Error::Export { path: config.directory.clone(), source })?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis());
+    let filename = Path::new(&config.directory)
+        .join(format!("refractor-{}.png", stamp))
+        .to_string_lossy()
+        .into_owned();
+
+    save_png(&filename, framebuffer, metadata)?;
+    if config.sidecar_json {
+        save_sidecar(&filename, metadata)?;
+    }
+    Ok(filename)
+}
+
+// Reads the metadata back from a screenshot PNG, or from a sidecar .json file.
+pub fn read_metadata(filename: &str) -> Result<RenderMetadata> {
+    let import_error = |reason: String| Error::Import { path: filename.to_string(), reason };
+    let file = File::open(filename).map_err(|source| Error::ImportIo { path: filename.to_string(), source })?;
+
+    let json = if filename.ends_with(".json") {
+        std::io::read_to_string(file).map_err(|source| Error::ImportIo { path: filename.to_string(), source })?
+    } else {
+        let reader = png::Decoder::new(file).read_info().map_err(|err| import_error(err.to_string()))?;
+        reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == METADATA_KEY)
+            .map(|chunk| chunk.text.clone())
+            .ok_or_else(|| import_error(String::from("no render metadata in the image")))?
+    };
+
+    serde_json::from_str(&json).map_err(|err| import_error(err.to_string()))
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use crate::color::Color;
+use crate::config::{SeasonsConfig, WeatherBias, tint_color};
+use crate::rng::Rng;
+use crate::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Snow,
+}
+
+// The blended look of the current point in the year.
+#[derive(Debug, Clone)]
+pub struct SeasonState {
+    pub leaf_tint: Color,
+    pub grass_tint: Color,
+    pub snow_cover: bool,
+    pub day_length: f32,
+}
+
+// Counts days and walks through the configured seasons, cross-fading over `blend_days`
+// at the end of each one and rolling the weather once per day.
+pub struct SeasonClock {
+    config: SeasonsConfig,
+    days: f32,
+    weather: Weather,
+    rng: Rng,
+}
+
+impl SeasonClock {
+    pub fn new(config: SeasonsConfig, seed: u64) -> Self {
+        let mut clock = SeasonClock {
+            config,
+            days: 0.0,
+            weather: Weather::Clear,
+            rng: Rng::new(seed),
+        };
+        clock.weather = clock.roll_weather();
+        clock
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.list.is_empty()
+    }
+
+    pub fn days(&self) -> f32 {
+        self.days
+    }
+
+    pub fn weather(&self) -> Weather {
+        self.weather
+    }
+
+    // Overrides today's weather until the next day rolls it again.
+    pub fn set_weather(&mut self, weather: Weather) {
+        self.weather = weather;
+    }
+
+    // Returns true when a new day started and the weather was rolled again.
+    pub fn advance(&mut self, days: f32) -> bool {
+        let previous_day = self.days.floor();
+        self.days += days.max(0.0);
+        if self.days.floor() == previous_day || !self.is_enabled() {
+            return false;
+        }
+
+        let weather = self.roll_weather();
+        if weather != self.weather {
+            log::info!("Day {}: weather is now {:?}", self.days.floor(), weather);
+        }
+        self.weather = weather;
+        true
+    }
+
+    // Index of the current season and how far into the blend towards the next one we are.
+    fn position(&self) -> (usize, f32) {
+        let count = self.config.list.len();
+        let length = self.config.days_per_season.max(1e-3);
+        let year = self.days / length;
+        let index = year.floor() as usize % count;
+        let into_season = year.fract() * length;
+
+        let blend_days = self.config.blend_days.clamp(0.0, length);
+        let blend_start = length - blend_days;
+        let blend = if blend_days > 0.0 && into_season > blend_start {
+            (into_season - blend_start) / blend_days
+        } else {
+            0.0
+        };
+        (index, blend)
+    }
+
+    pub fn state(&self) -> Option<SeasonState> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let (index, blend) = self.position();
+        let current = &self.config.list[index];
+        let next = &self.config.list[(index + 1) % self.config.list.len()];
+
+        Some(SeasonState {
+            leaf_tint: tint_color(current.leaf_tint).lerp(tint_color(next.leaf_tint), blend),
+            grass_tint: tint_color(current.grass_tint).lerp(tint_color(next.grass_tint), blend),
+            snow_cover: if blend < 0.5 { current.snow_cover } else { next.snow_cover },
+            day_length: current.day_length + (next.day_length - current.day_length) * blend,
+        })
+    }
+
+    fn bias(&self) -> WeatherBias {
+        if !self.is_enabled() {
+            return WeatherBias::default();
+        }
+        let (index, blend) = self.position();
+        let current = self.config.list[index].weather;
+        let next = self.config.list[(index + 1) % self.config.list.len()].weather;
+        let mix = |a: f32, b: f32| a + (b - a) * blend;
+        WeatherBias {
+            clear: mix(current.clear, next.clear),
+            rain: mix(current.rain, next.rain),
+            snow: mix(current.snow, next.snow),
+        }
+    }
+
+    fn roll_weather(&mut self) -> Weather {
+        let bias = self.bias();
+        let total = bias.clear.max(0.0) + bias.rain.max(0.0) + bias.snow.max(0.0);
+        if total <= 0.0 {
+            return Weather::Clear;
+        }
+
+        let roll = self.rng.next_f32() * total;
+        if roll < bias.rain.max(0.0) {
+            Weather::Rain
+        } else if roll < bias.rain.max(0.0) + bias.snow.max(0.0) {
+            Weather::Snow
+        } else {
+            Weather::Clear
+        }
+    }
+}
+
+// Whitens a texture into its snowed-over version, keeping a hint of the original detail.
+pub fn snow_covered(texture: &Texture) -> Texture {
+    texture.map_pixels(|[r, g, b, a]| {
+        let luma = (r as f32 + g as f32 + b as f32) / (3.0 * 255.0);
+        let cover = 0.7 + 0.25 * luma;
+        let mix = |c: u8, snow: f32| (c as f32 + (snow - c as f32) * cover) as u8;
+        [mix(r, 236.0), mix(g, 242.0), mix(b, 250.0), a]
+    })
+}
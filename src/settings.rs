@@ -0,0 +1,67 @@
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::light::SunlightModel;
+
+// Offsets secondary ray origins off the surface to avoid self-intersection (acne)
+// without detaching shadows from their casters (peter-panning).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ShadowBias {
+    pub constant: f32,
+    pub distance_scale: f32,
+    pub slope_scale: f32,
+}
+
+impl ShadowBias {
+    pub fn amount(&self, hit_distance: f32, normal: &Vec3, direction: &Vec3) -> f32 {
+        let mut bias = self.constant + self.distance_scale * hit_distance;
+        if self.slope_scale > 0.0 {
+            let cos = normal.dot(direction).abs().clamp(1e-3, 1.0);
+            let tan = (1.0 - cos * cos).sqrt() / cos;
+            bias += self.slope_scale * tan.min(10.0);
+        }
+        bias
+    }
+}
+
+impl Default for ShadowBias {
+    fn default() -> Self {
+        ShadowBias {
+            constant: 1e-4,
+            distance_scale: 0.0,
+            slope_scale: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RenderSettings {
+    pub shadow_bias: ShadowBias,
+    pub sunlight: SunlightModel,
+    // Rays per surface cell measuring how much sky it sees, to shade ambient light; 0 keeps
+    // the ambient light the same everywhere.
+    #[serde(default)]
+    pub sky_samples: usize,
+    // Darken ambient light in block corners from the neighbouring blocks.
+    #[serde(default)]
+    pub corner_occlusion: bool,
+    // Blend texture mip levels by each hit's footprint; off, textures are sampled at full
+    // size and shimmer in the distance.
+    #[serde(default = "filtered")]
+    pub texture_filtering: bool,
+    // Dither when pixels are rounded to 8 bits, so smooth gradients like the sky do not band.
+    #[serde(default)]
+    pub dither: bool,
+    // Show primary rays shaded into NaN or infinity in magenta and log the first few (see
+    // `Watchdog`).
+    #[serde(default)]
+    pub nan_watchdog: bool,
+    // Split light refracting into dispersive materials into one ray per colour channel, for
+    // rainbow fringes. Light is split once, at the first such surface, so what lies behind
+    // it costs three times as much to trace.
+    #[serde(default)]
+    pub dispersion: bool,
+}
+
+fn filtered() -> bool {
+    true
+}
@@ -0,0 +1,186 @@
+use nalgebra_glm::Vec3;
+use crate::ray_intersect::Intersect;
+use crate::settings::ShadowBias;
+
+// The optics behind shading, as plain functions of vectors so they can be checked without a
+// scene or a window.
+
+// Where a secondary ray leaving `intersect` along `direction` starts: pushed off the surface
+// to the side it leaves towards, so it does not hit the surface it starts on.
+pub fn offset_origin(intersect: &Intersect, direction: &Vec3, bias: &ShadowBias) -> Vec3 {
+    let offset = intersect.normal * bias.amount(intersect.distance, &intersect.normal, direction);
+    if direction.dot(&intersect.normal) < 0.0 {
+        intersect.point - offset
+    } else {
+        intersect.point + offset
+    }
+}
+
+pub fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
+    incident - 2.0 * incident.dot(normal) * normal
+}
+
+// Snell's law; `None` on total internal reflection. Works from either side of the surface.
+pub fn refract(incident: &Vec3, normal: &Vec3, refractive_index: f32) -> Option<Vec3> {
+    let mut cos_i = incident.dot(normal).clamp(-1.0, 1.0);
+    let (eta, n) = if cos_i < 0.0 {
+        cos_i = -cos_i;
+        (1.0 / refractive_index, *normal)
+    } else {
+        (refractive_index, -normal)
+    };
+
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        None
+    } else {
+        Some((eta * incident + (eta * cos_i - k.sqrt()) * n).normalize())
+    }
+}
+
+// Share of the light a transparent surface reflects rather than lets through (Schlick's
+// approximation); 1 on total internal reflection.
+pub fn fresnel(incident: &Vec3, normal: &Vec3, refractive_index: f32) -> f32 {
+    let cos_i = incident.dot(normal).clamp(-1.0, 1.0);
+    let (eta_i, eta_t) = if cos_i > 0.0 { (refractive_index, 1.0) } else { (1.0, refractive_index) };
+
+    let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).max(0.0).sqrt();
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos = if eta_i > eta_t { (1.0 - sin_t * sin_t).sqrt() } else { cos_i.abs() };
+    schlick(((eta_i - eta_t) / (eta_i + eta_t)).powi(2), cos)
+}
+
+// Schlick's approximation: a surface reflecting `base` of the light that hits it head on
+// reflects more as `cos` (of the angle to the normal) falls, all of it at grazing angles.
+pub fn schlick(base: f32, cos: f32) -> f32 {
+    base + (1.0 - base) * (1.0 - cos.abs().min(1.0)).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use proptest::prelude::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn unit(azimuth: f32, elevation: f32) -> Vec3 {
+        Vec3::new(elevation.cos() * azimuth.cos(), elevation.sin(), elevation.cos() * azimuth.sin())
+    }
+
+    fn direction() -> impl Strategy<Value = Vec3> {
+        (0.0..std::f32::consts::TAU, -1.5f32..1.5).prop_map(|(azimuth, elevation)| unit(azimuth, elevation))
+    }
+
+    // A unit normal and a unit direction meeting it from the side it faces, not edge-on.
+    fn incoming() -> impl Strategy<Value = (Vec3, Vec3)> {
+        (direction(), direction())
+            .prop_filter("edge-on", |(normal, incident)| normal.dot(incident).abs() > 1e-3)
+            .prop_map(|(normal, incident)| if incident.dot(&normal) > 0.0 { (normal, -incident) } else { (normal, incident) })
+    }
+
+    fn sin_between(a: &Vec3, b: &Vec3) -> f32 {
+        a.cross(b).norm()
+    }
+
+    proptest! {
+        #[test]
+        fn reflection_keeps_the_angle_to_the_normal((normal, incident) in incoming()) {
+            let reflected = reflect(&incident, &normal);
+            prop_assert!((reflected.norm() - 1.0).abs() < EPSILON);
+            prop_assert!((reflected.dot(&normal) + incident.dot(&normal)).abs() < EPSILON);
+            // The part along the surface carries on unchanged.
+            let along = |v: &Vec3| v - v.dot(&normal) * normal;
+            prop_assert!((along(&reflected) - along(&incident)).norm() < EPSILON);
+        }
+
+        #[test]
+        fn reflecting_twice_gives_the_ray_back((normal, incident) in incoming()) {
+            let back = reflect(&reflect(&incident, &normal), &normal);
+            prop_assert!((back - incident).norm() < EPSILON);
+        }
+
+        #[test]
+        fn refraction_into_a_denser_medium_follows_snells_law((normal, incident) in incoming(), index in 1.0f32..2.5) {
+            let refracted = refract(&incident, &normal, index).expect("entering a denser medium always refracts");
+            prop_assert!((refracted.norm() - 1.0).abs() < EPSILON);
+            prop_assert!(refracted.dot(&normal) < 0.0, "the ray carries on through the surface");
+            prop_assert!(refracted.dot(&incident.cross(&normal)).abs() < EPSILON, "the ray stays in the plane of incidence");
+            let sin_i = sin_between(&incident, &normal);
+            prop_assert!((sin_between(&refracted, &normal) * index - sin_i).abs() < 1e-3);
+        }
+
+        #[test]
+        fn refracted_light_can_retrace_its_path((normal, incident) in incoming(), index in 1.0f32..2.5) {
+            let inside = refract(&incident, &normal, index).unwrap();
+            let back = refract(&-inside, &normal, index).expect("light sent back the way it came gets out again");
+            prop_assert!((back + incident).norm() < 1e-3);
+        }
+
+        #[test]
+        fn light_past_the_critical_angle_is_all_reflected((normal, incident) in incoming(), index in 1.05f32..2.5) {
+            // Leaving the denser medium, from the inside of the surface.
+            let leaving = -incident;
+            let critical = sin_between(&leaving, &normal) * index >= 1.0 + EPSILON;
+            prop_assume!(critical || sin_between(&leaving, &normal) * index < 1.0 - EPSILON);
+            prop_assert_eq!(refract(&leaving, &normal, index).is_none(), critical);
+            if critical {
+                prop_assert_eq!(fresnel(&leaving, &normal, index), 1.0);
+            }
+        }
+
+        #[test]
+        fn fresnel_never_passes_on_more_light_than_arrives(incident in direction(), normal in direction(), index in 1.0f32..3.0) {
+            let reflectance = fresnel(&incident, &normal, index);
+            prop_assert!((0.0..=1.0).contains(&reflectance));
+        }
+
+        #[test]
+        fn fresnel_grows_towards_grazing_angles(normal in direction(), a in 0.0f32..1.5, b in 0.0f32..1.5, index in 1.0f32..2.5) {
+            // Two rays in the same plane, `a` and `b` radians off the normal.
+            let side = normal.cross(&Vec3::new(0.3, 0.5, 0.8));
+            prop_assume!(side.norm() > 1e-3);
+            let tangent = side.normalize();
+            let at = |angle: f32| -(normal * angle.cos() + tangent * angle.sin());
+            let (near, far) = if a < b { (a, b) } else { (b, a) };
+            prop_assert!(fresnel(&at(near), &normal, index) <= fresnel(&at(far), &normal, index) + EPSILON);
+        }
+
+        #[test]
+        fn schlick_runs_from_the_base_head_on_to_everything_at_grazing(base in 0.0f32..1.0, a in 0.0f32..1.0, b in 0.0f32..1.0) {
+            prop_assert!((schlick(base, 1.0) - base).abs() < EPSILON);
+            prop_assert!((schlick(base, 0.0) - 1.0).abs() < EPSILON);
+            let (steep, shallow) = if a > b { (a, b) } else { (b, a) };
+            prop_assert!(base - EPSILON <= schlick(base, steep));
+            prop_assert!(schlick(base, steep) <= schlick(base, shallow) + EPSILON);
+            prop_assert!(schlick(base, shallow) <= 1.0 + EPSILON);
+        }
+
+        #[test]
+        fn offset_origins_leave_on_the_side_the_ray_goes(normal in direction(), outgoing in direction()) {
+            prop_assume!(outgoing.dot(&normal).abs() > 1e-3);
+            let point = Vec3::new(1.0, 2.0, 3.0);
+            let intersect = Intersect::new(point, normal, 5.0, Material::black(), None);
+            let bias = ShadowBias { constant: 1e-3, distance_scale: 1e-4, slope_scale: 1e-4 };
+            let origin = offset_origin(&intersect, &outgoing, &bias);
+            prop_assert!((origin - point).dot(&normal) * outgoing.dot(&normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn fresnel_at_normal_incidence_is_the_base_reflectance() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        // Glass reflects about 4% of light hitting it head on.
+        assert!((fresnel(&-normal, &normal, 1.5) - 0.04).abs() < EPSILON);
+    }
+
+    #[test]
+    fn an_index_of_one_does_not_bend_rays() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let incident = Vec3::new(0.6, -0.8, 0.0);
+        assert!((refract(&incident, &normal, 1.0).unwrap() - incident).norm() < EPSILON);
+    }
+}
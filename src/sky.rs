@@ -0,0 +1,240 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use crate::color::Color;
+use crate::config::AuroraConfig;
+
+const DAY_ZENITH_COLOR: Color = Color::new(68, 142, 228);
+const DAY_HORIZON_COLOR: Color = Color::new(172, 206, 240);
+const NIGHT_ZENITH_COLOR: Color = Color::new(10, 10, 30);
+const NIGHT_HORIZON_COLOR: Color = Color::new(28, 30, 58);
+const DUSK_COLOR: Color = Color::new(250, 140, 70);
+const SUN_GLOW_COLOR: Color = Color::new(255, 236, 190);
+const MOON_COLOR: Color = Color::new(225, 228, 215);
+// Drawn larger than life, so its phase reads at a glance.
+const MOON_ANGULAR_RADIUS: f32 = 0.06;
+// How much of the moon's dark side still shows against the sky.
+const EARTHSHINE: f32 = 0.06;
+// How far above its lowest elevation the aurora takes to reach full brightness, as a
+// change in the view direction's height.
+const AURORA_FADE: f32 = 0.15;
+// Where the curtain hangs on the sheet of sky it is drawn on, towards -z, and how thick it is.
+const AURORA_OFFSET: f32 = 0.9;
+const AURORA_WIDTH: f32 = 0.45;
+
+const CACHE_WIDTH: usize = 128;
+const CACHE_HEIGHT: usize = 64;
+const REFRESH_ANGLE: f32 = 0.01;
+
+pub fn sky_color(direction: &Vec3, sun_dir: &Vec3) -> Color {
+    let direction = direction.normalize();
+
+    let daylight = smoothstep(-0.15, 0.15, sun_dir.y);
+    let elevation = direction.y.max(0.0).sqrt();
+
+    let day = DAY_HORIZON_COLOR.lerp(DAY_ZENITH_COLOR, elevation);
+    let night = NIGHT_HORIZON_COLOR.lerp(NIGHT_ZENITH_COLOR, elevation);
+    let mut color = night.lerp(day, daylight);
+
+    let sun_alignment = direction.dot(sun_dir).max(0.0);
+    let dusk = (1.0 - sun_dir.y.abs() * 4.0).max(0.0) * (1.0 - elevation);
+    color = color.lerp(DUSK_COLOR, dusk * sun_alignment * 0.8);
+
+    let glow = sun_alignment.powf(64.0) * daylight;
+    color.lerp(SUN_GLOW_COLOR, glow)
+}
+
+// The moon, opposite the sun, over the sky colour `color` seen along `direction`. `phase`
+// picks the lit part (0 new, 0.5 full, waxing from its right side); it fades in as the sun
+// sets.
+pub fn moon_over(color: Color, direction: &Vec3, sun_dir: &Vec3, phase: f32) -> Color {
+    let night = 1.0 - smoothstep(-0.15, 0.15, sun_dir.y);
+    let moon = -sun_dir.normalize();
+    let direction = direction.normalize();
+    let cos_angle = direction.dot(&moon);
+    if night <= 0.0 || cos_angle < MOON_ANGULAR_RADIUS.cos() {
+        return color;
+    }
+
+    // Position on the disc, -1 to 1 across.
+    let helper = if moon.z.abs() < 0.9 { Vec3::new(0.0, 0.0, 1.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let right = moon.cross(&helper).normalize();
+    let up = right.cross(&moon);
+    let offset = (direction / cos_angle - moon) / MOON_ANGULAR_RADIUS.tan();
+    let (x, y) = (offset.dot(&right), offset.dot(&up));
+    if x * x + y * y > 1.0 {
+        return color;
+    }
+
+    let terminator = (2.0 * PI * phase).cos() * (1.0 - y * y).sqrt();
+    let lit = if phase < 0.5 { x > terminator } else { -x > terminator };
+    let disc = if lit { MOON_COLOR } else { color.lerp(MOON_COLOR, EARTHSHINE) };
+    color.lerp(disc, night)
+}
+
+// Curtains of light over the night sky, animated by `time`; `strength` fades them in and
+// out, 0 being none at all.
+#[derive(Clone)]
+pub struct Aurora {
+    pub strength: f32,
+    pub time: f32,
+    low_color: Color,
+    high_color: Color,
+    min_height: f32,
+    brightness: f32,
+    speed: f32,
+}
+
+impl Aurora {
+    pub fn new(config: &AuroraConfig) -> Self {
+        Aurora {
+            strength: 0.0,
+            time: 0.0,
+            low_color: Color::new(config.low_color[0], config.low_color[1], config.low_color[2]),
+            high_color: Color::new(config.high_color[0], config.high_color[1], config.high_color[2]),
+            min_height: config.min_elevation.clamp(1.0, 80.0).to_radians().sin(),
+            brightness: config.brightness.max(0.0),
+            speed: config.speed,
+        }
+    }
+
+    // The aurora over the sky colour `color` seen along `direction`; it fades in as the sun
+    // sets.
+    pub fn over(&self, color: Color, direction: &Vec3, sun_dir: &Vec3) -> Color {
+        let night = 1.0 - smoothstep(-0.15, 0.15, sun_dir.y);
+        let direction = direction.normalize();
+        let rise = smoothstep(self.min_height, self.min_height + AURORA_FADE, direction.y);
+        let glow = self.strength * self.brightness * night * rise;
+        if glow <= 0.0 {
+            return color;
+        }
+
+        // Where the view meets a flat sheet high above, so the curtain shrinks towards the
+        // horizon the way a real one does.
+        let (x, z) = (direction.x / direction.y, direction.z / direction.y);
+        let t = self.time * self.speed;
+        // The curtain winds across the sky and slowly changes its folds; rays run down it,
+        // drifting along.
+        let fold = (fbm(x * 0.35 + t * 0.04, t * 0.02) - 0.5) * 3.0;
+        let across = (z + AURORA_OFFSET + fold) / AURORA_WIDTH;
+        let band = (-across * across).exp();
+        let streak = value_noise(x * 8.0 + fold * 3.0 - t * 0.3, t * 0.15);
+        let rays = 0.15 + 1.2 * streak * streak;
+
+        let tint = self.low_color.lerp(self.high_color, smoothstep(0.5, 1.0, direction.y));
+        color + tint * (band * rays * glow)
+    }
+}
+
+impl Default for Aurora {
+    fn default() -> Self {
+        Aurora::new(&AuroraConfig::default())
+    }
+}
+
+// Smooth noise from 0 to 1 with features about a unit apart.
+fn value_noise(x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (smoothstep(0.0, 1.0, x - x0), smoothstep(0.0, 1.0, y - y0));
+    let (x0, y0) = (x0 as i32, y0 as i32);
+    let top = lerp(lattice(x0, y0), lattice(x0 + 1, y0), fx);
+    let bottom = lerp(lattice(x0, y0 + 1), lattice(x0 + 1, y0 + 1), fx);
+    lerp(top, bottom, fy)
+}
+
+// Value noise with two finer octaves on top.
+fn fbm(x: f32, y: f32) -> f32 {
+    (value_noise(x, y) + 0.5 * value_noise(2.0 * x, 2.0 * y) + 0.25 * value_noise(4.0 * x, 4.0 * y)) / 1.75
+}
+
+fn lattice(x: i32, y: i32) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841);
+    hash = (hash ^ (hash >> 13)).wrapping_mul(0x5bd1_e995);
+    (hash ^ (hash >> 15)) as f32 / u32::MAX as f32
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Low resolution lat/long image of the sky, rebuilt only when the sun has moved enough.
+pub struct SkyCache {
+    pixels: Vec<Color>,
+    sun_direction: Vec3,
+}
+
+impl SkyCache {
+    pub fn new(sun_direction: &Vec3) -> Self {
+        let mut cache = SkyCache {
+            pixels: vec![Color::black(); CACHE_WIDTH * CACHE_HEIGHT],
+            sun_direction: *sun_direction,
+        };
+        cache.rebuild(sun_direction);
+        cache
+    }
+
+    pub fn update(&mut self, sun_direction: &Vec3) {
+        if sun_direction.dot(&self.sun_direction) < REFRESH_ANGLE.cos() {
+            self.rebuild(sun_direction);
+        }
+    }
+
+    fn rebuild(&mut self, sun_direction: &Vec3) {
+        for y in 0..CACHE_HEIGHT {
+            for x in 0..CACHE_WIDTH {
+                let direction = texel_direction(x as f32 + 0.5, y as f32 + 0.5);
+                self.pixels[y * CACHE_WIDTH + x] = sky_color(&direction, sun_direction);
+            }
+        }
+        self.sun_direction = *sun_direction;
+        log::trace!("Rebuilt sky cache for sun direction {:?}", self.sun_direction);
+    }
+
+    pub fn sample(&self, direction: &Vec3) -> Color {
+        let ([x0, x1, y0, y1], fx, fy) = self.footprint(direction);
+        let top = self.texel(x0, y0).lerp(self.texel(x1, y0), fx);
+        let bottom = self.texel(x0, y1).lerp(self.texel(x1, y1), fx);
+        top.lerp(bottom, fy)
+    }
+
+    // Like `sample`, without rounding the blend between texels to whole levels.
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let ([x0, x1, y0, y1], fx, fy) = self.footprint(direction);
+        let texel = |x, y| self.texel(x, y).to_vec3();
+        let top = texel(x0, y0).lerp(&texel(x1, y0), fx);
+        let bottom = texel(x0, y1).lerp(&texel(x1, y1), fx);
+        top.lerp(&bottom, fy)
+    }
+
+    // The texel columns and rows either side of `direction`, and how far it lies between them.
+    fn footprint(&self, direction: &Vec3) -> ([usize; 4], f32, f32) {
+        let direction = direction.normalize();
+        let u = (direction.z.atan2(direction.x) / (2.0 * PI) + 0.5) * CACHE_WIDTH as f32 - 0.5;
+        let v = (direction.y.clamp(-1.0, 1.0).acos() / PI) * CACHE_HEIGHT as f32 - 0.5;
+
+        let x0 = u.floor();
+        let y0 = v.floor().clamp(0.0, (CACHE_HEIGHT - 1) as f32);
+        let fx = u - x0;
+        let fy = (v - y0).clamp(0.0, 1.0);
+
+        let x0 = (x0 as i32).rem_euclid(CACHE_WIDTH as i32) as usize;
+        let x1 = (x0 + 1) % CACHE_WIDTH;
+        let y0 = y0 as usize;
+        let y1 = (y0 + 1).min(CACHE_HEIGHT - 1);
+        ([x0, x1, y0, y1], fx, fy)
+    }
+
+    fn texel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * CACHE_WIDTH + x]
+    }
+}
+
+fn texel_direction(x: f32, y: f32) -> Vec3 {
+    let phi = (x / CACHE_WIDTH as f32 - 0.5) * 2.0 * PI;
+    let theta = y / CACHE_HEIGHT as f32 * PI;
+    Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+}
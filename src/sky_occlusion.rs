@@ -0,0 +1,39 @@
+use nalgebra_glm::Vec3;
+use std::sync::RwLock;
+use std::collections::HashMap;
+use crate::irradiance::{CacheKey, cache_key};
+
+// How much of the sky each surface sees, from 0 (none) to 1 (an open hemisphere), cached per
+// small cell and face orientation. It depends only on geometry, so unlike the irradiance
+// cache it survives the sun moving and is flushed only when blocks do.
+pub struct SkyOcclusion {
+    pub cell_size: f32,
+    entries: RwLock<HashMap<CacheKey, f32>>,
+}
+
+impl SkyOcclusion {
+    pub fn new(cell_size: f32) -> Self {
+        SkyOcclusion {
+            cell_size,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+        if !entries.is_empty() {
+            log::trace!("Flushing sky occlusion of {} cells", entries.len());
+        }
+        entries.clear();
+    }
+
+    pub fn lookup(&self, point: &Vec3, normal: &Vec3, compute: impl FnOnce() -> f32) -> f32 {
+        let key = cache_key(point, normal, self.cell_size);
+        if let Some(openness) = self.entries.read().unwrap().get(&key) {
+            return *openness;
+        }
+        let openness = compute();
+        self.entries.write().unwrap().insert(key, openness);
+        openness
+    }
+}
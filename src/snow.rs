@@ -0,0 +1,51 @@
+use crate::color::Color;
+use crate::daycycle::NOON;
+use crate::season::Weather;
+
+pub const SNOW_COLOR: Color = Color::new(240, 244, 250);
+const MELT_WINDOW_HOURS: f32 = 2.0;
+
+// Snow depth shared by every exposed top face, from 0 (bare) to 1 (fully white).
+pub struct SnowCover {
+    pub depth: f32,
+    pub accumulation_time: f32,
+    pub melt_time: f32,
+    exposed: Vec<usize>,
+}
+
+impl SnowCover {
+    pub fn new(exposed: Vec<usize>) -> Self {
+        log::debug!("{} top faces can collect snow", exposed.len());
+        SnowCover {
+            depth: 0.0,
+            accumulation_time: 30.0,
+            melt_time: 8.0,
+            exposed,
+        }
+    }
+
+    // Object indices of the cubes with open sky above them.
+    pub fn exposed(&self) -> &[usize] {
+        &self.exposed
+    }
+
+    pub fn set_exposed(&mut self, exposed: Vec<usize>) {
+        self.exposed = exposed;
+    }
+
+    pub fn is_melting(hours: f32) -> bool {
+        (hours - NOON).abs() < MELT_WINDOW_HOURS
+    }
+
+    // Returns true when the depth changed and materials need refreshing.
+    pub fn update(&mut self, dt: f32, weather: Weather, hours: f32) -> bool {
+        let previous = self.depth;
+        if SnowCover::is_melting(hours) {
+            self.depth -= dt / self.melt_time.max(1e-3);
+        } else if weather == Weather::Snow {
+            self.depth += dt / self.accumulation_time.max(1e-3);
+        }
+        self.depth = self.depth.clamp(0.0, 1.0);
+        self.depth != previous
+    }
+}
@@ -0,0 +1,51 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::material::Material;
+
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl Sphere {
+    // Entry and exit distances along the ray, which may be negative.
+    pub fn hit_distances(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<(f32, f32)> {
+        let oc = ray_origin - self.center;
+        let a = ray_direction.dot(ray_direction);
+        let b = 2.0 * oc.dot(ray_direction);
+        let c = oc.dot(&oc) - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        Some(((-b - root) / (2.0 * a), (-b + root) / (2.0 * a)))
+    }
+
+    pub fn get_uv(&self, normal: &Vec3) -> (f32, f32) {
+        let u = 0.5 + normal.z.atan2(normal.x) / (2.0 * PI);
+        let v = 0.5 + normal.y.clamp(-1.0, 1.0).asin() / PI;
+        (u, v)
+    }
+}
+
+impl RayIntersect for Sphere {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let Some((t_near, t_far)) = self.hit_distances(ray_origin, ray_direction) else {
+            return Intersect::empty();
+        };
+        let distance = if t_near >= 0.0 { t_near } else { t_far };
+        if distance < 0.0 {
+            return Intersect::empty();
+        }
+
+        let point = ray_origin + ray_direction * distance;
+        let normal = (point - self.center).normalize();
+        let uv = self.get_uv(&normal);
+        let mut intersect = Intersect::new(point, normal, distance, self.material.clone(), Some(uv));
+        intersect.uv_size = self.radius * 2.0;
+        intersect
+    }
+}
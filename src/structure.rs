@@ -0,0 +1,235 @@
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::cube::Cube;
+use crate::error::{Error, Result};
+use crate::history::Edit;
+use crate::palette::Palette;
+use crate::registry::MaterialRegistry;
+use crate::scene::Object;
+
+// Two opposite corners of a box of blocks, marked one after the other.
+#[derive(Default)]
+pub struct Selection {
+    corners: [Option<Vec3>; 2],
+    next: usize,
+}
+
+impl Selection {
+    // Sets the corner that was not set last to the centre of a block.
+    pub fn mark(&mut self, center: Vec3) {
+        self.corners[self.next] = Some(center);
+        self.next = 1 - self.next;
+    }
+
+    pub fn corners(&self) -> Option<(Vec3, Vec3)> {
+        match self.corners {
+            [Some(a), Some(b)] => Some((a, b)),
+            _ => None,
+        }
+    }
+}
+
+// Blocks lifted out of the scene to be placed again elsewhere, positioned relative to the
+// lowest block centre on each axis.
+#[derive(Clone, Default)]
+pub struct Structure {
+    pub blocks: Vec<Cube>,
+}
+
+impl Structure {
+    // The blocks with their centres in the box spanned by `a` and `b`, and their indices.
+    pub fn capture(objects: &[Object], a: &Vec3, b: &Vec3) -> (Structure, Vec<usize>) {
+        let (min, max) = (a.inf(b), a.sup(b));
+        let (indices, blocks) = objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| match object {
+                Object::Cube(cube, false) if (0..3).all(|axis| (min[axis] - 1e-3..=max[axis] + 1e-3).contains(&cube.center[axis])) => {
+                    Some((index, cube.clone()))
+                }
+                _ => None,
+            })
+            .unzip();
+        (Structure::anchored(blocks), indices)
+    }
+
+    // Moves the blocks so the lowest centre on each axis is zero.
+    fn anchored(mut blocks: Vec<Cube>) -> Structure {
+        let min = blocks.iter().fold(Vec3::repeat(f32::INFINITY), |min, cube| min.inf(&cube.center));
+        for cube in &mut blocks {
+            cube.center -= min;
+        }
+        Structure { blocks }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    // The middle block of the lowest layer, which the structure stands on the ground by.
+    pub fn base(&self) -> Vec3 {
+        let bottom: Vec<&Cube> = self.blocks.iter().filter(|cube| cube.center.y < 0.5).collect();
+        let sum = bottom.iter().fold(Vec3::zeros(), |sum, cube| sum + cube.center);
+        let middle = sum / bottom.len().max(1) as f32;
+        Vec3::new(middle.x.round(), 0.0, middle.z.round())
+    }
+
+    // The same blocks turned a quarter turn about the vertical axis.
+    pub fn rotated(&self) -> Structure {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|cube| Cube { center: Vec3::new(cube.center.z, cube.center.y, -cube.center.x), ..cube.clone() })
+            .collect();
+        Structure::anchored(blocks)
+    }
+
+    // Taking the captured blocks out, last index first so the others stay where they are.
+    pub fn removal(objects: &[Object], indices: &[usize]) -> Vec<Edit> {
+        indices
+            .iter()
+            .rev()
+            .filter_map(|&index| match &objects[index] {
+                Object::Cube(cube, false) => Some(Edit::Remove { index, cube: cube.clone() }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // The blocks with the structure's anchor at `origin`, leaving out the ones that would go
+    // where a block already is.
+    pub fn cubes_at(&self, objects: &[Object], origin: &Vec3) -> Vec<Cube> {
+        let occupied = |center: &Vec3| objects.iter().any(|object| matches!(object, Object::Cube(cube, false) if cube.contains(center, -1e-3)));
+        self.blocks
+            .iter()
+            .map(|cube| Cube { center: origin + cube.center, ..cube.clone() })
+            .filter(|cube| !occupied(&cube.center))
+            .collect()
+    }
+
+    // Placing `cubes_at(objects, origin)` as edits.
+    pub fn placement(&self, objects: &[Object], origin: &Vec3) -> Vec<Edit> {
+        self.cubes_at(objects, origin)
+            .into_iter()
+            .enumerate()
+            .map(|(offset, cube)| Edit::Place { index: objects.len() + offset, cube })
+            .collect()
+    }
+
+    // Reads a structure file written by `save` (or by hand), looking block names up in
+    // `registry`. Blocks with names it does not know are left out with a warning.
+    pub fn load(filename: &str, registry: &MaterialRegistry) -> Result<Structure> {
+        let text = std::fs::read_to_string(filename)
+            .map_err(|source| Error::ImportIo { path: filename.to_string(), source })?;
+        let file: StructureFile = toml::from_str(&text)
+            .map_err(|err| Error::Import { path: filename.to_string(), reason: err.to_string() })?;
+
+        let mut unknown = Vec::new();
+        let blocks = file
+            .blocks
+            .into_iter()
+            .filter_map(|block| match registry.get(&block.name) {
+                Some(material) => {
+                    let center = Vec3::new(block.at[0] as f32, block.at[1] as f32, block.at[2] as f32);
+                    Some(Cube { center, size: 1.0, material: material.clone() })
+                }
+                None => {
+                    if !unknown.contains(&block.name) {
+                        unknown.push(block.name);
+                    }
+                    None
+                }
+            })
+            .collect();
+        if !unknown.is_empty() {
+            log::warn!("Left blocks of unknown kinds out of {}: {}", filename, unknown.join(", "));
+        }
+        Ok(Structure::anchored(blocks))
+    }
+
+    // Writes the blocks as a TOML list of grid positions and palette block names. Blocks
+    // whose material is not a palette block are left out; returns how many were written.
+    pub fn save(&self, filename: &str, palette: &Palette) -> Result<usize> {
+        let blocks: Vec<StructureBlock> = self
+            .blocks
+            .iter()
+            .filter_map(|cube| {
+                let name = palette.name_of(&cube.material)?;
+                let at = cube.center.map(|c| c.round() as i32);
+                Some(StructureBlock { at: [at.x, at.y, at.z], name: name.to_string() })
+            })
+            .collect();
+        let written = blocks.len();
+        if written < self.blocks.len() {
+            log::warn!("Left {} blocks without a palette material out of {}", self.blocks.len() - blocks.len(), filename);
+        }
+        let text = toml::to_string(&StructureFile { blocks }).expect("structures are plain data");
+        std::fs::write(filename, text).map_err(|source| Error::Export { path: filename.to_string(), source })?;
+        Ok(written)
+    }
+}
+
+// Prefabs read from a directory of structure files, named after the files without their
+// extension, so scenes and the editor can place trees or rocks by name.
+#[derive(Default)]
+pub struct StructureLibrary {
+    structures: Vec<(String, Structure)>,
+}
+
+impl StructureLibrary {
+    // Every `.toml` file in `directory`, in name order. A missing directory is an empty
+    // library, and files that do not load are skipped with a warning.
+    pub fn load(directory: &str, registry: &MaterialRegistry) -> Self {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            log::debug!("No structures at {}", directory);
+            return StructureLibrary::default();
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .collect();
+        paths.sort();
+
+        let mut structures = Vec::new();
+        for path in paths {
+            let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            match Structure::load(&path.to_string_lossy(), registry) {
+                Ok(structure) if !structure.is_empty() => structures.push((name, structure)),
+                Ok(_) => log::warn!("Structure {} has no blocks", path.display()),
+                Err(err) => log::warn!("{}", err),
+            }
+        }
+        log::info!("Loaded {} structures from {}", structures.len(), directory);
+        StructureLibrary { structures }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Structure> {
+        self.structures.iter().find(|(entry, _)| entry == name).map(|(_, structure)| structure)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.structures.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.structures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.structures.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructureFile {
+    #[serde(default, rename = "block")]
+    blocks: Vec<StructureBlock>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructureBlock {
+    at: [i32; 3],
+    name: String,
+}
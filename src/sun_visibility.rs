@@ -0,0 +1,71 @@
+use nalgebra_glm::Vec3;
+use std::sync::RwLock;
+use std::collections::HashMap;
+
+// Width of a sun direction bucket; the cache is rebuilt lazily once the sun leaves it.
+const BUCKET_ANGLE: f32 = 0.02;
+const PROBE_OFFSETS: [f32; 3] = [-0.45, 0.0, 0.45];
+
+// What lies between a top face and the sun.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SunOcclusion {
+    Clear,
+    // Every probe is first blocked by the object at this index.
+    Blocked(usize),
+    // Probes disagree, so the face needs a full shadow ray per hit.
+    Mixed,
+}
+
+// Sun occlusion of the top faces of unit grid cubes, which make up most of the terrain.
+// Each face is probed on a small grid the first time it is shaded for a sun bucket.
+pub struct SunVisibility {
+    faces: RwLock<HashMap<[i32; 3], SunOcclusion>>,
+    sun_direction: Vec3,
+}
+
+impl SunVisibility {
+    pub fn new(sun_direction: &Vec3) -> Self {
+        SunVisibility {
+            faces: RwLock::new(HashMap::new()),
+            sun_direction: *sun_direction,
+        }
+    }
+
+    pub fn update(&mut self, sun_direction: &Vec3) {
+        if sun_direction.dot(&self.sun_direction) < BUCKET_ANGLE.cos() {
+            self.clear();
+            self.sun_direction = *sun_direction;
+        }
+    }
+
+    // Needed whenever geometry moves, since cached occluders may no longer be in the way.
+    pub fn clear(&self) {
+        let mut faces = self.faces.write().unwrap();
+        if !faces.is_empty() {
+            log::trace!("Flushing sun visibility of {} top faces", faces.len());
+        }
+        faces.clear();
+    }
+
+    // `occluder` returns the first object blocking the sun from a point just above the face.
+    pub fn top_face(&self, cell: [i32; 3], occluder: impl Fn(&Vec3) -> Option<usize>) -> SunOcclusion {
+        if let Some(occlusion) = self.faces.read().unwrap().get(&cell) {
+            return *occlusion;
+        }
+
+        let top = Vec3::new(cell[0] as f32, cell[1] as f32 + 0.5 + 1e-3, cell[2] as f32);
+        let mut probes = PROBE_OFFSETS
+            .iter()
+            .flat_map(|&dx| PROBE_OFFSETS.iter().map(move |&dz| top + Vec3::new(dx, 0.0, dz)))
+            .map(|point| occluder(&point));
+        let first = probes.next().flatten();
+        let occlusion = if !probes.all(|blocker| blocker == first) {
+            SunOcclusion::Mixed
+        } else {
+            first.map_or(SunOcclusion::Clear, SunOcclusion::Blocked)
+        };
+
+        self.faces.write().unwrap().insert(cell, occlusion);
+        occlusion
+    }
+}
@@ -0,0 +1,127 @@
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{self, Color as TerminalColor};
+use crossterm::{cursor, queue, terminal};
+use log::LevelFilter;
+use std::f32::consts::PI;
+use std::io::{Stdout, Write};
+use std::time::{Duration, Instant};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::display::Presenter;
+use crate::error::{Error, Result};
+use crate::framebuffer::Framebuffer;
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+
+const FRAME_TIME: Duration = Duration::from_millis(50);
+const ORBIT_STEP: f32 = 0.1;
+// Upper half block: the character cell's top pixel is the foreground colour, its bottom one
+// the background.
+const HALF_BLOCK: char = '\u{2580}';
+
+// Shows frames in the terminal the app was started from, two pixels to a character cell in
+// 24-bit colour, so the diorama can be watched over SSH. The log is silenced while it is up,
+// as it would scribble over the picture.
+pub struct TerminalPresenter {
+    out: Stdout,
+    log_level: LevelFilter,
+}
+
+impl TerminalPresenter {
+    pub fn new() -> Result<Self> {
+        let mut out = std::io::stdout();
+        terminal::enable_raw_mode().map_err(Error::Terminal)?;
+        crossterm::execute!(out, terminal::EnterAlternateScreen, cursor::Hide).map_err(Error::Terminal)?;
+        let log_level = log::max_level();
+        log::set_max_level(LevelFilter::Off);
+        Ok(TerminalPresenter { out, log_level })
+    }
+
+    // A key pressed since the last call, if any.
+    pub fn poll_key(&self) -> Result<Option<KeyEvent>> {
+        while event::poll(Duration::ZERO).map_err(Error::Terminal)? {
+            if let Event::Key(key) = event::read().map_err(Error::Terminal)? {
+                if key.kind == KeyEventKind::Press {
+                    return Ok(Some(key));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Presenter for TerminalPresenter {
+    fn render_size(&self) -> (usize, usize) {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        (columns.max(1) as usize, rows.max(1) as usize * 2)
+    }
+
+    fn present(&mut self, frame: &Framebuffer) -> Result<()> {
+        let rgb = |hex: u32| {
+            let color = Color::from_hex(hex).to_vec3();
+            TerminalColor::Rgb { r: color.x as u8, g: color.y as u8, b: color.z as u8 }
+        };
+        let mut colors = None;
+        for row in 0..frame.height.div_ceil(2) {
+            queue!(self.out, cursor::MoveTo(0, row as u16)).map_err(Error::Terminal)?;
+            for x in 0..frame.width {
+                let top = frame.get_pixel(x, row * 2).unwrap_or(0);
+                let bottom = frame.get_pixel(x, row * 2 + 1).unwrap_or(0);
+                // Colours are only sent when they change, which keeps frames small enough
+                // for a slow connection.
+                if colors != Some((top, bottom)) {
+                    queue!(self.out, style::SetColors(style::Colors::new(rgb(top), rgb(bottom)))).map_err(Error::Terminal)?;
+                    colors = Some((top, bottom));
+                }
+                queue!(self.out, style::Print(HALF_BLOCK)).map_err(Error::Terminal)?;
+            }
+        }
+        queue!(self.out, style::ResetColor).map_err(Error::Terminal)?;
+        self.out.flush().map_err(Error::Terminal)
+    }
+}
+
+impl Drop for TerminalPresenter {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(self.out, style::ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+        log::set_max_level(self.log_level);
+    }
+}
+
+// Plays the day in the terminal until q, Escape or Ctrl+C. The arrows orbit the camera and
+// + and - move it in and out; a whole day takes `day_length` seconds.
+pub fn run(presenter: &mut TerminalPresenter, renderer: &Renderer, scene: &mut Scene, camera: &mut Camera, day_length: f32) -> Result<()> {
+    let mut framebuffer = Framebuffer::new(1, 1);
+    let mut last_frame = Instant::now();
+    loop {
+        while let Some(key) = presenter.poll_key()? {
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Left => camera.orbit(ORBIT_STEP, 0.0),
+                KeyCode::Right => camera.orbit(-ORBIT_STEP, 0.0),
+                KeyCode::Up => camera.orbit(0.0, -ORBIT_STEP),
+                KeyCode::Down => camera.orbit(0.0, ORBIT_STEP),
+                KeyCode::Char('+') | KeyCode::Char('=') => camera.move_camera("forward"),
+                KeyCode::Char('-') => camera.move_camera("backward"),
+                _ => {}
+            }
+        }
+
+        let dt = last_frame.elapsed().as_secs_f32();
+        last_frame = Instant::now();
+        scene.day.update(dt, 2.0 * PI * dt / day_length);
+        scene.aurora.time += dt;
+        scene.follow_day();
+
+        let (width, height) = presenter.render_size();
+        if (framebuffer.width, framebuffer.height) != (width, height) {
+            framebuffer = Framebuffer::new(width, height);
+            camera.set_viewport(width, height);
+        }
+        renderer.render(&mut framebuffer, scene, camera);
+        presenter.present(&framebuffer)?;
+        std::thread::sleep(FRAME_TIME.saturating_sub(last_frame.elapsed()));
+    }
+}
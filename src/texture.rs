@@ -1,33 +1,437 @@
 // texture.rs
+use image::imageops::FilterType;
 use image::{DynamicImage, GenericImageView};
+use std::ops::Deref;
 use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant, SystemTime};
+use crate::error::{Error, Result};
 
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+// The pixels sit behind a RwLock so a reload can swap them in place: every material
+// sharing the Arc sees the new image without being touched.
 #[derive(Debug)] 
 pub struct Texture {
-    pub image: DynamicImage,
-    pub width: u32,
-    pub height: u32,
+    pixels: RwLock<Pixels>,
+    source: Option<String>,
+    modified: Mutex<Option<SystemTime>>,
+    // Longest side the image is shrunk to on load, to stay within a memory budget; 0 keeps
+    // it as it is.
+    max_side: AtomicU32,
+    // Further reduction for quick previews, undone by reloading the file.
+    preview: Mutex<Reduction>,
+}
+
+// How far a texture is cut down when loaded: the longest side in pixels and the colours in
+// its palette, each 0 to keep the file's.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Reduction {
+    pub max_side: u32,
+    pub colors: usize,
+}
+
+#[derive(Debug)]
+struct Pixels {
+    image: DynamicImage,
+    width: u32,
+    height: u32,
+    mips: Vec<MipLevel>,
+}
+
+impl Pixels {
+    fn new(image: DynamicImage) -> Self {
+        let (width, height) = image.dimensions();
+        let mips = build_mips(&image);
+        Pixels { image, width, height, mips }
+    }
+
+    fn reduced(image: DynamicImage, reduction: Reduction) -> Self {
+        let (width, height) = image.dimensions();
+        let max_side = reduction.max_side;
+        let image = if max_side == 0 || width.max(height) <= max_side {
+            image
+        } else {
+            let scale = max_side as f32 / width.max(height) as f32;
+            let (width, height) = (((width as f32 * scale) as u32).max(1), ((height as f32 * scale) as u32).max(1));
+            image.resize_exact(width, height, FilterType::Triangle)
+        };
+        match reduction.colors {
+            0 => Pixels::new(image),
+            colors => Pixels::new(quantize(image, colors)),
+        }
+    }
+
+    // Bytes held by the image and its mip chain.
+    fn memory(&self) -> usize {
+        self.image.as_bytes().len() + self.mips.iter().map(|level| level.pixels.len() * 3).sum::<usize>()
+    }
+}
+
+struct ImageGuard<'a>(RwLockReadGuard<'a, Pixels>);
+
+impl Deref for ImageGuard<'_> {
+    type Target = DynamicImage;
+
+    fn deref(&self) -> &DynamicImage {
+        &self.0.image
+    }
+}
+
+#[derive(Debug)]
+struct MipLevel {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl MipLevel {
+    fn from_image(image: &DynamicImage) -> Self {
+        let rgb = image.to_rgb8();
+        MipLevel {
+            width: rgb.width(),
+            height: rgb.height(),
+            pixels: rgb.pixels().map(|p| [p[0], p[1], p[2]]).collect(),
+        }
+    }
+
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (x * 2 + dx).min(self.width - 1);
+                    let sy = (y * 2 + dy).min(self.height - 1);
+                    let pixel = self.pixels[(sy * self.width + sx) as usize];
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as u32;
+                    }
+                }
+                pixels.push([(sum[0] / 4) as u8, (sum[1] / 4) as u8, (sum[2] / 4) as u8]);
+            }
+        }
+
+        MipLevel { width, height, pixels }
+    }
+
+    fn sample(&self, u: f32, v: f32) -> [u8; 3] {
+        let x = (u.rem_euclid(1.0) * self.width as f32) as u32 % self.width;
+        let y = ((1.0 - v.rem_euclid(1.0)) * self.height as f32) as u32 % self.height;
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+fn build_mips(image: &DynamicImage) -> Vec<MipLevel> {
+    let mut mips = vec![MipLevel::from_image(image)];
+    while let Some(last) = mips.last() {
+        if last.width == 1 && last.height == 1 {
+            break;
+        }
+        let next = last.downsample();
+        mips.push(next);
+    }
+    mips
+}
+
+// Median cut: the pixels are split into `colors` boxes, each time halving the box with the
+// widest spread in one channel at its median, and every pixel takes the mean of its box.
+// Alpha is kept as it is.
+fn quantize(image: DynamicImage, colors: usize) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let mut boxes: Vec<Vec<[u8; 3]>> = vec![rgba.pixels().map(|p| [p[0], p[1], p[2]]).collect()];
+    let spread = |pixels: &[[u8; 3]]| {
+        (0..3)
+            .map(|c| {
+                let (low, high) = pixels.iter().fold((255, 0), |(low, high), p| (p[c].min(low), p[c].max(high)));
+                (high.saturating_sub(low), c)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+    while boxes.len() < colors {
+        let Some((index, (_, channel))) = boxes
+            .iter()
+            .map(|pixels| spread(pixels))
+            .enumerate()
+            .filter(|(_, (range, _))| *range > 0)
+            .max_by_key(|(_, (range, _))| *range)
+        else {
+            break;
+        };
+        let mut pixels = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|p| p[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    let palette: Vec<[u8; 3]> = boxes
+        .iter()
+        .filter(|pixels| !pixels.is_empty())
+        .map(|pixels| {
+            let mut sum = [0u64; 3];
+            for p in pixels {
+                for c in 0..3 {
+                    sum[c] += p[c] as u64;
+                }
+            }
+            sum.map(|total| (total / pixels.len() as u64) as u8)
+        })
+        .collect();
+    for pixel in rgba.pixels_mut() {
+        let distance = |color: &[u8; 3]| (0..3).map(|c| (color[c] as i32 - pixel[c] as i32).pow(2)).sum::<i32>();
+        if let Some(nearest) = palette.iter().min_by_key(|color| distance(color)) {
+            pixel.0 = [nearest[0], nearest[1], nearest[2], pixel[3]];
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+fn modified_time(filename: &str) -> Option<SystemTime> {
+    std::fs::metadata(filename).and_then(|metadata| metadata.modified()).ok()
 }
 
 impl Texture {
-    pub fn new(filename: &str) -> Self {
-        let img = image::open(&Path::new(filename)).expect("Failed to load texture");
-        let (width, height) = img.dimensions();
+    pub fn new(filename: &str) -> Result<Self> {
+        let path = Path::new(filename);
+        if !path.is_file() {
+            let name = path.file_name().map_or_else(|| filename.to_string(), |name| name.to_string_lossy().into_owned());
+            return Err(Error::TextureNotFound { name, path: filename.to_string() });
+        }
+
+        let modified = modified_time(filename);
+        let img = image::open(path).map_err(|source| Error::Texture { path: filename.to_string(), source })?;
+        let texture = Texture {
+            pixels: RwLock::new(Pixels::new(img)),
+            source: Some(filename.to_string()),
+            modified: Mutex::new(modified),
+            max_side: AtomicU32::new(0),
+            preview: Mutex::new(Reduction::default()),
+        };
+        log::info!("Loaded texture {} ({}x{})", filename, texture.width(), texture.height());
+        Ok(texture)
+    }
+
+    pub fn from_image(img: DynamicImage) -> Self {
         Texture {
-            image: img,
-            width,
-            height,
+            pixels: RwLock::new(Pixels::new(img)),
+            source: None,
+            modified: Mutex::new(None),
+            max_side: AtomicU32::new(0),
+            preview: Mutex::new(Reduction::default()),
         }
     }
 
+    pub fn image(&self) -> impl Deref<Target = DynamicImage> + '_ {
+        ImageGuard(self.pixels())
+    }
+
+    fn pixels(&self) -> RwLockReadGuard<'_, Pixels> {
+        self.pixels.read().unwrap()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixels().width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixels().height
+    }
+
+    pub fn memory(&self) -> usize {
+        self.pixels().memory()
+    }
+
+    // Shrinks the image so neither side is over `max_side` pixels, now and whenever the
+    // file is reloaded.
+    pub fn limit_size(&self, max_side: u32) {
+        self.max_side.store(max_side, Ordering::Relaxed);
+        let reduction = self.reduction();
+        let mut pixels = self.pixels.write().unwrap();
+        if pixels.width.max(pixels.height) > max_side {
+            let image = std::mem::take(&mut pixels.image);
+            *pixels = Pixels::reduced(image, reduction);
+        }
+    }
+
+    // Loads the file again cut down by `preview` on top of any budget, or back at full size
+    // for the default. Returns whether the pixels changed; textures not loaded from a file
+    // are left alone.
+    pub fn set_preview(&self, preview: Reduction) -> Result<bool> {
+        let Some(filename) = &self.source else {
+            return Ok(false);
+        };
+        if *self.preview.lock().unwrap() == preview {
+            return Ok(false);
+        }
+        let img = image::open(filename).map_err(|source| Error::Texture { path: filename.clone(), source })?;
+        *self.preview.lock().unwrap() = preview;
+        *self.pixels.write().unwrap() = Pixels::reduced(img, self.reduction());
+        log::debug!("Texture {} now {}x{}", filename, self.width(), self.height());
+        Ok(true)
+    }
+
+    // The budget's and the preview's limits together.
+    fn reduction(&self) -> Reduction {
+        let preview = *self.preview.lock().unwrap();
+        let max_side = match (self.max_side.load(Ordering::Relaxed), preview.max_side) {
+            (0, side) | (side, 0) => side,
+            (a, b) => a.min(b),
+        };
+        Reduction { max_side, colors: preview.colors }
+    }
+
+    // The file the texture was loaded from, if any.
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    // Swaps in the pixels of `other`, e.g. to refresh a texture derived from a reloaded one.
+    pub fn replace(&self, other: Texture) {
+        let pixels = other.pixels.into_inner().unwrap();
+        *self.pixels.write().unwrap() = pixels;
+    }
+
+    // Loads the file again if it changed on disk since it was last read. A failed load keeps
+    // the old pixels until the file changes again, e.g. when an editor finishes saving it.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let Some(filename) = &self.source else {
+            return Ok(false);
+        };
+        let modified = modified_time(filename);
+        {
+            let mut last = self.modified.lock().unwrap();
+            if modified.is_none() || modified == *last {
+                return Ok(false);
+            }
+            *last = modified;
+        }
+
+        let img = image::open(filename).map_err(|source| Error::Texture { path: filename.clone(), source })?;
+        *self.pixels.write().unwrap() = Pixels::reduced(img, self.reduction());
+        log::info!("Reloaded texture {} ({}x{})", filename, self.width(), self.height());
+        Ok(true)
+    }
+
+    // New texture with `f` applied to every RGBA pixel of the full-resolution image.
+    pub fn map_pixels(&self, f: impl Fn([u8; 4]) -> [u8; 4]) -> Texture {
+        let mut rgba = self.image().to_rgba8();
+        for pixel in rgba.pixels_mut() {
+            pixel.0 = f(pixel.0);
+        }
+        Texture::from_image(DynamicImage::ImageRgba8(rgba))
+    }
+
     pub fn get_color(&self, u: f32, v: f32) -> [u8; 3] {
-        let u = u.fract();
-        let v = v.fract();
+        let pixels = self.pixels();
+        let u = u.rem_euclid(1.0);
+        let v = v.rem_euclid(1.0);
 
-        let x = (u * self.width as f32) as u32 % self.width;
-        let y = ((1.0 - v) * self.height as f32) as u32 % self.height;
+        let x = (u * pixels.width as f32) as u32 % pixels.width;
+        let y = ((1.0 - v) * pixels.height as f32) as u32 % pixels.height;
 
-        let pixel = self.image.get_pixel(x, y);
+        let pixel = pixels.image.get_pixel(x, y);
         [pixel[0], pixel[1], pixel[2]]
     }
+
+    pub fn get_rgba(&self, u: f32, v: f32) -> [u8; 4] {
+        let pixels = self.pixels();
+        let x = (u.rem_euclid(1.0) * pixels.width as f32) as u32 % pixels.width;
+        let y = ((1.0 - v.rem_euclid(1.0)) * pixels.height as f32) as u32 % pixels.height;
+        pixels.image.get_pixel(x, y).0
+    }
+
+    // Level of detail for a surface footprint given in UV units (1.0 covers the whole texture).
+    pub fn lod_for_footprint(&self, footprint: f32) -> f32 {
+        (footprint * self.width().max(self.height()) as f32).max(1e-6).log2().max(0.0)
+    }
+
+    // Mean colour of the whole image, i.e. the 1x1 mip level.
+    pub fn average_color(&self) -> [u8; 3] {
+        self.pixels().mips.last().map_or([0, 0, 0], |level| level.pixels[0])
+    }
+
+    pub fn get_color_lod(&self, u: f32, v: f32, lod: f32) -> [u8; 3] {
+        if lod <= 0.0 {
+            return self.get_color(u, v);
+        }
+
+        let pixels = self.pixels();
+        let mips = &pixels.mips;
+        let max_level = (mips.len() - 1) as f32;
+        let lod = lod.min(max_level);
+        let lower = lod.floor() as usize;
+        let upper = (lower + 1).min(mips.len() - 1);
+        let t = lod - lower as f32;
+
+        let a = mips[lower].sample(u, v);
+        let b = mips[upper].sample(u, v);
+        [
+            (a[0] as f32 + (b[0] as f32 - a[0] as f32) * t) as u8,
+            (a[1] as f32 + (b[1] as f32 - a[1] as f32) * t) as u8,
+            (a[2] as f32 + (b[2] as f32 - a[2] as f32) * t) as u8,
+        ]
+    }
+}
+
+// Halves the largest of `textures` until together they take at most `budget` bytes, or
+// every one is down to a single pixel.
+pub fn fit_budget(textures: &[Arc<Texture>], budget: usize) {
+    while textures.iter().map(|texture| texture.memory()).sum::<usize>() > budget {
+        let Some(largest) = textures.iter().max_by_key(|texture| texture.width().max(texture.height())) else {
+            return;
+        };
+        let side = largest.width().max(largest.height());
+        if side <= 1 {
+            return;
+        }
+        largest.limit_size(side / 2);
+        log::info!(
+            "Shrank texture {} to {}x{} to fit the texture budget",
+            largest.source().unwrap_or("(generated)"),
+            largest.width(),
+            largest.height()
+        );
+    }
+}
+
+// Polls the files behind a set of textures and reloads the ones that changed.
+pub struct TextureWatcher {
+    textures: Vec<Arc<Texture>>,
+    last_poll: Instant,
+}
+
+impl TextureWatcher {
+    pub fn new(textures: Vec<Arc<Texture>>) -> Self {
+        TextureWatcher {
+            textures,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Returns the textures reloaded since the last poll; files are checked at most every
+    // `WATCH_INTERVAL`.
+    pub fn poll(&mut self) -> Vec<Arc<Texture>> {
+        if self.last_poll.elapsed() < WATCH_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        self.textures
+            .iter()
+            .filter(|texture| match texture.reload_if_changed() {
+                Ok(reloaded) => reloaded,
+                Err(err) => {
+                    log::warn!("{}", err);
+                    false
+                }
+            })
+            .cloned()
+            .collect()
+    }
 }
@@ -0,0 +1,112 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashSet;
+use crate::cube::Cube;
+use crate::material::Material;
+use crate::obb::Obb;
+use crate::ray_intersect::{Intersect, RayIntersect};
+
+const GRAVITY: f32 = 9.8;
+// Share of speed kept across a bounce, and of sliding speed kept per second on the ground.
+const RESTITUTION: f32 = 0.3;
+const GROUND_FRICTION: f32 = 0.05;
+// Share of spin kept per second.
+const SPIN_DAMPING: f32 = 0.3;
+// Below this speed on solid ground the block stops and takes its place in the grid.
+const REST_SPEED: f32 = 0.6;
+// A block that falls this far below every solid cell is lost.
+const FLOOR_DEPTH: f32 = 16.0;
+
+// A block flying from a throw. It spins about the vertical axis as it goes, collides with
+// the grid of solid blocks as an upright unit box, and once it rests on something it is
+// turned into an ordinary block in the cell it came to. One that falls out of the world is
+// lost instead: it stays in the scene's objects, so no indices move, but is no longer seen.
+pub struct ThrownBlock {
+    pub velocity: Vec3,
+    // Radians per second about the vertical axis.
+    pub spin: f32,
+    shape: Obb,
+    grounded: bool,
+    lost: bool,
+}
+
+impl ThrownBlock {
+    pub fn new(center: Vec3, velocity: Vec3, spin: f32, material: Material) -> Self {
+        ThrownBlock {
+            velocity,
+            spin,
+            shape: Obb::new(center, Vec3::repeat(0.5), 0.0, material),
+            grounded: false,
+            lost: false,
+        }
+    }
+
+    pub fn is_flying(&self) -> bool {
+        !self.lost
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.shape.center
+    }
+
+    // Moves the block `dt` seconds on, against the `solid` cells.
+    pub fn update(&mut self, solid: &HashSet<[i32; 3]>, dt: f32) {
+        if self.lost {
+            return;
+        }
+        self.velocity.y -= GRAVITY * dt;
+        self.grounded = false;
+        // One axis at a time, so a block hitting a wall still falls and one landing still slides.
+        for axis in [1, 0, 2] {
+            let step = self.velocity[axis] * dt;
+            self.shape.center[axis] += step;
+            if overlaps(&self.shape.center, solid) {
+                self.shape.center[axis] -= step;
+                if axis == 1 && self.velocity.y < 0.0 {
+                    self.grounded = true;
+                }
+                self.velocity[axis] = -self.velocity[axis] * RESTITUTION;
+            }
+        }
+        let lowest = solid.iter().map(|cell| cell[1]).min().unwrap_or(0) as f32;
+        if self.shape.center.y < lowest - FLOOR_DEPTH {
+            log::debug!("A thrown block fell out of the world at {:?}", self.shape.center);
+            self.lost = true;
+            return;
+        }
+        if self.grounded {
+            let friction = GROUND_FRICTION.powf(dt);
+            self.velocity.x *= friction;
+            self.velocity.z *= friction;
+        }
+        self.spin *= SPIN_DAMPING.powf(dt);
+        self.shape.yaw += self.spin * dt;
+    }
+
+    // The block it becomes in the nearest free cell once it has come to rest on something.
+    pub fn settled(&self, solid: &HashSet<[i32; 3]>) -> Option<Cube> {
+        if self.lost || !self.grounded || self.velocity.norm() > REST_SPEED {
+            return None;
+        }
+        let mut cell = self.shape.center.map(|c| c.round() as i32);
+        while solid.contains(&[cell.x, cell.y, cell.z]) {
+            cell.y += 1;
+        }
+        let center = Vec3::new(cell.x as f32, cell.y as f32, cell.z as f32);
+        Some(Cube { center, size: 1.0, material: self.shape.material.clone() })
+    }
+}
+
+// Whether a unit box at `center` reaches into any of the `solid` cells.
+fn overlaps(center: &Vec3, solid: &HashSet<[i32; 3]>) -> bool {
+    let (min, max) = (center.add_scalar(-0.499).map(|c| c.round() as i32), center.add_scalar(0.499).map(|c| c.round() as i32));
+    (min.x..=max.x).any(|x| (min.y..=max.y).any(|y| (min.z..=max.z).any(|z| solid.contains(&[x, y, z]))))
+}
+
+impl RayIntersect for ThrownBlock {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        if self.lost {
+            return Intersect::empty();
+        }
+        self.shape.ray_intersect(ray_origin, ray_direction)
+    }
+}
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::rng::Rng;
+
+// Spreads the tiles of a frame over as many displayed frames as it takes to trace them, with
+// a fixed time budget per frame, so a costly scene slows how fast the picture fills in rather
+// than how fast input is handled. Tiles come in a shuffled order so the whole image updates
+// evenly instead of sweeping down the screen.
+pub struct TimeSlicer {
+    pub budget: Duration,
+    tile_size: usize,
+    // The picture as far as it has been traced; unfinished tiles still show older frames.
+    image: Framebuffer,
+    tiles: Vec<Rect>,
+    next: usize,
+}
+
+impl TimeSlicer {
+    pub fn new(budget: Duration, tile_size: usize) -> Self {
+        TimeSlicer {
+            budget,
+            tile_size: tile_size.max(1),
+            image: Framebuffer::new(0, 0),
+            tiles: Vec::new(),
+            next: 0,
+        }
+    }
+
+    // Has `render` trace tiles into the kept picture until the budget is spent, always at
+    // least one, then copies the picture into `frame`. Returns true when this finished a pass
+    // over the whole frame.
+    pub fn render(&mut self, frame: &mut Framebuffer, mut render: impl FnMut(&mut Framebuffer, Rect)) -> bool {
+        if (self.image.width, self.image.height) != (frame.width, frame.height) {
+            self.layout(frame);
+        }
+
+        let start = Instant::now();
+        let mut finished = false;
+        while !self.tiles.is_empty() {
+            render(&mut self.image, self.tiles[self.next]);
+            self.next += 1;
+            if self.next == self.tiles.len() {
+                self.next = 0;
+                finished = true;
+                break;
+            }
+            if start.elapsed() >= self.budget {
+                break;
+            }
+        }
+        frame.blit(&self.image, frame.bounds());
+        finished
+    }
+
+    // Starts over on a frame of a new size, from whatever it currently shows.
+    fn layout(&mut self, frame: &Framebuffer) {
+        let (width, height, tile) = (frame.width, frame.height, self.tile_size);
+        self.image = Framebuffer::new(width, height);
+        self.image.blit(frame, frame.bounds());
+        self.next = 0;
+        self.tiles = (0..height.div_ceil(tile))
+            .flat_map(|row| (0..width.div_ceil(tile)).map(move |column| (column, row)))
+            .map(|(column, row)| Rect::new(column * tile, row * tile, tile, tile).clipped(width, height))
+            .collect();
+
+        // Fisher-Yates with a fixed seed, so every pass visits tiles in the same order.
+        let mut rng = Rng::new(0x7153);
+        for i in (1..self.tiles.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            self.tiles.swap(i, j);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufWriter;
+use crate::camera::CameraPose;
+use crate::error::{Error, Result};
+use crate::screenshot::RenderMetadata;
+use crate::season::Weather;
+
+// Values keyed over time, in seconds from the start of a shot: the camera, the time of day,
+// the weather and where objects are. Every track is optional. Between keys values are
+// blended linearly, except the weather, which holds until its next key; past either end a
+// track holds its first or last value. `start` is everything else needed to render the shot
+// again, recorded when the timeline was.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeline {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<RenderMetadata>,
+    pub camera: Vec<Keyframe<CameraKey>>,
+    // Hours; keys past 24 carry on into the next day, so a shot can run through midnight.
+    pub time_of_day: Vec<Keyframe<f32>>,
+    pub weather: Vec<Keyframe<Weather>>,
+    pub snow_depth: Vec<Keyframe<f32>>,
+    pub objects: Vec<ObjectTrack>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CameraKey {
+    pub eye: [f32; 3],
+    pub center: [f32; 3],
+    pub up: [f32; 3],
+    pub fov_degrees: f32,
+}
+
+impl From<CameraPose> for CameraKey {
+    fn from(pose: CameraPose) -> Self {
+        CameraKey {
+            eye: pose.eye.into(),
+            center: pose.center.into(),
+            up: pose.up.into(),
+            fov_degrees: pose.fov.to_degrees(),
+        }
+    }
+}
+
+impl From<CameraKey> for CameraPose {
+    fn from(key: CameraKey) -> Self {
+        CameraPose {
+            eye: key.eye.into(),
+            center: key.center.into(),
+            up: Vec3::from(key.up).normalize(),
+            fov: key.fov_degrees.to_radians(),
+        }
+    }
+}
+
+// Where the object at `index` in the scene's list is centred over time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectTrack {
+    pub index: usize,
+    pub keys: Vec<Keyframe<[f32; 3]>>,
+}
+
+// The timeline's values at one moment; None for tracks without keys.
+pub struct TimelineState {
+    pub camera: Option<CameraPose>,
+    pub time_of_day: Option<f32>,
+    pub weather: Option<Weather>,
+    pub snow_depth: Option<f32>,
+    pub objects: Vec<(usize, Vec3)>,
+}
+
+trait Blend: Clone {
+    fn blend(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Blend for f32 {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Blend for [f32; 3] {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        [self[0].blend(&other[0], t), self[1].blend(&other[1], t), self[2].blend(&other[2], t)]
+    }
+}
+
+impl Blend for CameraKey {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        CameraKey {
+            eye: self.eye.blend(&other.eye, t),
+            center: self.center.blend(&other.center, t),
+            up: self.up.blend(&other.up, t),
+            fov_degrees: self.fov_degrees.blend(&other.fov_degrees, t),
+        }
+    }
+}
+
+impl Blend for Weather {
+    fn blend(&self, _other: &Self, _t: f32) -> Self {
+        *self
+    }
+}
+
+// The track's value at `time`. Keys must be sorted by time.
+fn sample<T: Blend>(keys: &[Keyframe<T>], time: f32) -> Option<T> {
+    let next = keys.iter().position(|key| key.time > time);
+    match next {
+        None => keys.last().map(|key| key.value.clone()),
+        Some(0) => Some(keys[0].value.clone()),
+        Some(next) => {
+            let (a, b) = (&keys[next - 1], &keys[next]);
+            let t = (time - a.time) / (b.time - a.time);
+            Some(a.value.blend(&b.value, t))
+        }
+    }
+}
+
+fn sort<T>(keys: &mut [Keyframe<T>]) {
+    keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+}
+
+impl Timeline {
+    pub fn load(filename: &str) -> Result<Self> {
+        let file = File::open(filename).map_err(|source| Error::ImportIo { path: filename.to_string(), source })?;
+        let mut timeline: Timeline = serde_json::from_reader(file)
+            .map_err(|err| Error::Import { path: filename.to_string(), reason: err.to_string() })?;
+        sort(&mut timeline.camera);
+        sort(&mut timeline.time_of_day);
+        sort(&mut timeline.weather);
+        sort(&mut timeline.snow_depth);
+        for track in &mut timeline.objects {
+            sort(&mut track.keys);
+        }
+        Ok(timeline)
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let export_error = |source| Error::Export { path: filename.to_string(), source };
+        let file = File::create(filename).map_err(export_error)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self).map_err(|err| export_error(err.into()))
+    }
+
+    // Seconds until the last key of any track.
+    pub fn duration(&self) -> f32 {
+        let last = |times: &mut dyn Iterator<Item = f32>| times.fold(0.0, f32::max);
+        [
+            last(&mut self.camera.iter().map(|key| key.time)),
+            last(&mut self.time_of_day.iter().map(|key| key.time)),
+            last(&mut self.weather.iter().map(|key| key.time)),
+            last(&mut self.snow_depth.iter().map(|key| key.time)),
+            last(&mut self.objects.iter().flat_map(|track| track.keys.iter().map(|key| key.time))),
+        ]
+        .into_iter()
+        .fold(0.0, f32::max)
+    }
+
+    pub fn at(&self, time: f32) -> TimelineState {
+        TimelineState {
+            camera: sample(&self.camera, time).map(CameraPose::from),
+            time_of_day: sample(&self.time_of_day, time).map(|hours| hours.rem_euclid(24.0)),
+            weather: sample(&self.weather, time),
+            snow_depth: sample(&self.snow_depth, time),
+            objects: self
+                .objects
+                .iter()
+                .filter_map(|track| sample(&track.keys, time).map(|center| (track.index, Vec3::from(center))))
+                .collect(),
+        }
+    }
+}
+
+// Keys the interactive session into a timeline every `interval` seconds of play.
+pub struct Recorder {
+    timeline: Timeline,
+    interval: f32,
+    elapsed: f32,
+    next_key: f32,
+}
+
+impl Recorder {
+    pub fn new(start: RenderMetadata, interval: f32) -> Self {
+        Recorder {
+            timeline: Timeline { start: Some(start), ..Timeline::default() },
+            interval: interval.max(1e-3),
+            elapsed: 0.0,
+            next_key: 0.0,
+        }
+    }
+
+    // Moves the recording on by `dt` and keys what the scene shows if a key is due.
+    pub fn update(&mut self, dt: f32, pose: CameraPose, hours: f32, weather: Weather, snow_depth: f32) {
+        if self.elapsed >= self.next_key {
+            let time = self.elapsed;
+            // Unwrap the clock past midnight so blending between keys never runs backwards.
+            let hours = match self.timeline.time_of_day.last() {
+                Some(last) => hours + 24.0 * ((last.value - hours) / 24.0).round(),
+                None => hours,
+            };
+            self.timeline.camera.push(Keyframe { time, value: pose.into() });
+            self.timeline.time_of_day.push(Keyframe { time, value: hours });
+            self.timeline.snow_depth.push(Keyframe { time, value: snow_depth });
+            if self.timeline.weather.last().is_none_or(|last| last.value != weather) {
+                self.timeline.weather.push(Keyframe { time, value: weather });
+            }
+            self.next_key += self.interval;
+        }
+        self.elapsed += dt;
+    }
+
+    pub fn finish(self) -> Timeline {
+        self.timeline
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::error::{Error, Result};
+use crate::material::Material;
+
+const MAX_SIZE: i32 = 256;
+const MAX_COLORS: usize = 255;
+
+#[derive(Debug, Default)]
+pub struct VoxStats {
+    pub voxels: usize,
+    pub colors: usize,
+    pub size: [i32; 3],
+}
+
+// Flat colour a material contributes to a voxel palette.
+fn voxel_color(material: &Material) -> Color {
+    match &material.texture {
+        Some(texture) => {
+            let [r, g, b] = texture.average_color();
+            let tint = material.tint.to_vec3() / 255.0;
+            Color::from_vec3(Color::new(r, g, b).to_vec3().component_mul(&tint))
+        }
+        None => material.diffuse,
+    }
+}
+
+// Writes the unit cubes on the block grid as a MagicaVoxel .vox model. MagicaVoxel is
+// z-up, so the scene's y axis becomes z; the model's minimum corner maps to the origin.
+pub fn export_vox(filename: &str, cubes: &[&Cube]) -> Result<VoxStats> {
+    let cells: Vec<([i32; 3], Color)> = cubes
+        .iter()
+        .filter_map(|cube| cube.grid_cell().map(|cell| (cell, voxel_color(&cube.material))))
+        .collect();
+    if cells.is_empty() {
+        return Err(Error::Unexportable { path: filename.to_string(), reason: String::from("no grid-aligned cubes") });
+    }
+
+    let min: [i32; 3] = std::array::from_fn(|axis| cells.iter().map(|(cell, _)| cell[axis]).min().unwrap());
+    let max: [i32; 3] = std::array::from_fn(|axis| cells.iter().map(|(cell, _)| cell[axis]).max().unwrap());
+    let size = [max[0] - min[0] + 1, max[2] - min[2] + 1, max[1] - min[1] + 1];
+    if size.iter().any(|&extent| extent > MAX_SIZE) {
+        let reason = format!("scene is {:?} voxels, .vox models are limited to 256 per axis", size);
+        return Err(Error::Unexportable { path: filename.to_string(), reason });
+    }
+
+    let mut palette: Vec<Color> = Vec::new();
+    let mut index_of: HashMap<u32, u8> = HashMap::new();
+    let mut voxels: HashMap<[u8; 3], u8> = HashMap::new();
+    for (cell, color) in &cells {
+        let index = match index_of.get(&color.to_hex()) {
+            Some(&index) => index,
+            None if palette.len() < MAX_COLORS => {
+                palette.push(*color);
+                let index = palette.len() as u8;
+                index_of.insert(color.to_hex(), index);
+                index
+            }
+            None => nearest(&palette, color),
+        };
+        let x = (cell[0] - min[0]) as u8;
+        let y = (cell[2] - min[2]) as u8;
+        let z = (cell[1] - min[1]) as u8;
+        voxels.insert([x, y, z], index);
+    }
+
+    let mut size_chunk = Vec::new();
+    for extent in size {
+        size_chunk.extend_from_slice(&extent.to_le_bytes());
+    }
+
+    let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+    let mut sorted: Vec<_> = voxels.iter().collect();
+    sorted.sort();
+    for ([x, y, z], index) in sorted {
+        xyzi.extend_from_slice(&[*x, *y, *z, *index]);
+    }
+
+    let mut rgba = Vec::with_capacity(256 * 4);
+    for slot in 0..256 {
+        let hex = palette.get(slot).map_or(0, |color| color.to_hex());
+        rgba.extend_from_slice(&[(hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255]);
+    }
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &size_chunk, &[]);
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    write_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+    let mut file = b"VOX ".to_vec();
+    file.extend_from_slice(&150u32.to_le_bytes());
+    write_chunk(&mut file, b"MAIN", &[], &children);
+
+    std::fs::write(filename, file).map_err(|source| Error::Export { path: filename.to_string(), source })?;
+    Ok(VoxStats { voxels: voxels.len(), colors: palette.len(), size })
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+}
+
+fn nearest(palette: &[Color], color: &Color) -> u8 {
+    let target = color.to_vec3();
+    let (index, _) = palette
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| (index, (candidate.to_vec3() - target).norm_squared()))
+        .fold((0, f32::INFINITY), |best, entry| if entry.1 < best.1 { entry } else { best });
+    index as u8 + 1
+}
@@ -0,0 +1,128 @@
+use nalgebra_glm::Vec3;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::projection::Ray;
+use crate::ray_intersect::Intersect;
+use crate::scene::Object;
+
+// Shown instead of a pixel whose shading went wrong, bright enough to spot at a glance.
+pub const MAGENTA: Vec3 = Vec3::new(255.0, 0.0, 255.0);
+// Bad samples described in the log; the rest are only counted.
+const MAX_REPORTS: usize = 5;
+
+thread_local! {
+    // Whether shading on this thread met a value that was not finite since the sample was
+    // last checked. Colours are rounded to 8 bits as they are shaded, which turns NaN into
+    // black, so the final colour alone cannot tell.
+    static TAINTED: Cell<bool> = const { Cell::new(false) };
+}
+
+// Marks the sample being shaded on this thread as bad if any of `values` is not finite.
+pub fn watch(values: &[f32]) {
+    if values.iter().any(|value| !value.is_finite()) {
+        TAINTED.with(|tainted| tainted.set(true));
+    }
+}
+
+// Whether shading on this thread went bad since the last call, clearing the mark.
+pub fn take_taint() -> bool {
+    TAINTED.with(|tainted| tainted.replace(false))
+}
+
+// Debug aid that catches primary rays whose shading comes out NaN or infinite, or that hit
+// with a broken normal or distance, which the conversion to 8 bits would otherwise hide as
+// black or white pixels. Each one is shown in magenta, and the first few are logged with
+// the ray and what it hit so they can be tracked down.
+#[derive(Default)]
+pub struct Watchdog {
+    found: AtomicUsize,
+}
+
+impl Watchdog {
+    // Starts describing bad samples in the log again, e.g. when switched back on.
+    pub fn reset(&self) {
+        self.found.store(0, Ordering::Relaxed);
+    }
+
+    // Bad samples seen since the last reset.
+    pub fn found(&self) -> usize {
+        self.found.load(Ordering::Relaxed)
+    }
+
+    // The colour to show for a primary ray: `color`, or magenta when it or the hit is not
+    // finite. `intersect` is None for rays that reached the sky.
+    pub fn check(&self, ray: &Ray, intersect: Option<(&Intersect, Option<&Object>)>, color: Vec3) -> Vec3 {
+        let problem = if !finite(&ray.direction) || ray.direction.norm() == 0.0 {
+            Some("ray direction")
+        } else if !finite(&color) {
+            Some("colour")
+        } else if take_taint() {
+            Some("shading value")
+        } else {
+            intersect.and_then(|(intersect, _)| {
+                if !intersect.distance.is_finite() {
+                    Some("hit distance")
+                } else if !finite(&intersect.point) {
+                    Some("hit point")
+                } else if !finite(&intersect.normal) || intersect.normal.norm() < 1e-6 {
+                    Some("normal")
+                } else {
+                    None
+                }
+            })
+        };
+        let Some(problem) = problem else {
+            return color;
+        };
+
+        let count = self.found.fetch_add(1, Ordering::Relaxed) + 1;
+        if count <= MAX_REPORTS {
+            let hit = match intersect {
+                Some((intersect, object)) => format!(
+                    "hit {} at distance {} point {} normal {}",
+                    describe(object),
+                    intersect.distance,
+                    format_vec3(&intersect.point),
+                    format_vec3(&intersect.normal)
+                ),
+                None => String::from("no hit"),
+            };
+            log::warn!(
+                "Bad {} ({}/{}): ray from {} towards {}, {}, colour {}",
+                problem,
+                count,
+                MAX_REPORTS,
+                format_vec3(&ray.origin),
+                format_vec3(&ray.direction),
+                hit,
+                format_vec3(&color)
+            );
+            if count == MAX_REPORTS {
+                log::warn!("Further bad pixels are shown in magenta without being logged");
+            }
+        }
+        MAGENTA
+    }
+}
+
+fn finite(v: &Vec3) -> bool {
+    v.iter().all(|component| component.is_finite())
+}
+
+fn format_vec3(v: &Vec3) -> String {
+    format!("({}, {}, {})", v.x, v.y, v.z)
+}
+
+fn describe(object: Option<&Object>) -> &'static str {
+    match object {
+        Some(Object::Cube(_, true)) => "the sun",
+        Some(Object::Cube(_, false)) => "a cube",
+        Some(Object::Portal(_)) => "a portal",
+        Some(Object::Csg(_)) => "a carved shape",
+        Some(Object::Billboard(_)) => "a billboard",
+        Some(Object::Door(_)) => "a door",
+        Some(Object::Wall(_)) => "the border wall",
+        Some(Object::Thrown(_)) => "a thrown block",
+        None => "nothing",
+    }
+}
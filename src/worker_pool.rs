@@ -0,0 +1,164 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+// A job as workers see it. The borrow it was made from is erased so it can sit in the shared
+// state; `run` keeps that borrow alive until every worker is done with it.
+#[derive(Clone, Copy)]
+struct Job(*const (dyn Fn(usize) + Sync));
+
+// Safe to hand to workers because the closure behind it is Sync.
+unsafe impl Send for Job {}
+
+struct State {
+    job: Option<Job>,
+    count: usize,
+    // Bumped for every batch, so a worker never picks the same one up twice.
+    batch: u64,
+    // Workers that have not finished the current batch yet.
+    busy: usize,
+    panicked: bool,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    // Signalled when a batch is posted or the pool shuts down.
+    posted: Condvar,
+    // Signalled when the last worker finishes a batch.
+    finished: Condvar,
+    next: AtomicUsize,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Runs jobs until the batch has none left; returns false if one of them panicked.
+    fn drain(&self, job: &(dyn Fn(usize) + Sync), count: usize) -> bool {
+        panic::catch_unwind(AssertUnwindSafe(|| loop {
+            let index = self.next.fetch_add(1, Ordering::Relaxed);
+            if index >= count {
+                break;
+            }
+            job(index);
+        }))
+        .is_ok()
+    }
+}
+
+// Threads started once and kept for the whole run, so a frame only posts its jobs instead
+// of paying for thread startup. The calling thread works through the batch alongside them.
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    // `threads` counts the calling thread; 0 uses every core.
+    pub fn new(threads: usize) -> Self {
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, |cores| cores.get()),
+            threads => threads,
+        };
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { job: None, count: 0, batch: 0, busy: 0, panicked: false, shutdown: false }),
+            posted: Condvar::new(),
+            finished: Condvar::new(),
+            next: AtomicUsize::new(0),
+        });
+        let workers = (1..threads)
+            .map(|index| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("render-{}", index))
+                    .spawn(move || work(&shared))
+                    .expect("failed to start a render thread")
+            })
+            .collect();
+        log::info!("Rendering on {} threads", threads);
+        WorkerPool { shared, workers }
+    }
+
+    // Calls `job` once for every index below `count`, spread over the pool, and returns when
+    // all calls have. A panic in any of them is raised again here.
+    pub fn run(&self, count: usize, job: &(dyn Fn(usize) + Sync)) {
+        if count == 0 {
+            return;
+        }
+        if self.workers.is_empty() {
+            (0..count).for_each(job);
+            return;
+        }
+
+        // Erases the borrow's lifetime; it outlives the batch because this waits below.
+        let erased = Job(unsafe {
+            std::mem::transmute::<*const (dyn Fn(usize) + Sync + '_), *const (dyn Fn(usize) + Sync + 'static)>(job)
+        });
+        {
+            let mut state = self.shared.state();
+            self.shared.next.store(0, Ordering::Relaxed);
+            state.job = Some(erased);
+            state.count = count;
+            state.batch += 1;
+            state.busy = self.workers.len();
+            state.panicked = false;
+        }
+        self.shared.posted.notify_all();
+
+        let completed = self.shared.drain(job, count);
+
+        let mut state = self.shared.state();
+        while state.busy > 0 {
+            state = self.shared.finished.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        state.job = None;
+        if !completed || state.panicked {
+            drop(state);
+            panic!("a render job panicked");
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.state().shutdown = true;
+        self.shared.posted.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    let mut seen = 0;
+    loop {
+        let (job, count) = {
+            let mut state = shared.state();
+            while !state.shutdown && state.batch == seen {
+                state = shared.posted.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            if state.shutdown {
+                return;
+            }
+            seen = state.batch;
+            match state.job {
+                Some(job) => (job, state.count),
+                None => continue,
+            }
+        };
+
+        // The job stays valid until `busy` reaches zero, which cannot happen before this
+        // thread reports in below.
+        let completed = shared.drain(unsafe { &*job.0 }, count);
+
+        let mut state = shared.state();
+        state.panicked |= !completed;
+        state.busy -= 1;
+        if state.busy == 0 {
+            shared.finished.notify_one();
+        }
+    }
+}